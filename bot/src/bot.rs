@@ -0,0 +1,316 @@
+use std::time::Instant;
+
+use bevy::math::{Quat, Vec3};
+use uflow::client::{Event::*, ErrorType};
+
+use common::PHYSICS_TIMESTEP;
+use common::batching::OutgoingBatches;
+use common::channels::Channel;
+use common::compact_transform::CompactTransform;
+use common::construct_access::{ConstructAccess, ConstructAccessChanged};
+use common::construct_replication::ConstructStates;
+use common::missile::SpawnMissileRequest;
+use common::network_id::NetworkId;
+use common::part::PartId;
+use common::part::events::{DeletePartCommand, DeletePartRequest, PlacePartCommand, PlacePartRequest};
+use common::player::{PlayerId, PlayerName};
+use common::player_connection::{InitialState, InitialStateChunk, InitialStateLoaded};
+use common::player_movement::{PlayerInput, PlayerStates};
+use common::protocol::{ClientHello, ConnectionRejected, Handshake};
+use common::transport::ClientTransport;
+use packets::{Packet, PacketType};
+use packets::batch::Unbatcher;
+
+use crate::stats::{BotStats, ServerTickObserver};
+
+// Parts are placed on a grid of this many metres either side of the construct's origin,
+// which keeps them within MAX_BUILD_DISTANCE of where players spawn
+const BUILD_RADIUS: i32 = 3;
+// Missiles are fired at the construct from this far away
+const MISSILE_DISTANCE: f32 = 20.0;
+const MISSILE_SPEED: f32 = 50.0;
+// The predefined parts, see add_hardcoded_parts
+const PART_IDS: [u32; 2] = [0, 1];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BotState {
+    Connecting,
+    LoadingInitialState,
+    InGame,
+    Disconnected(String),
+}
+
+// The construct the bot builds on, as far as the bot knows
+struct Construct {
+    network_id: NetworkId,
+    access: ConstructAccess,
+    parts: Vec<NetworkId>,
+}
+
+// A client without a window that places, deletes and shoots at random
+// It only tracks what it needs to make valid requests, nothing is simulated
+pub struct Bot {
+    name: PlayerName,
+    transport: Box<dyn ClientTransport>,
+    outgoing_batches: OutgoingBatches,
+    rng: fastrand::Rng,
+    // The chance of acting on each tick
+    action_chance: f32,
+    state: BotState,
+    player_id: Option<PlayerId>,
+    construct: Option<Construct>,
+    // Sent back to the server once the last chunk has arrived
+    loaded_chunks: u32,
+    request_seq: u32,
+    input_sequence: u32,
+    server_ticks: ServerTickObserver,
+    stats: BotStats,
+}
+
+impl Bot {
+    pub fn new(name: PlayerName, transport: impl ClientTransport + 'static, actions_per_second: f32, seed: u64) -> Self {
+        Self {
+            name,
+            transport: Box::new(transport),
+            outgoing_batches: OutgoingBatches::default(),
+            rng: fastrand::Rng::with_seed(seed),
+            action_chance: (actions_per_second * PHYSICS_TIMESTEP).min(1.0),
+            state: BotState::Connecting,
+            player_id: None,
+            construct: None,
+            loaded_chunks: 0,
+            request_seq: 0,
+            input_sequence: 0,
+            server_ticks: ServerTickObserver::default(),
+            stats: BotStats::default(),
+        }
+    }
+
+    pub fn name(&self) -> &PlayerName {
+        &self.name
+    }
+
+    pub fn state(&self) -> &BotState {
+        &self.state
+    }
+
+    pub fn rtt_s(&self) -> Option<f64> {
+        self.transport.rtt_s()
+    }
+
+    // Returns the stats since they were last taken
+    pub fn take_stats(&mut self) -> BotStats {
+        std::mem::take(&mut self.stats)
+    }
+
+    // Handles what the server sent, acts if it's time to, and sends the tick's packets
+    pub fn tick(&mut self, now: Instant) {
+        for event in self.transport.step() {
+            match event {
+                Connect => {
+                    // The server expects the handshake before anything else, on its own
+                    let handshake_packet = Packet::from(&Handshake::default());
+                    self.send_datagram((&handshake_packet).into(), Channel::PlayerConnectionEvents);
+
+                    self.send(&ClientHello { name: self.name.clone() }, Channel::PlayerConnectionEvents);
+                    self.state = BotState::LoadingInitialState;
+                },
+                Disconnect => {
+                    if !matches!(self.state, BotState::Disconnected(_)) {
+                        self.state = BotState::Disconnected("Disconnected from server".to_string());
+                    }
+                },
+                Receive(data) => {
+                    self.stats.bytes_received += data.len() as u64;
+                    self.stats.datagrams_received += 1;
+
+                    for packet in Unbatcher::new(data) {
+                        match packet {
+                            Ok(packet) => self.handle_packet(packet, now),
+                            Err(err) => self.state = BotState::Disconnected(format!("Received an invalid datagram: {}", err)),
+                        }
+                    }
+                },
+                Error(error_type) => {
+                    let reason = match error_type {
+                        ErrorType::Timeout => "Connection to server timed out",
+                        ErrorType::Version => "Connection failed: protocol version mismatch",
+                        ErrorType::Config => "Connection failed: invalid endpoint configuration",
+                        ErrorType::ServerFull => "Connection failed: server full",
+                    };
+                    self.state = BotState::Disconnected(reason.to_string());
+                },
+            }
+        }
+
+        if self.state == BotState::InGame {
+            // Real clients send their input every tick, even when standing still
+            self.input_sequence += 1;
+            let input = PlayerInput { sequence: self.input_sequence, impulse: Vec3::ZERO, torque_impulse: Vec3::ZERO };
+            self.send(&input, Channel::PlayerMovement);
+
+            if self.rng.f32() < self.action_chance {
+                self.act();
+            }
+        }
+
+        let batches: Vec<_> = self.outgoing_batches.drain().collect();
+        for (channel, batch) in batches {
+            self.send_datagram(batch, channel);
+        }
+    }
+
+    // The bot stops acting, but has to keep ticking until the server has been told
+    pub fn disconnect(&mut self) {
+        self.state = BotState::Disconnected("Disconnected by the bot".to_string());
+        self.transport.disconnect();
+        self.transport.flush();
+    }
+
+    fn send<'a, T>(&mut self, message: &'a T, channel: Channel)
+    where
+        Packet: From<&'a T>,
+    {
+        self.outgoing_batches.push(channel, &Packet::from(message));
+    }
+
+    fn send_datagram(&mut self, data: Box<[u8]>, channel: Channel) {
+        self.stats.bytes_sent += data.len() as u64;
+        self.stats.datagrams_sent += 1;
+        self.transport.send(data, channel);
+    }
+
+    fn handle_packet(&mut self, packet: Packet, now: Instant) {
+        // Packets the bot has no use for are skipped without being decoded
+        match packet.packet_type() {
+            PacketType::ConnectionRejected => {
+                if let Ok(rejected) = ConnectionRejected::try_from(packet) {
+                    self.state = BotState::Disconnected(format!("Connection rejected by server: {}", rejected.reason));
+                }
+            },
+            PacketType::InitialState => {
+                if let Ok(initial_state) = InitialState::try_from(packet) {
+                    self.player_id = Some(initial_state.player_id);
+                    // Bots only build on the first construct
+                    self.construct = initial_state.constructs.into_iter()
+                        .next()
+                        .map(|(network_id, _, access)| Construct { network_id, access, parts: Vec::new() });
+                }
+            },
+            PacketType::InitialStateChunk => {
+                if let Ok(chunk) = InitialStateChunk::try_from(packet) {
+                    self.loaded_chunks += 1;
+
+                    if let Some(construct) = self.construct.as_mut().filter(|construct| Some(construct.network_id) == chunk.construct_network_id) {
+                        // Parts placed while the chunks were on their way can also be in them
+                        for (_, _, network_id) in chunk.parts.iter() {
+                            if !construct.parts.contains(network_id) {
+                                construct.parts.push(*network_id);
+                            }
+                        }
+                    }
+
+                    if chunk.last {
+                        self.send(&InitialStateLoaded { chunk_count: self.loaded_chunks }, Channel::PartCommands);
+                        self.state = BotState::InGame;
+                    }
+                }
+            },
+            PacketType::PlacePart => {
+                if let Ok(place_part) = PlacePartCommand::try_from(packet) {
+                    if let Some(construct) = self.construct.as_mut().filter(|construct| construct.network_id == place_part.construct_network_id) {
+                        construct.parts.push(place_part.part_network_id);
+                    }
+                }
+            },
+            PacketType::DeletePart => {
+                if let Ok(DeletePartCommand(network_id)) = DeletePartCommand::try_from(packet) {
+                    if let Some(construct) = self.construct.as_mut() {
+                        construct.parts.retain(|part| *part != network_id);
+                    }
+                }
+            },
+            // Only ever sent to the player whose request was rejected
+            PacketType::BuildRequestRejected => self.stats.build_requests_rejected += 1,
+            PacketType::ConstructAccessChanged => {
+                if let Ok(access_changed) = ConstructAccessChanged::try_from(packet) {
+                    if let Some(construct) = self.construct.as_mut().filter(|construct| construct.network_id == access_changed.construct_network_id) {
+                        construct.access = access_changed.access;
+                    }
+                }
+            },
+            PacketType::PlayerStates => {
+                if let Ok(player_states) = PlayerStates::try_from(packet) {
+                    self.server_ticks.observe(player_states.tick, now, &mut self.stats);
+                }
+            },
+            PacketType::ConstructStates => {
+                if let Ok(construct_states) = ConstructStates::try_from(packet) {
+                    self.server_ticks.observe(construct_states.tick, now, &mut self.stats);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn act(&mut self) {
+        let can_build = match (self.player_id, self.construct.as_ref()) {
+            (Some(player_id), Some(construct)) => construct.access.can_build(player_id),
+            _ => false,
+        };
+
+        match self.rng.u8(0..4) {
+            0 | 1 if can_build => self.place_part(),
+            2 if can_build => self.delete_part(),
+            _ => self.fire_missile(),
+        }
+    }
+
+    fn place_part(&mut self) {
+        let Some(construct) = self.construct.as_ref() else { return };
+
+        let coordinate = || self.rng.i32(-BUILD_RADIUS..=BUILD_RADIUS) as f32;
+        let translation = Vec3::new(coordinate(), coordinate(), coordinate());
+
+        self.request_seq += 1;
+        let request = PlacePartRequest {
+            request_seq: self.request_seq,
+            part_id: PartId::from(PART_IDS[self.rng.usize(..PART_IDS.len())]),
+            part_transform: CompactTransform::new(translation, Quat::IDENTITY),
+            construct_network_id: construct.network_id,
+        };
+
+        self.send(&request, Channel::PartCommands);
+        self.stats.parts_placed += 1;
+    }
+
+    fn delete_part(&mut self) {
+        let Some(construct) = self.construct.as_ref() else { return };
+        if construct.parts.is_empty() {
+            return;
+        }
+
+        let part = construct.parts[self.rng.usize(..construct.parts.len())];
+
+        self.request_seq += 1;
+        let request = DeletePartRequest { request_seq: self.request_seq, part_network_id: part };
+
+        self.send(&request, Channel::PartCommands);
+        self.stats.parts_deleted += 1;
+    }
+
+    fn fire_missile(&mut self) {
+        // From a random direction, straight at the construct's origin
+        let direction = Vec3::new(self.rng.f32() - 0.5, self.rng.f32() - 0.5, self.rng.f32() - 0.5)
+            .try_normalize()
+            .unwrap_or(Vec3::Y);
+
+        let request = SpawnMissileRequest {
+            transform: CompactTransform::new(direction * MISSILE_DISTANCE, Quat::IDENTITY),
+            velocity: -direction * MISSILE_SPEED,
+        };
+
+        self.send(&request, Channel::Missile);
+        self.stats.missiles_fired += 1;
+    }
+}
\ No newline at end of file
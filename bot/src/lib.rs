@@ -0,0 +1,2 @@
+pub mod bot;
+pub mod stats;
\ No newline at end of file
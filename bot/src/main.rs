@@ -0,0 +1,219 @@
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use uflow::client::Client;
+use uflow::EndpointConfig;
+
+use common::PHYSICS_TIMESTEP;
+use common::player::PlayerName;
+use ship_designer_bot::bot::{Bot, BotState};
+use ship_designer_bot::stats::{BotStats, Report, TickTimes};
+
+const USAGE: &str = "\
+Connects bots that build and shoot at random, and reports how the server keeps up
+
+Usage: ship-designer-bot [OPTIONS]
+
+Options:
+    --address <ADDRESS>    The server to connect to [default: 127.0.0.1:36756]
+    --bots <COUNT>         How many bots to connect [default: 10]
+    --actions <RATE>       Placements, deletions and missiles per second for each bot [default: 2]
+    --duration <SECONDS>   How long to run for, runs until interrupted if not given
+    --report <SECONDS>     How often to print statistics [default: 5]
+    --seed <SEED>          Seeds the bots' behaviour, each bot adds its index [default: 0]
+    -h, --help             Print this message";
+
+// Bots still connected after disconnecting get this long to tell the server
+const DISCONNECT_TIME: Duration = Duration::from_millis(500);
+
+struct Options {
+    address: String,
+    bots: usize,
+    actions_per_second: f32,
+    duration: Option<Duration>,
+    report_interval: Duration,
+    seed: u64,
+}
+
+fn parse_value<T: std::str::FromStr>(option: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} expects a value", option))?;
+    value.parse().map_err(|_| format!("invalid value {} for {}", value, option))
+}
+
+fn parse_seconds(option: &str, value: Option<String>) -> Result<Duration, String> {
+    let seconds: f32 = parse_value(option, value)?;
+    Duration::try_from_secs_f32(seconds).map_err(|_| format!("invalid value {} for {}", seconds, option))
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        address: "127.0.0.1:36756".to_string(),
+        bots: 10,
+        actions_per_second: 2.0,
+        duration: None,
+        report_interval: Duration::from_secs(5),
+        seed: 0,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--address" => options.address = parse_value(&arg, args.next())?,
+            "--bots" => options.bots = parse_value(&arg, args.next())?,
+            "--actions" => options.actions_per_second = parse_value(&arg, args.next())?,
+            "--duration" => options.duration = Some(parse_seconds(&arg, args.next())?),
+            "--report" => options.report_interval = parse_seconds(&arg, args.next())?,
+            "--seed" => options.seed = parse_value(&arg, args.next())?,
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+
+    if options.bots == 0 {
+        return Err("--bots must be at least 1".to_string());
+    }
+
+    if options.report_interval.is_zero() {
+        return Err("--report must be more than 0".to_string());
+    }
+
+    Ok(options)
+}
+
+fn connect_bots(options: &Options) -> Result<Vec<Bot>, String> {
+    (0..options.bots)
+        .map(|index| {
+            let client_config = uflow::client::Config {
+                endpoint_config: EndpointConfig {
+                    active_timeout_ms: 3600000,
+                    ..Default::default()
+                }
+            };
+
+            let client = Client::connect(options.address.as_str(), client_config)
+                .map_err(|err| format!("failed to connect to {}: {}", options.address, err))?;
+            let name = PlayerName::from(format!("Bot {}", index + 1));
+
+            Ok(Bot::new(name, client, options.actions_per_second, options.seed + index as u64))
+        })
+        .collect()
+}
+
+fn report(bots: &mut [Bot], started: Instant, interval: Duration, tick_times: &TickTimes, totals: &mut BotStats) {
+    let mut stats = BotStats::default();
+    for bot in bots.iter_mut() {
+        stats.add(&bot.take_stats());
+    }
+    totals.add(&stats);
+
+    let in_game: Vec<&Bot> = bots.iter().filter(|bot| *bot.state() == BotState::InGame).collect();
+    let rtts: Vec<f64> = in_game.iter().filter_map(|bot| bot.rtt_s()).collect();
+    let rtt = (!rtts.is_empty()).then(|| Duration::from_secs_f64(rtts.iter().sum::<f64>() / rtts.len() as f64));
+
+    println!("{}", Report {
+        elapsed: started.elapsed(),
+        interval,
+        bots_in_game: in_game.len(),
+        bot_count: bots.len(),
+        stats: &stats,
+        tick_times,
+        rtt,
+    });
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let options = match parse_args(args.into_iter()) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut bots = match connect_bots(&options) {
+        Ok(bots) => bots,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!("Connecting {} bots to {}", bots.len(), options.address);
+
+    let tick_duration = Duration::from_secs_f32(PHYSICS_TIMESTEP);
+    let started = Instant::now();
+    let mut next_tick = started;
+    let mut last_report = started;
+    let mut tick_times = TickTimes::default();
+    let mut totals = BotStats::default();
+    let mut disconnected = vec![false; bots.len()];
+
+    loop {
+        if let Some(duration) = options.duration {
+            if started.elapsed() >= duration {
+                break;
+            }
+        }
+
+        let tick_start = Instant::now();
+        for (bot, disconnected) in bots.iter_mut().zip(disconnected.iter_mut()) {
+            bot.tick(tick_start);
+
+            if let (BotState::Disconnected(reason), false) = (bot.state(), *disconnected) {
+                eprintln!("{}: {}", bot.name(), reason);
+                *disconnected = true;
+            }
+        }
+        tick_times.record(tick_start.elapsed());
+
+        if disconnected.iter().all(|disconnected| *disconnected) {
+            eprintln!("error: every bot has disconnected");
+            return ExitCode::FAILURE;
+        }
+
+        if last_report.elapsed() >= options.report_interval {
+            report(&mut bots, started, last_report.elapsed(), &tick_times, &mut totals);
+            last_report = Instant::now();
+            tick_times = TickTimes::default();
+        }
+
+        // Falling behind is caught up on straight away instead of sleeping
+        next_tick += tick_duration;
+        if let Some(remaining) = next_tick.checked_duration_since(Instant::now()) {
+            std::thread::sleep(remaining);
+        }
+    }
+
+    report(&mut bots, started, last_report.elapsed(), &tick_times, &mut totals);
+
+    let seconds = started.elapsed().as_secs_f64();
+    println!(
+        "Total: sent {:.1} KiB, received {:.1} KiB, placed {}, deleted {}, missiles {}, rejected {} in {:.1}s",
+        totals.bytes_sent as f64 / 1024.0,
+        totals.bytes_received as f64 / 1024.0,
+        totals.parts_placed,
+        totals.parts_deleted,
+        totals.missiles_fired,
+        totals.build_requests_rejected,
+        seconds,
+    );
+
+    for bot in bots.iter_mut() {
+        bot.disconnect();
+    }
+
+    let disconnect_started = Instant::now();
+    while disconnect_started.elapsed() < DISCONNECT_TIME {
+        for bot in bots.iter_mut() {
+            bot.tick(Instant::now());
+        }
+        std::thread::sleep(tick_duration);
+    }
+
+    ExitCode::SUCCESS
+}
\ No newline at end of file
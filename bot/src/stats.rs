@@ -0,0 +1,156 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// What a bot did and saw since its stats were last taken
+// Byte counts are of the datagrams handed to and from the transport, without uflow's own headers
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BotStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub datagrams_sent: u64,
+    pub datagrams_received: u64,
+    pub parts_placed: u64,
+    pub parts_deleted: u64,
+    pub missiles_fired: u64,
+    pub build_requests_rejected: u64,
+    // How far the server's tick advanced, going by the ticks in its snapshots
+    pub server_ticks: u64,
+    // The longest wait between snapshots with a newer tick than the last
+    pub longest_snapshot_gap: Duration,
+}
+
+impl BotStats {
+    pub fn add(&mut self, other: &BotStats) {
+        self.bytes_sent += other.bytes_sent;
+        self.bytes_received += other.bytes_received;
+        self.datagrams_sent += other.datagrams_sent;
+        self.datagrams_received += other.datagrams_received;
+        self.parts_placed += other.parts_placed;
+        self.parts_deleted += other.parts_deleted;
+        self.missiles_fired += other.missiles_fired;
+        self.build_requests_rejected += other.build_requests_rejected;
+        self.server_ticks += other.server_ticks;
+        self.longest_snapshot_gap = self.longest_snapshot_gap.max(other.longest_snapshot_gap);
+    }
+}
+
+// Follows the server's tick through the snapshots it sends
+// Snapshots are unreliable, so older ones that arrive late are ignored
+#[derive(Debug, Default)]
+pub struct ServerTickObserver {
+    newest: Option<(u32, Instant)>,
+}
+
+impl ServerTickObserver {
+    pub fn observe(&mut self, tick: u32, now: Instant, stats: &mut BotStats) {
+        match self.newest {
+            Some((newest_tick, _)) if tick <= newest_tick => {},
+            Some((newest_tick, received_at)) => {
+                stats.server_ticks += (tick - newest_tick) as u64;
+                stats.longest_snapshot_gap = stats.longest_snapshot_gap.max(now - received_at);
+                self.newest = Some((tick, now));
+            },
+            None => self.newest = Some((tick, now)),
+        }
+    }
+}
+
+// How long stepping every bot took, which has to stay well under a tick for the numbers to mean anything
+#[derive(Debug, Default)]
+pub struct TickTimes {
+    total: Duration,
+    longest: Duration,
+    count: u32,
+}
+
+impl TickTimes {
+    pub fn record(&mut self, tick_time: Duration) {
+        self.total += tick_time;
+        self.longest = self.longest.max(tick_time);
+        self.count += 1;
+    }
+
+    pub fn mean(&self) -> Duration {
+        self.total.checked_div(self.count).unwrap_or_default()
+    }
+}
+
+fn kib_per_second(bytes: u64, seconds: f64) -> f64 {
+    bytes as f64 / 1024.0 / seconds
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+// One line of the report, covering the stats of every bot over an interval
+pub struct Report<'a> {
+    pub elapsed: Duration,
+    pub interval: Duration,
+    pub bots_in_game: usize,
+    pub bot_count: usize,
+    pub stats: &'a BotStats,
+    pub tick_times: &'a TickTimes,
+    pub rtt: Option<Duration>,
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.interval.as_secs_f64().max(f64::EPSILON);
+        let stats = self.stats;
+
+        write!(f, "[{:7.1}s] {}/{} bots in game", self.elapsed.as_secs_f64(), self.bots_in_game, self.bot_count)?;
+
+        // Every bot sees the same server ticks, so their average is the server's tick rate
+        if self.bots_in_game > 0 {
+            let server_tick_rate = stats.server_ticks as f64 / self.bots_in_game as f64 / seconds;
+            write!(f, " | server {:.1} ticks/s, longest snapshot gap {:.0} ms", server_tick_rate, milliseconds(stats.longest_snapshot_gap))?;
+        }
+
+        if let Some(rtt) = self.rtt {
+            write!(f, " | rtt {:.0} ms", milliseconds(rtt))?;
+        }
+
+        write!(
+            f,
+            " | up {:.1} KiB/s ({:.0} datagrams/s), down {:.1} KiB/s ({:.0} datagrams/s)",
+            kib_per_second(stats.bytes_sent, seconds),
+            stats.datagrams_sent as f64 / seconds,
+            kib_per_second(stats.bytes_received, seconds),
+            stats.datagrams_received as f64 / seconds,
+        )?;
+
+        write!(
+            f,
+            " | placed {}, deleted {}, missiles {}, rejected {}",
+            stats.parts_placed,
+            stats.parts_deleted,
+            stats.missiles_fired,
+            stats.build_requests_rejected,
+        )?;
+
+        write!(f, " | bot tick {:.2} ms (longest {:.2} ms)", milliseconds(self.tick_times.mean()), milliseconds(self.tick_times.longest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{BotStats, ServerTickObserver};
+
+    #[test]
+    fn late_snapshots_are_ignored() {
+        let start = Instant::now();
+        let mut stats = BotStats::default();
+        let mut observer = ServerTickObserver::default();
+
+        observer.observe(10, start, &mut stats);
+        observer.observe(13, start + Duration::from_millis(50), &mut stats);
+        observer.observe(12, start + Duration::from_millis(60), &mut stats);
+        observer.observe(14, start + Duration::from_millis(70), &mut stats);
+
+        assert_eq!(stats.server_ticks, 4);
+        assert_eq!(stats.longest_snapshot_gap, Duration::from_millis(50));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,92 @@
+use std::time::Instant;
+
+use bevy::prelude::*;
+
+use common::construct_access::ConstructAccess;
+use common::fixed_update::{SetupFixedTimeStepSchedule, SetupRapier};
+use common::network_id::NetworkId;
+use common::part::{PartHandle, PartId, Parts};
+use common::player::{PlayerId, PlayerName};
+use common::ship::ShipBundle;
+use common::transport::loopback::{LinkConditions, LoopbackNetwork};
+use ship_designer_bot::bot::{Bot, BotState};
+use ship_designer_bot::stats::BotStats;
+use ship_designer_server::app_setup::{SetupBevyPlugins, SetupServerSpecific, setup_hardcoded_parts};
+use ship_designer_server::network_id_generator::NetworkIdGenerator;
+use ship_designer_server::part::spawn_part;
+use ship_designer_server::server_state::ServerState;
+
+fn spawn_construct(
+    mut commands: Commands,
+    mut network_id_generator: ResMut<NetworkIdGenerator>,
+    parts: Res<Parts>,
+) {
+    let construct = commands.spawn(ShipBundle {
+        network_id: network_id_generator.generate(),
+        // Anyone who joins can build on it
+        access: ConstructAccess::open(),
+        ..Default::default()
+    }).id();
+
+    spawn_part(
+        &mut commands,
+        &parts,
+        parts.get_handle(PartId::from(0)),
+        Transform::from_xyz(0.0, 0.0, 0.0),
+        network_id_generator.generate(),
+        construct
+    );
+}
+
+fn server_app(network: &LoopbackNetwork) -> App {
+    let mut app = App::new();
+
+    app.setup_bevy_plugins()
+        .setup_fixed_timestep_schedule()
+        .setup_rapier()
+        .setup_server_specific()
+        .add_systems(Startup, spawn_construct.after(setup_hardcoded_parts))
+        .insert_non_send_resource(ServerState::new(network.server()));
+
+    app
+}
+
+fn fixed_update(app: &mut App) {
+    let timestep = app.world.resource::<FixedTime>().period;
+    app.world.resource_mut::<FixedTime>().tick(timestep);
+    app.update();
+}
+
+#[test]
+fn bots_build_and_shoot() {
+    let network = LoopbackNetwork::new(LinkConditions { latency: 1, ..Default::default() }, 1);
+    let mut server = server_app(&network);
+
+    let mut bots: Vec<Bot> = (0..3)
+        .map(|index| Bot::new(PlayerName::from(format!("Bot {}", index + 1)), network.connect(), 20.0, index))
+        .collect();
+
+    let mut stats = BotStats::default();
+    for _ in 0..300 {
+        fixed_update(&mut server);
+
+        for bot in bots.iter_mut() {
+            bot.tick(Instant::now());
+        }
+    }
+
+    for bot in bots.iter_mut() {
+        assert_eq!(*bot.state(), BotState::InGame);
+        stats.add(&bot.take_stats());
+    }
+
+    assert!(stats.parts_placed > 0);
+    assert!(stats.missiles_fired > 0);
+    assert!(stats.bytes_sent > 0 && stats.bytes_received > 0);
+    assert!(stats.server_ticks > 0);
+
+    // Some of the placements were accepted, and replicated to the server's world
+    let part_count = server.world.query::<(&PartHandle, &NetworkId)>().iter(&server.world).len();
+    assert!(part_count > 1);
+    assert_eq!(server.world.query::<&PlayerId>().iter(&server.world).len(), 3);
+}
\ No newline at end of file
@@ -1,20 +1,30 @@
 use bevy::prelude::*;
-use common::fixed_update::FixedUpdateSet;
+use common::fixed_update::{FixedUpdateSet, NetworkReceiveSet, NetworkSendSet};
+use common::network_message::NetworkSide;
 use common::part::Parts;
+use common::player_connection::PlayerConnectionPlugin;
+use common::player_movement::PlayerMovementPlugin;
+use common::construct_access::ConstructAccessPlugin;
+use common::construct_replication::ConstructReplicationPlugin;
 use common::predefined_parts::add_hardcoded_parts;
 use common::{part::PartPlugin, missile::MissilePlugin};
 
 use crate::camera::CameraPlugin;
-use crate::packet_handling::process_packets;
+use crate::client_state::ClientStatePlugin;
+use crate::connection_state::ConnectionState;
+use crate::construct_access::ClientConstructAccessPlugin;
+use crate::construct_replication::ClientConstructReplicationPlugin;
+use crate::packet_handling::{process_packets, send_packets};
 use crate::part::meshes::PartMeshHandles;
 use crate::part::meshes::mesh_generation::generate_part_mesh;
 use crate::settings;
 use crate::fixed_input::FixedInputPlugin;
 use crate::free_camera::FreeCameraPlugin;
 use crate::building::BuildingPlugin;
-use crate::player_connection::PlayerConnectionPlugin;
+use crate::player_connection::ClientPlayerConnectionPlugin;
 use crate::part::ClientPartPlugin;
 use crate::player_controller::PlayerControllerPlugin;
+use crate::player_movement::ClientPlayerMovementPlugin;
 use crate::missile::ClientMissilePlugin;
 
 pub fn setup_hardcoded_parts(
@@ -40,18 +50,36 @@ impl SetupClientSpecific for App {
     fn setup_client_specific(&mut self) -> &mut Self {
         self.insert_resource(settings::Settings::default())
             .add_plugins((
+                ClientStatePlugin,
                 FixedInputPlugin,
                 CameraPlugin,
                 FreeCameraPlugin,
                 BuildingPlugin,
                 PlayerConnectionPlugin,
+                ClientPlayerConnectionPlugin,
                 PartPlugin,
                 ClientPartPlugin,
+                ConstructAccessPlugin,
+                ClientConstructAccessPlugin,
                 PlayerControllerPlugin,
                 MissilePlugin,
                 ClientMissilePlugin,
             ))
-            .add_systems(FixedUpdate, process_packets.in_set(FixedUpdateSet::PreUpdate))
+            // Replication of state the server sends every few ticks
+            .add_plugins((
+                PlayerMovementPlugin,
+                ClientPlayerMovementPlugin,
+                ConstructReplicationPlugin,
+                ClientConstructReplicationPlugin,
+            ))
+            .insert_resource(NetworkSide::Client)
+            .add_systems(FixedUpdate, (
+                process_packets
+                    .run_if(resource_exists::<ConnectionState>())
+                    .in_set(FixedUpdateSet::PreUpdate)
+                    .before(NetworkReceiveSet),
+                send_packets.in_set(FixedUpdateSet::PostUpdate).after(NetworkSendSet),
+            ))
             .add_systems(Startup, setup_hardcoded_parts)
     }
 }
\ No newline at end of file
@@ -2,20 +2,23 @@ use core::f32::consts::PI;
 
 use bevy::input::mouse::MouseButton;
 use bevy::prelude::*;
+use common::entity_lookup::lookup;
 use common::fixed_update::FixedUpdateSet;
-use common::part::colliders::{PartCollider, RegenerateColliders};
+use common::part::colliders::PartCollider;
 use bevy_rapier3d::prelude::*;
 
+use common::construct_access::ConstructAccess;
 use common::network_id::NetworkId;
 use common::compact_transform::CompactTransform;
-use common::part::events::{PlacePartRequest, DeletePartRequest};
-use common::part::materials::Material;
-use common::part::{PartHandle, Parts, PartId, VOXEL_SIZE};
+use common::part::events::{PlacePartRequest, DeletePartRequest, DeleteVoxelRequest};
+use common::part::{Part, PartHandle, Parts, PartId, VOXEL_SIZE, MAX_BUILD_DISTANCE};
+use common::player::PlayerId;
 
 use crate::building_material::BuildingMaterial;
 use crate::fixed_input::FixedInput;
 use crate::part::meshes::{PartMeshHandles, get_mesh_or_generate};
-use crate::part::meshes::mesh_generation::RegeneratePartMesh;
+use crate::part::{ProvisionalPart, spawn_provisional_part};
+use crate::player_controller::LocalPlayer;
 use crate::raycast_selection::SelectionSource;
 
 #[derive(Bundle)]
@@ -76,6 +79,10 @@ fn snap_to_grid(point: Vec3, snap_resolution: f32) -> Vec3 {
 #[derive(Component)]
 pub struct BuildMarker;
 
+// The sequence number of the newest build request, used to match the server's answer to the provisional part
+#[derive(Resource, Default)]
+pub struct BuildRequestSequence(u32);
+
 #[derive(Component)]
 pub struct BuildMarkerOrientation(pub Quat);
 
@@ -165,22 +172,57 @@ fn rotate_build_marker(
     }
 }
 
+// The server would reject changes to constructs the local player isn't allowed to build on
+fn can_build_on_selection(
+    selection_source_query: Query<&SelectionSource>,
+    part_collider_query: Query<&PartCollider>,
+    parent_query: Query<&Parent>,
+    access_query: Query<&ConstructAccess>,
+    local_player_query: Query<&PlayerId, With<LocalPlayer>>,
+) -> bool {
+    let Some((entity, _)) = selection_source_query.iter().next().and_then(|source| source.intersection()) else {
+        return false;
+    };
+
+    let Ok(&local_player_id) = local_player_query.get_single() else {
+        return false;
+    };
+
+    part_collider_query.get(entity).ok()
+        .and_then(|collider| parent_query.get(collider.part).ok())
+        .and_then(|parent| access_query.get(parent.get()).ok())
+        .is_some_and(|access| access.can_build(local_player_id))
+}
+
+// The corners of a part's bounding box in its parent's space, which is exact for the build marker's 90 degree rotations
+fn part_bounds(part: &Part, transform: &Transform) -> (Vec3, Vec3) {
+    // Shrunk like the build marker's collider, so parts next to each other don't overlap
+    let half_extents = part.center() - Vec3::splat(0.01);
+    let rotation = Mat3::from_quat(transform.rotation);
+    let extents = rotation.x_axis.abs() * half_extents.x
+        + rotation.y_axis.abs() * half_extents.y
+        + rotation.z_axis.abs() * half_extents.z;
+
+    (transform.translation - extents, transform.translation + extents)
+}
+
 fn create_build_request_events(
     mouse_buttons: Res<FixedInput<MouseButton>>,
     keys: Res<FixedInput<KeyCode>>,
     mut place_part_request_writer: EventWriter<PlacePartRequest>,
     mut delete_part_request_writer: EventWriter<DeletePartRequest>,
+    mut delete_voxel_request_writer: EventWriter<DeleteVoxelRequest>,
     selection_source_query: Query<&SelectionSource>,
-    mut voxel_intersection_query: Query<(&GlobalTransform, &mut PartHandle)>,
-    mut parts: ResMut<Parts>,
-    mut regenerate_part_mesh_writer: EventWriter<RegeneratePartMesh>,
-    mut regenerate_collider_writer: EventWriter<RegenerateColliders>,
+    part_query: Query<(&GlobalTransform, &PartHandle, &NetworkId)>,
+    parts: Res<Parts>,
     parent_query: Query<&Parent>,
     part_collider_query: Query<&PartCollider>,
-    construct_transform_query: Query<&GlobalTransform>,
+    construct_query: Query<(&GlobalTransform, &NetworkId)>,
     marker_query: Query<(&GlobalTransform, &Collider), With<BuildMarker>>,
-    network_id_query: Query<&NetworkId>,
-    rapier_context: Res<RapierContext>
+    local_player_query: Query<&GlobalTransform, With<LocalPlayer>>,
+    provisional_part_query: Query<(&Transform, &PartHandle, &Parent), With<ProvisionalPart>>,
+    rapier_context: Res<RapierContext>,
+    mut build_request_sequence: ResMut<BuildRequestSequence>,
 ) {
     let (entity, intersection_data) = match selection_source_query.iter().next() {
         Some(source) => match source.intersection() {
@@ -203,11 +245,18 @@ fn create_build_request_events(
     if mouse_buttons.just_pressed(MouseButton::Left) {
         // Part deletion
         if keys.pressed(KeyCode::AltLeft) {
-            let network_id = network_id_query.get(part_entity).unwrap();
-            delete_part_request_writer.send(DeletePartRequest(*network_id));
-        // Voxel deletion
+            // Provisional parts can't be deleted until the server has confirmed them
+            if let Ok((_, _, network_id)) = part_query.get(part_entity) {
+                build_request_sequence.0 += 1;
+
+                delete_part_request_writer.send(DeletePartRequest {
+                    request_seq: build_request_sequence.0,
+                    part_network_id: *network_id,
+                });
+            }
+        // Voxel deletion, which shows once the server sends the part's changed voxels
         } else if keys.pressed(KeyCode::ControlLeft) {
-            if let Ok((part_transform, mut part_handle)) = voxel_intersection_query.get_mut(part_entity) {
+            if let Ok((part_transform, part_handle, network_id)) = part_query.get(part_entity) {
                 let inverse = part_transform.affine().inverse();
                 
                 if !inverse.is_finite() {
@@ -215,37 +264,60 @@ fn create_build_request_events(
                     return;
                 }
 
-                let part = parts.get_mut(&mut part_handle).unwrap();
+                let part = parts.get(part_handle).unwrap();
 
                 let inverse_normal = inverse.transform_vector3(intersection_data.normal);
                 let inverse_intersection = inverse.transform_point3(intersection_data.point);
                 
                 let voxel_pos = (inverse_intersection + part.center() - inverse_normal * Vec3::splat(VOXEL_SIZE / 2.0)) / VOXEL_SIZE;
 
-                part.set(voxel_pos.into(), Material::Empty);
+                build_request_sequence.0 += 1;
 
-                regenerate_part_mesh_writer.send(RegeneratePartMesh(part_entity));
-                regenerate_collider_writer.send(RegenerateColliders(part_entity));
+                delete_voxel_request_writer.send(DeleteVoxelRequest {
+                    request_seq: build_request_sequence.0,
+                    part_network_id: *network_id,
+                    voxel_pos: voxel_pos.into(),
+                });
             }
         // Part placement
         } else {
-            if let Ok(construct_transform) = construct_transform_query.get(construct) {
+            if let Ok((construct_transform, construct_network_id)) = construct_query.get(construct) {
                 if let Some((marker_transform, marker_collider)) = marker_query.iter().next() {
                     let (_, marker_rotation, marker_translation) = marker_transform.to_scale_rotation_translation();
-                    if rapier_context.intersection_with_shape(
+
+                    // The server would reject the part, so it isn't shown either
+                    let in_range = local_player_query.get_single()
+                        .is_ok_and(|player_transform| player_transform.translation().distance(marker_translation) <= MAX_BUILD_DISTANCE);
+                    if !in_range {
+                        return;
+                    }
+
+                    let part_id = PartId::from(1);
+                    let part = parts.get_part_from_id(part_id).unwrap();
+                    let construct_space_transform = marker_transform.reparented_to(construct_transform);
+
+                    // Parts waiting for the server can still be missing from the physics world, so they are checked separately
+                    let (min, max) = part_bounds(part, &construct_space_transform);
+                    let overlaps_provisional_part = provisional_part_query.iter()
+                        .filter(|(_, _, parent)| parent.get() == construct)
+                        .any(|(transform, part_handle, _)| {
+                            let (other_min, other_max) = part_bounds(parts.get(part_handle).unwrap(), transform);
+                            min.cmplt(other_max).all() && other_min.cmplt(max).all()
+                        });
+
+                    if !overlaps_provisional_part && rapier_context.intersection_with_shape(
                         marker_translation,
                         marker_rotation,
                         marker_collider,
                         QueryFilter::new().exclude_sensors()
                     ).is_none() {
-                        let part_id = PartId::from(1);
-
-                        let construct_space_transform = marker_transform.reparented_to(&construct_transform);
+                        build_request_sequence.0 += 1;
     
                         place_part_request_writer.send(PlacePartRequest {
+                            request_seq: build_request_sequence.0,
                             part_id,
                             part_transform: CompactTransform::from(construct_space_transform),
-                            construct_network_id: *network_id_query.get(construct).unwrap()
+                            construct_network_id: *construct_network_id
                         });
                     }
                 }
@@ -254,15 +326,46 @@ fn create_build_request_events(
     }
 }
 
+// Shows requested parts straight away, they are confirmed or rolled back once the server answers
+fn spawn_provisional_parts(
+    mut place_part_request_reader: EventReader<PlacePartRequest>,
+    mut commands: Commands,
+    mut mesh_handles: ResMut<PartMeshHandles>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<BuildingMaterial>>,
+    parts: Res<Parts>,
+    construct_query: Query<(Entity, &NetworkId)>,
+) {
+    for request in place_part_request_reader.iter() {
+        let Some(construct) = lookup(&construct_query, &request.construct_network_id) else {
+            continue;
+        };
+
+        spawn_provisional_part(
+            &mut commands,
+            &mut mesh_handles,
+            &mut meshes,
+            &mut materials,
+            &parts,
+            parts.get_handle(request.part_id),
+            Transform::from(request.part_transform),
+            request.request_seq,
+            construct,
+        );
+    }
+}
+
 pub struct BuildingPlugin;
 
 impl Plugin for BuildingPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(MaterialPlugin::<BuildingMaterial>::default())
+            .init_resource::<BuildRequestSequence>()
             .add_systems(FixedUpdate, (
                 move_build_marker,
                 rotate_build_marker,
-                create_build_request_events
+                create_build_request_events.run_if(can_build_on_selection),
+                spawn_provisional_parts,
             ).chain().in_set(FixedUpdateSet::Update));
     }
 }
\ No newline at end of file
@@ -0,0 +1,197 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+use uflow::client::Client;
+use uflow::EndpointConfig;
+
+use common::network_id::NetworkId;
+use common::player::{PlayerId, PlayerName};
+use common::snapshot::InterpolationClock;
+
+use crate::camera::ActiveCamera;
+use crate::connection_state::ConnectionState;
+use crate::free_camera::FreeCamera;
+use crate::player_connection::InitialStateProgress;
+use crate::settings::Settings;
+
+#[derive(States, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ClientState {
+    #[default]
+    MainMenu,
+    // Waiting for the server to accept the connection
+    Connecting,
+    // Connected and waiting for the initial state
+    LoadingInitialState,
+    InGame,
+    // Showing why the connection ended, until the player goes back to the menu
+    Disconnected,
+}
+
+// What the player has entered on the main menu
+#[derive(Resource, Debug)]
+pub struct ConnectionSettings {
+    pub address: String,
+    pub player_name: String,
+}
+
+impl Default for ConnectionSettings {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:36756".to_string(),
+            player_name: "Player".to_string(),
+        }
+    }
+}
+
+// Why the last connection ended, shown on the disconnected screen
+#[derive(Resource, Debug, Default)]
+pub struct DisconnectReason(pub String);
+
+fn connect(settings: &ConnectionSettings, active_timeout_ms: u64) -> Result<Client, String> {
+    // The server would reject the name anyway, but this way we can say why without connecting
+    PlayerName::from(settings.player_name.clone()).validate()
+        .map_err(|reason| format!("Invalid player name: {}", reason))?;
+
+    let client_config = uflow::client::Config {
+        endpoint_config: EndpointConfig {
+            active_timeout_ms,
+            ..Default::default()
+        }
+    };
+
+    Client::connect(settings.address.trim(), client_config)
+        .map_err(|err| format!("Failed to connect to {}: {}", settings.address.trim(), err))
+}
+
+fn main_menu(
+    mut contexts: EguiContexts,
+    mut settings: ResMut<ConnectionSettings>,
+    client_settings: Res<Settings>,
+    mut next_state: ResMut<NextState<ClientState>>,
+    mut commands: Commands,
+) {
+    egui::Window::new("Ship Designer")
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .collapsible(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("connection_settings").show(ui, |ui| {
+                ui.label("Server address");
+                ui.text_edit_singleline(&mut settings.address);
+                ui.end_row();
+
+                ui.label("Player name");
+                ui.text_edit_singleline(&mut settings.player_name);
+                ui.end_row();
+            });
+
+            if ui.button("Connect").clicked() {
+                match connect(&settings, client_settings.active_timeout_ms) {
+                    Ok(client) => {
+                        info!("Connecting to {}", settings.address.trim());
+                        commands.insert_resource(ConnectionState::new(client));
+                        next_state.set(ClientState::Connecting);
+                    },
+                    Err(reason) => {
+                        error!("{}", reason);
+                        commands.insert_resource(DisconnectReason(reason));
+                        next_state.set(ClientState::Disconnected);
+                    }
+                }
+            }
+        });
+}
+
+fn connecting_screen(
+    mut contexts: EguiContexts,
+    client_state: Res<State<ClientState>>,
+    settings: Res<ConnectionSettings>,
+    progress: Option<Res<InitialStateProgress>>,
+    mut connection_state: ResMut<ConnectionState>,
+) {
+    let status = match client_state.get() {
+        ClientState::Connecting => format!("Connecting to {}...", settings.address.trim()),
+        _ => "Loading...".to_string(),
+    };
+
+    egui::Window::new("Ship Designer")
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .collapsible(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(status);
+
+            // Shown once the server has said how many parts are coming
+            if let Some(progress) = progress.as_ref().filter(|_| *client_state.get() == ClientState::LoadingInitialState) {
+                ui.add(egui::ProgressBar::new(progress.fraction())
+                    .text(format!("{}/{} parts", progress.loaded_parts.min(progress.part_count), progress.part_count)));
+            }
+
+            // The disconnect event moves us to the disconnected screen
+            if ui.button("Cancel").clicked() {
+                connection_state.client.disconnect();
+            }
+        });
+}
+
+fn disconnected_screen(
+    mut contexts: EguiContexts,
+    reason: Res<DisconnectReason>,
+    mut next_state: ResMut<NextState<ClientState>>,
+) {
+    egui::Window::new("Disconnected")
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .collapsible(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(&reason.0);
+
+            if ui.button("Back to menu").clicked() {
+                next_state.set(ClientState::MainMenu);
+            }
+        });
+}
+
+// Removes everything that came from the server, so the next connection starts from a clean world
+fn clean_up_connection(
+    mut commands: Commands,
+    network_entity_query: Query<Entity, (Or<(With<PlayerId>, With<NetworkId>)>, Without<Parent>)>,
+    mut free_camera_query: Query<(Entity, &mut Camera), With<FreeCamera>>,
+) {
+    commands.remove_resource::<ConnectionState>();
+    commands.remove_resource::<InitialStateProgress>();
+    // The next server starts counting ticks from zero
+    commands.insert_resource(InterpolationClock::default());
+
+    for entity in network_entity_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    // The player camera was despawned with the player, so go back to how the free camera started
+    for (entity, mut camera) in free_camera_query.iter_mut() {
+        camera.is_active = false;
+        commands.entity(entity).remove::<ActiveCamera>();
+    }
+}
+
+pub struct ClientStatePlugin;
+
+impl Plugin for ClientStatePlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+
+        app.add_state::<ClientState>()
+            .init_resource::<ConnectionSettings>()
+            .init_resource::<DisconnectReason>()
+            .add_systems(OnEnter(ClientState::Disconnected), clean_up_connection)
+            .add_systems(Update, (
+                main_menu.run_if(in_state(ClientState::MainMenu)),
+                connecting_screen.run_if(
+                    (in_state(ClientState::Connecting).or_else(in_state(ClientState::LoadingInitialState)))
+                        .and_then(resource_exists::<ConnectionState>())
+                ),
+                disconnected_screen.run_if(in_state(ClientState::Disconnected)),
+            ));
+    }
+}
\ No newline at end of file
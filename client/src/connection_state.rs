@@ -1,13 +1,30 @@
 use bevy::prelude::Resource;
-use uflow::client::Client;
+use common::batching::OutgoingBatches;
+use common::channels::Channel;
+use common::transport::ClientTransport;
+use packets::Packet;
 
 #[derive(Resource)]
 pub struct ConnectionState {
-    pub client: Client,
+    pub client: Box<dyn ClientTransport>,
+    // Set when the server tells us why it is about to disconnect us
+    pub rejection_reason: Option<String>,
+    outgoing_batches: OutgoingBatches,
 }
 
 impl ConnectionState {
-    pub fn new(client: Client) -> Self {
-        Self { client }
+    pub fn new(client: impl ClientTransport + 'static) -> Self {
+        Self { client: Box::new(client), rejection_reason: None, outgoing_batches: OutgoingBatches::default() }
+    }
+
+    // Queues the packet to be sent with the rest of the tick's packets by send_batches
+    pub fn send(&mut self, packet: &Packet, channel: Channel) {
+        self.outgoing_batches.push(channel, packet);
+    }
+
+    pub fn send_batches(&mut self) {
+        for (channel, batch) in self.outgoing_batches.drain() {
+            self.client.send(batch, channel);
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,28 @@
+use bevy::prelude::*;
+
+use common::construct_access::{ConstructAccess, ConstructAccessChanged};
+use common::fixed_update::FixedUpdateSet;
+use common::network_id::NetworkId;
+use common::ship::Ship;
+
+fn apply_construct_access(
+    mut access_changed_reader: EventReader<ConstructAccessChanged>,
+    mut construct_query: Query<(&NetworkId, &mut ConstructAccess), With<Ship>>,
+) {
+    for access_changed in access_changed_reader.iter() {
+        let Some((_, mut access)) = construct_query.iter_mut()
+            .find(|(network_id, _)| **network_id == access_changed.construct_network_id) else {
+            continue;
+        };
+
+        *access = access_changed.access.clone();
+    }
+}
+
+pub struct ClientConstructAccessPlugin;
+
+impl Plugin for ClientConstructAccessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, apply_construct_access.in_set(FixedUpdateSet::Update));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,181 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use common::construct_replication::{ConstructState, ConstructStates};
+use common::fixed_update::FixedUpdateSet;
+use common::network_id::NetworkId;
+use common::ship::Ship;
+use common::snapshot::{InterpolationClock, Snapshot, SnapshotBuffer};
+
+use crate::connection_state::ConnectionState;
+use crate::player_controller::LocalPlayer;
+use crate::player_movement::advance_interpolation_clock;
+
+// Constructs closer than this to the local player are simulated locally, so that collisions with them respond at once
+const PREDICTION_RADIUS: f32 = 20.0;
+// Differences from the server smaller than this are left alone
+const CORRECTION_THRESHOLD: f32 = 0.02;
+// Differences larger than this are corrected at once instead of smoothly
+const SNAP_THRESHOLD: f32 = 2.0;
+// The fraction of the difference corrected for each snapshot
+const CORRECTION_RATE: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConstructSimulation {
+    // Shown where the server had it a little while ago, as a kinematic body
+    Interpolated,
+    // Simulated locally and nudged towards the server's state
+    Predicted,
+}
+
+#[derive(Component, Debug)]
+pub struct ConstructReplication {
+    pub simulation: ConstructSimulation,
+    newest_state: Option<ConstructState>,
+}
+
+impl Default for ConstructReplication {
+    fn default() -> Self {
+        Self {
+            simulation: ConstructSimulation::Interpolated,
+            newest_state: None,
+        }
+    }
+}
+
+#[derive(Bundle, Default)]
+pub struct ReplicatedConstructBundle {
+    pub replication: ConstructReplication,
+    pub snapshots: SnapshotBuffer,
+}
+
+// Moves a locally simulated construct towards the server's state, extrapolated to the present
+fn correct_predicted_construct(
+    transform: &mut Transform,
+    velocity: &mut Velocity,
+    state: &ConstructState,
+    latency: f32,
+) {
+    let server_translation = state.transform.translation + state.linear_velocity * latency;
+    let server_rotation = Quat::from_scaled_axis(state.angular_velocity * latency) * state.transform.rotation;
+
+    let translation_error = server_translation - transform.translation;
+    let rotation_error = server_rotation * transform.rotation.inverse();
+
+    if translation_error.length() < CORRECTION_THRESHOLD && rotation_error.angle_between(Quat::IDENTITY) < CORRECTION_THRESHOLD {
+        return;
+    }
+
+    let rate = if translation_error.length() > SNAP_THRESHOLD { 1.0 } else { CORRECTION_RATE };
+
+    transform.translation += translation_error * rate;
+    transform.rotation = (Quat::IDENTITY.slerp(rotation_error, rate) * transform.rotation).normalize();
+    velocity.linvel = velocity.linvel.lerp(state.linear_velocity, rate);
+    velocity.angvel = velocity.angvel.lerp(state.angular_velocity, rate);
+}
+
+fn apply_construct_states(
+    mut construct_states_reader: EventReader<ConstructStates>,
+    mut clock: ResMut<InterpolationClock>,
+    connection_state: Option<Res<ConnectionState>>,
+    mut construct_query: Query<(&NetworkId, &mut Transform, &mut Velocity, &mut ConstructReplication, &mut SnapshotBuffer), With<Ship>>,
+) {
+    // The snapshot is about half a round trip old by the time it arrives
+    let latency = connection_state
+        .and_then(|connection_state| connection_state.client.rtt_s())
+        .map_or(0.0, |rtt| rtt as f32 / 2.0);
+
+    for construct_states in construct_states_reader.iter() {
+        clock.observe(construct_states.tick);
+
+        for state in construct_states.constructs.iter() {
+            let Some((_, mut transform, mut velocity, mut replication, mut snapshots)) = construct_query.iter_mut()
+                .find(|(network_id, ..)| **network_id == state.network_id) else {
+                continue;
+            };
+
+            snapshots.push(Snapshot {
+                tick: construct_states.tick,
+                translation: state.transform.translation,
+                rotation: state.transform.rotation,
+            });
+
+            // Snapshots that arrive out of order are still useful for interpolation, but not for correction
+            if snapshots.newest().is_some_and(|newest| newest.tick != construct_states.tick) {
+                continue;
+            }
+
+            replication.newest_state = Some(*state);
+
+            if replication.simulation == ConstructSimulation::Predicted {
+                correct_predicted_construct(&mut transform, &mut velocity, state, latency);
+            }
+        }
+    }
+}
+
+fn select_construct_simulation(
+    local_player_query: Query<&Transform, With<LocalPlayer>>,
+    mut construct_query: Query<(&Transform, &mut RigidBody, &mut Velocity, &mut ConstructReplication), Without<LocalPlayer>>,
+) {
+    let local_player_translation = local_player_query.get_single().ok().map(|transform| transform.translation);
+
+    for (transform, mut rigid_body, mut velocity, mut replication) in construct_query.iter_mut() {
+        let nearby = local_player_translation
+            .is_some_and(|translation| translation.distance(transform.translation) < PREDICTION_RADIUS);
+
+        let simulation = if nearby { ConstructSimulation::Predicted } else { ConstructSimulation::Interpolated };
+        if simulation == replication.simulation {
+            continue;
+        }
+
+        match simulation {
+            ConstructSimulation::Predicted => {
+                *rigid_body = RigidBody::Dynamic;
+
+                // Carry on from the server's motion instead of from rest
+                if let Some(state) = replication.newest_state {
+                    velocity.linvel = state.linear_velocity;
+                    velocity.angvel = state.angular_velocity;
+                }
+            },
+            ConstructSimulation::Interpolated => {
+                *rigid_body = RigidBody::KinematicPositionBased;
+            }
+        }
+
+        replication.simulation = simulation;
+    }
+}
+
+fn interpolate_constructs(
+    clock: Res<InterpolationClock>,
+    mut construct_query: Query<(&mut Transform, &ConstructReplication, &SnapshotBuffer), With<Ship>>,
+) {
+    let Some(playback_tick) = clock.playback_tick() else {
+        return;
+    };
+
+    for (mut transform, replication, snapshots) in construct_query.iter_mut() {
+        if replication.simulation != ConstructSimulation::Interpolated {
+            continue;
+        }
+
+        if let Some((translation, rotation)) = snapshots.sample(playback_tick) {
+            transform.translation = translation;
+            transform.rotation = rotation;
+        }
+    }
+}
+
+pub struct ClientConstructReplicationPlugin;
+
+impl Plugin for ClientConstructReplicationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, (
+            apply_construct_states.before(advance_interpolation_clock),
+            select_construct_simulation.after(apply_construct_states),
+            interpolate_constructs.after(select_construct_simulation).after(advance_interpolation_clock),
+        ).in_set(FixedUpdateSet::Update));
+    }
+}
\ No newline at end of file
@@ -2,8 +2,11 @@ pub mod app_setup;
 pub mod building;
 pub mod building_material;
 pub mod camera;
+pub mod client_state;
 pub mod free_camera;
 pub mod connection_state;
+pub mod construct_access;
+pub mod construct_replication;
 pub mod fixed_input;
 pub mod missile;
 pub mod part;
@@ -11,5 +14,6 @@ pub mod packet_handling;
 pub mod player_camera;
 pub mod player_connection;
 pub mod player_controller;
+pub mod player_movement;
 pub mod raycast_selection;
 pub mod settings;
\ No newline at end of file
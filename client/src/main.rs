@@ -6,8 +6,6 @@ use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_rapier3d::prelude::*;
 use ship_designer_client::camera::CameraDebugPlugin;
 use ship_designer_client::settings::Settings;
-use uflow::client::Client;
-use uflow::EndpointConfig;
 
 use common::part::{Parts, PartId};
 use common::fixed_update::{FixedUpdateSet, SetupFixedTimeStepSchedule, SetupRapier};
@@ -21,18 +19,6 @@ use ship_designer_client::connection_state::ConnectionState;
 use ship_designer_client::part::meshes::PartMeshHandles;
 
 fn main() {
-    let server_address = "127.0.0.1:36756";
-    let client_config = uflow::client::Config {
-        endpoint_config: EndpointConfig {
-            active_timeout_ms: 3600000,
-            ..Default::default()
-        }
-    };
-
-    let client = Client::connect(server_address, client_config).expect("Failed to connect to server!");
-
-    let connection_state = ConnectionState::new(client);
-    
     App::new().insert_resource(Msaa::default())
         .add_plugins(
             DefaultPlugins.set(LogPlugin {
@@ -59,7 +45,6 @@ fn main() {
         ))
         .add_plugins(CameraDebugPlugin)
         .add_plugins(WorldInspectorPlugin::new())
-        .insert_resource(connection_state)
         .add_systems(FixedUpdate,
             update_intersections.in_set(FixedUpdateSet::PreUpdate).after(FixedInputSystem)
         )
@@ -107,8 +92,10 @@ fn setup(
 
 fn disconnect_on_esc(
     keys: Res<Input<KeyCode>>,
-    mut connection_state: ResMut<ConnectionState>
+    connection_state: Option<ResMut<ConnectionState>>
 ) {
+    let Some(mut connection_state) = connection_state else { return };
+
     if keys.pressed(KeyCode::Escape) {
         connection_state.client.disconnect();
     }
@@ -116,8 +103,10 @@ fn disconnect_on_esc(
 
 fn disconnect_on_window_close(
     window_closed: EventReader<WindowClosed>,
-    mut connection_state: ResMut<ConnectionState>
+    connection_state: Option<ResMut<ConnectionState>>
 ) {
+    let Some(mut connection_state) = connection_state else { return };
+
     if !window_closed.is_empty() {
         connection_state.client.disconnect();
     }
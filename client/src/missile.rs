@@ -3,14 +3,11 @@ use bevy::prelude::shape::Cube;
 use bevy_rapier3d::prelude::*;
 use common::{entity_lookup::lookup, fixed_update::FixedUpdateSet};
 use common::network_id::NetworkId;
-use uflow::SendMode;
 
 use common::missile::{SpawnMissileRequest, SpawnMissileCommand, MissileBundle, ExplodeMissileCommand, Missile};
-use packets::Packet; 
-use common::channels::Channel;
 
 use crate::camera::ActiveCamera;
-use crate::{connection_state::ConnectionState, fixed_input::FixedInput};
+use crate::fixed_input::FixedInput;
 
 fn request_spawn_missiles(
     keys: Res<FixedInput<KeyCode>>,
@@ -29,21 +26,6 @@ fn request_spawn_missiles(
     }
 }
 
-fn send_spawn_missile_requests(
-    mut spawn_event_reader: EventReader<SpawnMissileRequest>,
-    mut connection_state: ResMut<ConnectionState>,
-) {
-    for spawn_event in spawn_event_reader.iter() {
-        let packet = Packet::from(spawn_event);
-
-        connection_state.client.send(
-            (&packet).into(),
-            Channel::Missile.into(),
-            SendMode::Reliable
-        );
-    }
-}
-
 fn spawn_missiles(
     mut spawn_event_reader: EventReader<SpawnMissileCommand>,
     mut commands: Commands,
@@ -85,7 +67,6 @@ impl Plugin for ClientMissilePlugin {
         app.add_systems(FixedUpdate, (
             spawn_missiles,
             request_spawn_missiles,
-            send_spawn_missile_requests.after(request_spawn_missiles),
             explode_missiles,
         ).in_set(FixedUpdateSet::Update));
     }
@@ -1,171 +1,103 @@
-use bevy::app::AppExit;
 use bevy::prelude::*;
-use common::missile::{SpawnMissileCommand, ExplodeMissileCommand};
-use common::part::events::VoxelUpdate;
+use common::network_message::{IncomingPackets, OutgoingPackets};
 use uflow::client::{Event::*, ErrorType};
 
-use common::part::events::{PlacePartCommand, DeletePartCommand};
-use common::player_connection::{PlayerConnected, PlayerDisconnected, InitialState};
+use common::channels::Channel;
+use common::player::PlayerName;
+use common::protocol::{ClientHello, Handshake, ConnectionRejected};
 use packets::{Packet, PacketType};
+use packets::batch::Unbatcher;
 
+use crate::client_state::{ClientState, ConnectionSettings, DisconnectReason};
 use crate::connection_state::ConnectionState;
 
+// Leaves the connection, showing the reason on the disconnected screen
+fn disconnect(reason: String, commands: &mut Commands, next_state: &mut NextState<ClientState>) {
+    commands.insert_resource(DisconnectReason(reason));
+    next_state.set(ClientState::Disconnected);
+}
+
 pub fn process_packets(
     mut state: ResMut<ConnectionState>,
-    mut place_part_command_writer: EventWriter<PlacePartCommand>,
-    mut delete_part_command_writer: EventWriter<DeletePartCommand>,
-    mut player_connected_writer: EventWriter<PlayerConnected>,
-    mut player_disconnected_writer: EventWriter<PlayerDisconnected>,
-    mut app_exit_writer: EventWriter<AppExit>,
-    mut initial_state_writer: EventWriter<InitialState>,
-    mut voxel_update_writer: EventWriter<VoxelUpdate>,
-    mut spawn_missile_writer: EventWriter<SpawnMissileCommand>,
-    mut explode_missile_writer: EventWriter<ExplodeMissileCommand>,
+    mut incoming_packets: ResMut<IncomingPackets>,
+    settings: Res<ConnectionSettings>,
+    mut next_state: ResMut<NextState<ClientState>>,
+    mut commands: Commands,
 ) {
     for event in state.client.step() {
         match event {
             Connect => {
                 info!("Connected to server");
+
+                // The server expects the handshake before anything else
+                let handshake_packet = Packet::from(&Handshake::default());
+                state.client.send((&handshake_packet).into(), Channel::PlayerConnectionEvents);
+
+                // Followed by the hello, which the server needs to create our player
+                let hello = ClientHello { name: PlayerName::from(settings.player_name.clone()) };
+                state.send(&Packet::from(&hello), Channel::PlayerConnectionEvents);
+
+                next_state.set(ClientState::LoadingInitialState);
             },
             Disconnect => {
                 info!("Disconnected from server");
-                app_exit_writer.send(AppExit);
+
+                let reason = state.rejection_reason.take()
+                    .unwrap_or_else(|| "Disconnected from server".to_string());
+                disconnect(reason, &mut commands, &mut next_state);
             },
             Receive(packet_data) => {
-                match Packet::try_from(packet_data) {
-                    Ok(packet) => {
-                        generate_events(
-                            packet,
-                            &mut place_part_command_writer,
-                            &mut delete_part_command_writer,
-                            &mut player_connected_writer,
-                            &mut player_disconnected_writer,
-                            &mut initial_state_writer,
-                            &mut voxel_update_writer,
-                            &mut spawn_missile_writer,
-                            &mut explode_missile_writer,
-                        );
-                    },
-                    Err(err) => {
-                        warn!(?err);
+                // The server batches its packets, except for connection rejections
+                for packet in Unbatcher::new(packet_data) {
+                    match packet {
+                        Ok(packet) if packet.packet_type() == PacketType::ConnectionRejected => {
+                            match ConnectionRejected::try_from(packet) {
+                                Ok(connection_rejected) => {
+                                    error!("Connection rejected by server: {}", connection_rejected.reason);
+                                    state.rejection_reason = Some(format!("Connection rejected by server: {}", connection_rejected.reason));
+                                },
+                                Err(err) => {
+                                    warn!(?err);
+                                }
+                            }
+                        },
+                        Ok(packet) => {
+                            incoming_packets.push(None, packet);
+                        },
+                        Err(err) => {
+                            warn!(?err);
+                        }
                     }
                 }
             },
             Error(error_type) => {
-                match error_type {
-                    ErrorType::Timeout => {
-                        error!("Connection to server timed out!");
-                        app_exit_writer.send(AppExit);
-                    },
-                    ErrorType::Version => {
-                        error!("Connection failed: protocol version mismatch!");
-                        app_exit_writer.send(AppExit);
-                    },
-                    ErrorType::Config => {
-                        error!("Connection failed: invalid endpoint configuration!");
-                        app_exit_writer.send(AppExit);
-                    },
-                    ErrorType::ServerFull => {
-                        error!("Connection failed: server full!");
-                        app_exit_writer.send(AppExit);
-                    }
-                }
+                let reason = match error_type {
+                    ErrorType::Timeout => "Connection to server timed out!",
+                    ErrorType::Version => "Connection failed: protocol version mismatch!",
+                    ErrorType::Config => "Connection failed: invalid endpoint configuration!",
+                    ErrorType::ServerFull => "Connection failed: server full!",
+                };
+
+                error!("{}", reason);
+                disconnect(reason.to_string(), &mut commands, &mut next_state);
             }
         }
     }
 }
 
-fn generate_events(
-    packet: Packet,
-    place_part_command_writer: &mut EventWriter<PlacePartCommand>,
-    delete_part_command_writer: &mut EventWriter<DeletePartCommand>,
-    player_connected_writer: &mut EventWriter<PlayerConnected>,
-    player_disconnected_writer: &mut EventWriter<PlayerDisconnected>,
-    initial_state_writer: &mut EventWriter<InitialState>,
-    voxel_update_writer: &mut EventWriter<VoxelUpdate>,
-    spawn_missile_writer: &mut EventWriter<SpawnMissileCommand>,
-    explode_missile_writer: &mut EventWriter<ExplodeMissileCommand>,
+pub fn send_packets(
+    state: Option<ResMut<ConnectionState>>,
+    mut outgoing_packets: ResMut<OutgoingPackets>,
 ) {
-    match packet.packet_type() {
-        PacketType::PlacePart => {
-            match PlacePartCommand::try_from(packet) {
-                Ok(place_part_command) => {
-                    place_part_command_writer.send(place_part_command);
-                },
-                Err(err) => {
-                    warn!(?err);
-                }
-            }
-        },
-        PacketType::DeletePart => {
-            match DeletePartCommand::try_from(packet) {
-                Ok(delete_part_command) => {
-                    delete_part_command_writer.send(delete_part_command);
-                },
-                Err(err) => {
-                    warn!(?err);
-                }
-            }
-        },
-        PacketType::PlayerConnected => {
-            match PlayerConnected::try_from(packet) {
-                Ok(player_connected) => {
-                    player_connected_writer.send(player_connected);
-                },
-                Err(err) => {
-                    warn!(?err);
-                }
-            }
-        },
-        PacketType::PlayerDisconnected => {
-            match PlayerDisconnected::try_from(packet) {
-                Ok(player_disconnected) => {
-                    player_disconnected_writer.send(player_disconnected);
-                },
-                Err(err) => {
-                    warn!(?err);
-                }
-            }
-        },
-        PacketType::InitialState => {
-            match InitialState::try_from(packet) {
-                Ok(initial_state) => {
-                    initial_state_writer.send(initial_state);
-                },
-                Err(err) => {
-                    warn!(?err);
-                }
-            }
-        },
-        PacketType::VoxelUpdate => {
-            match VoxelUpdate::try_from(packet) {
-                Ok(voxel_update) => {
-                    voxel_update_writer.send(voxel_update);
-                },
-                Err(err) => {
-                    warn!(?err);
-                }
-            }
-        },
-        PacketType::SpawnMissile => {
-            match SpawnMissileCommand::try_from(packet) {
-                Ok(spawn_missile) => {
-                    spawn_missile_writer.send(spawn_missile);
-                },
-                Err(err) => {
-                    warn!(?err);
-                }
-            }
-        },
-        PacketType::ExplodeMissile => {
-            match ExplodeMissileCommand::try_from(packet) {
-                Ok(explode_missile) => {
-                    explode_missile_writer.send(explode_missile);
-                },
-                Err(err) => {
-                    warn!(?err);
-                }
-            }
-        },
+    // Without a connection there is nobody to send to, so the packets are dropped
+    let Some(mut state) = state else {
+        outgoing_packets.drain().for_each(drop);
+        return;
+    };
+
+    for outgoing_packet in outgoing_packets.drain() {
+        state.send(&outgoing_packet.packet, outgoing_packet.channel);
     }
+
+    state.send_batches();
 }
\ No newline at end of file
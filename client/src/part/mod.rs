@@ -1,24 +1,38 @@
+use std::collections::HashSet;
+
 use bevy::prelude::*;
 
-use common::channels::Channel;
 use common::entity_lookup::lookup;
 use common::fixed_update::{AddFixedEvent, FixedUpdateSet};
-use packets::Packet;
-use common::part::events::{VoxelUpdate, PlacePartRequest, DeletePartRequest, PlacePartCommand, DeletePartCommand};
+use common::part::events::{VoxelUpdate, PlacePartCommand, DeletePartCommand, BuildRequestRejected, FullVoxelsRequest, FullVoxelsCommand};
 use common::network_id::NetworkId;
+use common::player::PlayerId;
 use common::part::{PartHandle, Parts, DeletePart};
 use common::part::colliders::{PartCollider, RegenerateColliders, generate_collider_data};
 
 use meshes::{PartMeshHandles, get_mesh_or_generate, free_part_mesh_handles};
 use meshes::mesh_generation::{RegeneratePartMesh, regenerate_part_mesh};
-use uflow::SendMode;
 use crate::building_material::BuildingMaterial;
-use crate::connection_state::ConnectionState;
+use crate::player_controller::LocalPlayer;
 use crate::raycast_selection::Selectable;
 
 pub mod meshes;
 
-pub fn spawn_part(
+const PART_COLOR: Color = Color::rgb(0.0, 0.3, 0.5);
+// Parts that the server hasn't confirmed yet are drawn lighter
+const PROVISIONAL_PART_COLOR: Color = Color::rgb(0.35, 0.6, 0.8);
+// Provisional parts are removed after 5 seconds without an answer, since the request or the answer was lost
+pub const PROVISIONAL_PART_TIMEOUT_TICKS: u32 = 300;
+
+// A part placed by the local player that is shown before the server has confirmed it
+// It has no NetworkId until the PlacePartCommand for its request arrives
+#[derive(Component, Debug)]
+pub struct ProvisionalPart {
+    pub request_seq: u32,
+    pub ticks_waited: u32,
+}
+
+fn spawn_part_entity(
     commands: &mut Commands,
     mesh_handles: &mut PartMeshHandles,
     meshes: &mut Assets<Mesh>,
@@ -26,7 +40,7 @@ pub fn spawn_part(
     parts: &Parts,
     part_handle: PartHandle,
     transform: Transform,
-    part_network_id: NetworkId,
+    color: Color,
     construct: Entity,
 ) -> Entity {
     let part = parts.get(&part_handle).unwrap();
@@ -35,12 +49,11 @@ pub fn spawn_part(
 
     let part_entity = commands.spawn(MaterialMeshBundle::<BuildingMaterial> {
             mesh: mesh_handle,
-            material: materials.add(BuildingMaterial { color: Color::rgb(0.0, 0.3, 0.5).into() }),
+            material: materials.add(BuildingMaterial { color }),
             transform,
             ..Default::default()
         })
         .insert(part_handle)
-        .insert(part_network_id)
         .id();
     
     commands.entity(construct).add_child(part_entity);
@@ -58,19 +71,93 @@ pub fn spawn_part(
     part_entity
 }
 
+pub fn spawn_part(
+    commands: &mut Commands,
+    mesh_handles: &mut PartMeshHandles,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<BuildingMaterial>,
+    parts: &Parts,
+    part_handle: PartHandle,
+    transform: Transform,
+    part_network_id: NetworkId,
+    construct: Entity,
+) -> Entity {
+    let part_entity = spawn_part_entity(commands, mesh_handles, meshes, materials, parts, part_handle, transform, PART_COLOR, construct);
+    commands.entity(part_entity).insert(part_network_id);
+
+    part_entity
+}
+
+pub fn spawn_provisional_part(
+    commands: &mut Commands,
+    mesh_handles: &mut PartMeshHandles,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<BuildingMaterial>,
+    parts: &Parts,
+    part_handle: PartHandle,
+    transform: Transform,
+    request_seq: u32,
+    construct: Entity,
+) -> Entity {
+    let part_entity = spawn_part_entity(commands, mesh_handles, meshes, materials, parts, part_handle, transform, PROVISIONAL_PART_COLOR, construct);
+    commands.entity(part_entity).insert(ProvisionalPart { request_seq, ticks_waited: 0 });
+
+    part_entity
+}
+
 fn update_voxels(
     mut voxel_update_reader: EventReader<VoxelUpdate>,
+    mut full_voxels_reader: EventReader<FullVoxelsCommand>,
+    mut full_voxels_request_writer: EventWriter<FullVoxelsRequest>,
     mut regenerate_part_mesh_writer: EventWriter<RegeneratePartMesh>,
     mut regenerate_colliders_writer: EventWriter<RegenerateColliders>,
     entity_query: Query<(Entity, &NetworkId), With<PartHandle>>,
     mut part_handle_query: Query<&mut PartHandle>,
-    mut parts: ResMut<Parts>
+    mut parts: ResMut<Parts>,
+    // Parts whose voxels have been requested, so they are only requested once
+    mut awaiting_full_voxels: Local<HashSet<NetworkId>>,
 ) {
-    for voxel_update in voxel_update_reader.iter() {
-        if let Some(entity) = lookup(&entity_query, &voxel_update.network_id) {
+    // Updates sent before the full voxels are at or below their revision, so applying the full voxels first loses nothing
+    let full_voxels = full_voxels_reader.iter()
+        .map(|full_voxels| (full_voxels.network_id, full_voxels.revision, &full_voxels.changes));
+    let voxel_updates = voxel_update_reader.iter()
+        .map(|voxel_update| (voxel_update.network_id, voxel_update.revision, &voxel_update.changes));
+
+    for (network_id, revision, changes) in full_voxels.chain(voxel_updates) {
+        if !changes.is_delta() {
+            awaiting_full_voxels.remove(&network_id);
+        }
+
+        if let Some(entity) = lookup(&entity_query, &network_id) {
             if let Ok(mut part_handle) = part_handle_query.get_mut(entity) {
                 if let Some(part) = parts.get_mut(&mut part_handle) {
-                    part.set_voxels(&voxel_update.voxels);
+                    // Parts sent in the initial state already include the changes made up to when they were sent
+                    if revision <= part.revision() {
+                        continue;
+                    }
+
+                    // Part commands are reliable, so a gap means the part's voxels were already out of sync
+                    // The delta would only make them worse, so it is dropped until all of the part's voxels arrive
+                    let expected_revision = part.revision().wrapping_add(1);
+                    if changes.is_delta() && revision != expected_revision {
+                        if awaiting_full_voxels.insert(network_id) {
+                            warn!(
+                                "Part {:?} skipped from revision {} to {}, requesting all of its voxels",
+                                network_id,
+                                part.revision(),
+                                revision
+                            );
+                            full_voxels_request_writer.send(FullVoxelsRequest(network_id));
+                        }
+
+                        continue;
+                    }
+
+                    if let Err(err) = part.apply_changes(changes, revision) {
+                        warn!("Invalid voxel update for part {:?}: {}", network_id, err);
+                        continue;
+                    }
+
                     regenerate_part_mesh_writer.send(RegeneratePartMesh(entity));
                     regenerate_colliders_writer.send(RegenerateColliders(entity));
                 }
@@ -119,26 +206,6 @@ fn regenerate_colliders(
     }
 }
 
-fn send_place_part_requests(
-    mut connection_state: ResMut<ConnectionState>,
-    mut place_part_request_reader: EventReader<PlacePartRequest>
-) {
-    for place_part_request in place_part_request_reader.iter() {
-        let packet: Packet = place_part_request.into();
-        connection_state.client.send((&packet).into(), Channel::PartCommands.into(), SendMode::Reliable);
-    }
-}
-
-fn send_delete_part_requests(
-    mut connection_state: ResMut<ConnectionState>,
-    mut delete_part_request_reader: EventReader<DeletePartRequest>
-) {
-    for delete_part_request in delete_part_request_reader.iter() {
-        let packet: Packet = delete_part_request.into();
-        connection_state.client.send((&packet).into(), Channel::PartCommands.into(), SendMode::Reliable);
-    }
-}
-
 fn place_parts(
     mut place_part_command_reader: EventReader<PlacePartCommand>,
     mut commands: Commands,
@@ -147,8 +214,37 @@ fn place_parts(
     mut materials: ResMut<Assets<BuildingMaterial>>,
     parts: Res<Parts>,
     entity_query: Query<(Entity, &NetworkId)>,
+    local_player_query: Query<&PlayerId, With<LocalPlayer>>,
+    provisional_part_query: Query<(Entity, &ProvisionalPart, &Handle<BuildingMaterial>)>,
 ) {
+    let local_player_id = local_player_query.get_single().ok().copied();
+
     for event in place_part_command_reader.iter() {
+        // Our own requests were already shown, so the provisional part only needs to be confirmed
+        if local_player_id == Some(event.requester) {
+            let provisional_part = provisional_part_query.iter()
+                .find(|(_, provisional_part, _)| provisional_part.request_seq == event.request_seq);
+
+            if let Some((entity, _, material_handle)) = provisional_part {
+                commands.entity(entity)
+                    .insert(event.part_network_id)
+                    .remove::<ProvisionalPart>();
+
+                if let Some(material) = materials.get_mut(material_handle) {
+                    material.color = PART_COLOR;
+                }
+
+                debug!("Confirmed provisional part with entity ID {:?}", entity);
+                continue;
+            }
+        }
+
+        // Part commands sent in the same tick as our initial state can arrive before it, the part is in its chunks too
+        let Some(construct) = lookup(&entity_query, &event.construct_network_id) else {
+            debug!("Skipped part {:?} placed on construct {:?} that hasn't loaded yet", event.part_network_id, event.construct_network_id);
+            continue;
+        };
+
         let transform = Transform::from(event.transform);
         let entity = spawn_part(
             &mut commands,
@@ -159,7 +255,7 @@ fn place_parts(
             parts.get_handle(event.part_id),
             transform,
             event.part_network_id,
-            lookup(&entity_query, &event.construct_network_id).unwrap()
+            construct
         );
         
         debug!("Spawned part with entity ID {:?}", entity);
@@ -178,22 +274,53 @@ fn delete_parts(
     }
 }
 
+fn roll_back_rejected_parts(
+    mut build_request_rejected_reader: EventReader<BuildRequestRejected>,
+    mut commands: Commands,
+    provisional_part_query: Query<(Entity, &ProvisionalPart)>,
+) {
+    for rejection in build_request_rejected_reader.iter() {
+        info!("Build request {} was rejected: {:?}", rejection.request_seq, rejection.reason);
+
+        for (entity, provisional_part) in provisional_part_query.iter() {
+            if provisional_part.request_seq == rejection.request_seq {
+                debug!("Rolled back rejected part with entity ID {:?}", entity);
+                commands.add(DeletePart(entity));
+            }
+        }
+    }
+}
+
+fn expire_provisional_parts(
+    mut commands: Commands,
+    mut provisional_part_query: Query<(Entity, &mut ProvisionalPart)>,
+) {
+    for (entity, mut provisional_part) in provisional_part_query.iter_mut() {
+        provisional_part.ticks_waited += 1;
+
+        if provisional_part.ticks_waited > PROVISIONAL_PART_TIMEOUT_TICKS {
+            debug!("Build request {} wasn't answered, removed provisional part with entity ID {:?}", provisional_part.request_seq, entity);
+            commands.add(DeletePart(entity));
+        }
+    }
+}
+
 pub struct ClientPartPlugin;
 
 impl Plugin for ClientPartPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(PartMeshHandles::new())
             .add_fixed_event::<RegeneratePartMesh>()
-            .add_fixed_event::<VoxelUpdate>()
             .add_systems(FixedUpdate, (
                 update_voxels,
                 regenerate_part_mesh.after(update_voxels),
                 regenerate_colliders.after(update_voxels),
                 free_part_mesh_handles,
-                send_place_part_requests,
-                send_delete_part_requests,
                 place_parts,
+                roll_back_rejected_parts,
                 delete_parts,
-            ).in_set(FixedUpdateSet::Update));
+            ).in_set(FixedUpdateSet::Update))
+            // Answers that arrived this tick have been applied by now, so confirmed parts aren't expired
+            .add_systems(FixedUpdate, expire_provisional_parts.in_set(FixedUpdateSet::PostUpdate));
     }
 }
\ No newline at end of file
@@ -1,28 +1,58 @@
+use std::collections::HashSet;
+
 use bevy::prelude::*;
 use bevy::render::view::RenderLayers;
 use bevy_rapier3d::prelude::*;
 
 use common::entity_lookup::lookup;
-use common::fixed_update::{AddFixedEvent, FixedUpdateSet};
-use common::player_connection::{PlayerConnected, PlayerDisconnected, InitialState};
+use common::fixed_update::FixedUpdateSet;
+use common::network_id::NetworkId;
+use common::player_connection::{PlayerConnected, PlayerDisconnected, InitialState, InitialStateChunk, InitialStateLoaded};
 use common::player::{PlayerId, PlayerName, PlayerBundle};
+use common::snapshot::SnapshotBuffer;
 use common::part::{Parts, PartNetworkRepr, PartId};
 use common::ship::Ship;
 
 use crate::camera::ActiveCamera;
+use crate::client_state::ClientState;
+use crate::construct_replication::ReplicatedConstructBundle;
 use crate::part::spawn_part;
 use crate::building_material::BuildingMaterial;
 use crate::part::meshes::PartMeshHandles;
 use crate::player_controller::{LocalPlayer, PlayerCamera, ActivelyControlled};
+use crate::player_movement::PredictedStates;
 use crate::raycast_selection::SelectionSource;
 
+// How much of the initial state has arrived, for the loading screen
+#[derive(Resource, Debug, Default)]
+pub struct InitialStateProgress {
+    pub loaded_parts: u32,
+    pub part_count: u32,
+    pub loaded_chunks: u32,
+}
+
+impl InitialStateProgress {
+    pub fn fraction(&self) -> f32 {
+        match self.part_count {
+            0 => 1.0,
+            part_count => (self.loaded_parts as f32 / part_count as f32).min(1.0),
+        }
+    }
+}
+
 fn player_connected(
     mut player_connected_reader: EventReader<PlayerConnected>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    player_id_query: Query<&PlayerId>,
 ) {
     for player_connected in player_connected_reader.iter() {
+        // A player that connected in the same tick as us is also in our initial state
+        if player_id_query.iter().any(|id| *id == player_connected.id) {
+            continue;
+        }
+
         info!("{} connected with ID {:?}!", player_connected.name, player_connected.id);
 
         commands.spawn(PbrBundle {
@@ -37,8 +67,10 @@ fn player_connected(
             id: player_connected.id,
             name: player_connected.name.clone(),
             transform: TransformBundle::from_transform(player_connected.transform),
+            // Remote players are moved by the server's snapshots
+            rigid_body: RigidBody::KinematicPositionBased,
             ..Default::default()
-        });
+        }).insert(SnapshotBuffer::default());
     }
 }
 
@@ -59,16 +91,21 @@ fn player_disconnected(
 
 fn initial_state_setup(
     mut commands: Commands,
-    mut mesh_handles: ResMut<PartMeshHandles>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut standard_materials: ResMut<Assets<StandardMaterial>>,
-    mut building_materials: ResMut<Assets<BuildingMaterial>>,
     mut initial_state_reader: EventReader<InitialState>,
-    mut parts: ResMut<Parts>,
-    active_camera_query: Query<Entity, With<ActiveCamera>>
+    active_camera_query: Query<Entity, With<ActiveCamera>>,
+    player_id_query: Query<&PlayerId>,
 ) {
     for initial_state in initial_state_reader.iter() {
+        commands.insert_resource(InitialStateProgress { loaded_parts: 0, part_count: initial_state.part_count, loaded_chunks: 0 });
+
         for (id, name, transform) in initial_state.players.iter() {
+            // Players that connected while the initial state was on its way have already been spawned
+            if player_id_query.iter().any(|existing_id| existing_id == id) {
+                continue;
+            }
+
             let player = commands.spawn(PbrBundle {
                 mesh: meshes.add(Mesh::from(shape::Capsule {
                         radius: 0.5,
@@ -92,6 +129,7 @@ fn initial_state_setup(
                 commands.entity(player)
                     .insert(LocalPlayer)
                     .insert(ActivelyControlled)
+                    .insert(PredictedStates::default())
                     // Make the controller player invisible to the first person camera
                     .insert(RenderLayers::from_layers(&[1]))
                     .with_children(|parent| {
@@ -103,18 +141,70 @@ fn initial_state_setup(
                             .insert(PlayerCamera)
                             .insert(ActiveCamera);
                     });
+            } else {
+                commands.entity(player)
+                    .insert(RigidBody::KinematicPositionBased)
+                    .insert(SnapshotBuffer::default());
             }
         }
 
-        let construct = commands.spawn(RigidBody::Dynamic)
-            .insert(VisibilityBundle::default())
-            .insert(TransformBundle::from_transform(Transform::from(initial_state.construct_transform)))
-            .insert(Velocity::default())
-            .insert(initial_state.construct_network_id)
-            .insert(Ship)
-            .id();
+        for (network_id, transform, access) in initial_state.constructs.iter() {
+            // Constructs start out interpolated, until the local player gets close enough to simulate them
+            commands.spawn(RigidBody::KinematicPositionBased)
+                .insert(VisibilityBundle::default())
+                .insert(TransformBundle::from_transform(Transform::from(*transform)))
+                .insert(Velocity::default())
+                .insert(*network_id)
+                .insert(Ship)
+                .insert(access.clone())
+                .insert(ReplicatedConstructBundle::default());
+        }
+    }
+}
+
+fn initial_state_chunk_setup(
+    mut commands: Commands,
+    mut mesh_handles: ResMut<PartMeshHandles>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut building_materials: ResMut<Assets<BuildingMaterial>>,
+    mut chunk_reader: EventReader<InitialStateChunk>,
+    mut initial_state_loaded_writer: EventWriter<InitialStateLoaded>,
+    mut parts: ResMut<Parts>,
+    mut progress: ResMut<InitialStateProgress>,
+    construct_query: Query<(Entity, &NetworkId), With<Ship>>,
+    network_id_query: Query<&NetworkId>,
+    mut next_state: ResMut<NextState<ClientState>>,
+) {
+    if chunk_reader.is_empty() {
+        return;
+    }
+
+    // Parts placed while the initial state was on its way may have been spawned already
+    let existing_ids: HashSet<NetworkId> = network_id_query.iter().copied().collect();
+
+    for chunk in chunk_reader.iter() {
+        progress.loaded_chunks += 1;
+
+        let construct = chunk.construct_network_id.and_then(|construct_network_id| {
+            let construct = lookup(&construct_query, &construct_network_id);
+            if construct.is_none() {
+                warn!("Received initial state chunk for unknown construct {:?}", construct_network_id);
+            }
+
+            construct
+        });
+
+        for (part_network_repr, transform, network_id) in chunk.parts.iter() {
+            progress.loaded_parts += 1;
+
+            let Some(construct) = construct else {
+                continue;
+            };
+
+            if existing_ids.contains(network_id) {
+                continue;
+            }
 
-        for (part_network_repr, transform, network_id) in initial_state.parts.iter() {
             let part_handle = match part_network_repr {
                 PartNetworkRepr::Predefined(part_id) => {
                     parts.get_handle(PartId::from(*part_id))
@@ -136,20 +226,30 @@ fn initial_state_setup(
                 construct
             );
         }
+
+        if chunk.last {
+            info!("Loaded the initial state");
+            initial_state_loaded_writer.send(InitialStateLoaded { chunk_count: progress.loaded_chunks });
+            next_state.set(ClientState::InGame);
+        }
     }
 }
 
-pub struct PlayerConnectionPlugin;
+pub struct ClientPlayerConnectionPlugin;
 
-impl Plugin for PlayerConnectionPlugin {
+impl Plugin for ClientPlayerConnectionPlugin {
     fn build(&self, app: &mut App) {
-        app.add_fixed_event::<PlayerConnected>()
-            .add_fixed_event::<PlayerDisconnected>()
-            .add_fixed_event::<InitialState>()
-            .add_systems(FixedUpdate, (
-                player_connected,
+        app.add_systems(FixedUpdate, (
+                // The players and construct spawned from the initial state have to exist before the chunks and connected players are handled
+                (
+                    initial_state_setup.run_if(on_event::<InitialState>()),
+                    apply_deferred,
+                    (
+                        initial_state_chunk_setup.run_if(on_event::<InitialStateChunk>().and_then(resource_exists::<InitialStateProgress>())),
+                        player_connected,
+                    ),
+                ).chain(),
                 player_disconnected,
-                initial_state_setup.run_if(on_event::<InitialState>()),
             ).in_set(FixedUpdateSet::Update));
     }
 }
\ No newline at end of file
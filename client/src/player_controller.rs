@@ -4,6 +4,7 @@ use bevy_rapier3d::prelude::*;
 
 use common::fixed_update::FixedUpdateSet;
 use common::PHYSICS_TIMESTEP;
+use common::player_movement::{MAX_MOVE_IMPULSE, MAX_TORQUE_IMPULSE};
 
 use crate::camera::ActiveCamera;
 use crate::fixed_input::{FixedInput, FixedMouseMotion};
@@ -18,7 +19,7 @@ pub struct ActivelyControlled;
 #[derive(Component)]
 pub struct PlayerCamera;
 
-fn player_movement(
+pub fn player_movement(
     keys: Res<FixedInput<KeyCode>>,
     mut player_data_query: Query<(&mut ExternalImpulse, &Transform), (With<LocalPlayer>, With<ActivelyControlled>)>,
     mut motion_reader: EventReader<FixedMouseMotion>,
@@ -79,8 +80,9 @@ fn player_movement(
         rotate_vector += player_transform.down() * motion.delta.x * settings.first_person_sensitivity * scale_factor;
     }
 
-    external_impulse.impulse = move_direction.normalize_or_zero() * PHYSICS_TIMESTEP * 50.0;
-    external_impulse.torque_impulse = rotate_vector * PHYSICS_TIMESTEP;
+    // Clamped the same way as on the server, so that the prediction matches
+    external_impulse.impulse = move_direction.normalize_or_zero() * MAX_MOVE_IMPULSE;
+    external_impulse.torque_impulse = (rotate_vector * PHYSICS_TIMESTEP).clamp_length_max(MAX_TORQUE_IMPULSE);
 }
 
 fn cursor_lock(
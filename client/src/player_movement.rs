@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use common::compact_transform::CompactTransform;
+use common::fixed_update::{FixedUpdateSet, NetworkSendSet};
+use common::player::PlayerId;
+use common::player_movement::{PlayerInput, PlayerStates};
+use common::snapshot::{InterpolationClock, Snapshot, SnapshotBuffer};
+
+use crate::player_controller::{LocalPlayer, player_movement};
+
+// Predicted states older than this many inputs are forgotten
+const MAX_PREDICTIONS: usize = 120;
+// Differences from the server smaller than this are left alone
+const CORRECTION_THRESHOLD: f32 = 0.05;
+// Differences larger than this are corrected at once instead of smoothly
+const SNAP_THRESHOLD: f32 = 3.0;
+// The fraction of the difference corrected for each snapshot
+const CORRECTION_RATE: f32 = 0.2;
+
+// The local player's state after each input, to compare against the server's state for the same input
+#[derive(Component, Debug, Default)]
+pub struct PredictedStates {
+    sequence: u32,
+    states: VecDeque<(u32, Vec3, Quat)>,
+}
+
+fn send_player_input(
+    mut player_query: Query<(&ExternalImpulse, &mut PredictedStates), With<LocalPlayer>>,
+    mut player_input_writer: EventWriter<PlayerInput>,
+) {
+    let Ok((external_impulse, mut predicted_states)) = player_query.get_single_mut() else {
+        return;
+    };
+
+    // Inputs are sent every tick, even without movement, so every predicted state has an input to match
+    predicted_states.sequence += 1;
+
+    player_input_writer.send(PlayerInput {
+        sequence: predicted_states.sequence,
+        impulse: external_impulse.impulse,
+        torque_impulse: external_impulse.torque_impulse,
+    });
+}
+
+fn record_predicted_state(
+    mut player_query: Query<(&Transform, &mut PredictedStates), With<LocalPlayer>>,
+) {
+    let Ok((transform, mut predicted_states)) = player_query.get_single_mut() else {
+        return;
+    };
+
+    let sequence = predicted_states.sequence;
+    predicted_states.states.push_back((sequence, transform.translation, transform.rotation));
+
+    if predicted_states.states.len() > MAX_PREDICTIONS {
+        predicted_states.states.pop_front();
+    }
+}
+
+// Moves the local player towards where the server had it, for the difference between the server and our prediction
+fn correct_local_player(
+    transform: &mut Transform,
+    predicted_states: &mut PredictedStates,
+    last_input: u32,
+    server_transform: CompactTransform,
+) {
+    predicted_states.states.retain(|&(sequence, _, _)| sequence >= last_input);
+
+    let Some(&(_, predicted_translation, predicted_rotation)) = predicted_states.states.front() else {
+        return;
+    };
+
+    let translation_error = server_transform.translation - predicted_translation;
+    let rotation_error = server_transform.rotation * predicted_rotation.inverse();
+
+    if translation_error.length() < CORRECTION_THRESHOLD && rotation_error.angle_between(Quat::IDENTITY) < CORRECTION_THRESHOLD {
+        return;
+    }
+
+    let rate = if translation_error.length() > SNAP_THRESHOLD { 1.0 } else { CORRECTION_RATE };
+    let translation_correction = translation_error * rate;
+    let rotation_correction = Quat::IDENTITY.slerp(rotation_error, rate);
+
+    transform.translation += translation_correction;
+    transform.rotation = (rotation_correction * transform.rotation).normalize();
+
+    // The newer predictions had the same error, which has now been corrected
+    for (_, translation, rotation) in predicted_states.states.iter_mut() {
+        *translation += translation_correction;
+        *rotation = (rotation_correction * *rotation).normalize();
+    }
+}
+
+fn apply_player_states(
+    mut player_states_reader: EventReader<PlayerStates>,
+    mut clock: ResMut<InterpolationClock>,
+    mut local_player_query: Query<(&PlayerId, &mut Transform, &mut PredictedStates), With<LocalPlayer>>,
+    mut remote_player_query: Query<(&PlayerId, &mut SnapshotBuffer), Without<LocalPlayer>>,
+) {
+    for player_states in player_states_reader.iter() {
+        clock.observe(player_states.tick);
+
+        for player_state in player_states.players.iter() {
+            if let Ok((local_id, mut transform, mut predicted_states)) = local_player_query.get_single_mut() {
+                if *local_id == player_state.id {
+                    correct_local_player(&mut transform, &mut predicted_states, player_state.last_input, player_state.transform);
+                    continue;
+                }
+            }
+
+            let Some((_, mut snapshots)) = remote_player_query.iter_mut().find(|(id, _)| **id == player_state.id) else {
+                continue;
+            };
+
+            snapshots.push(Snapshot {
+                tick: player_states.tick,
+                translation: player_state.transform.translation,
+                rotation: player_state.transform.rotation,
+            });
+        }
+    }
+}
+
+pub fn advance_interpolation_clock(mut clock: ResMut<InterpolationClock>) {
+    clock.advance();
+}
+
+fn interpolate_remote_players(
+    clock: Res<InterpolationClock>,
+    mut remote_player_query: Query<(&mut Transform, &SnapshotBuffer), (With<PlayerId>, Without<LocalPlayer>)>,
+) {
+    let Some(playback_tick) = clock.playback_tick() else {
+        return;
+    };
+
+    for (mut transform, snapshots) in remote_player_query.iter_mut() {
+        if let Some((translation, rotation)) = snapshots.sample(playback_tick) {
+            transform.translation = translation;
+            transform.rotation = rotation;
+        }
+    }
+}
+
+pub struct ClientPlayerMovementPlugin;
+
+impl Plugin for ClientPlayerMovementPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InterpolationClock>()
+            .add_systems(FixedUpdate, (
+                (
+                    apply_player_states,
+                    advance_interpolation_clock,
+                    interpolate_remote_players,
+                ).chain().in_set(FixedUpdateSet::Update),
+                send_player_input.in_set(FixedUpdateSet::Update).after(player_movement),
+                record_predicted_state.in_set(FixedUpdateSet::PostUpdate).before(NetworkSendSet),
+            ));
+    }
+}
\ No newline at end of file
@@ -7,6 +7,8 @@ pub struct Settings {
     pub camera_speed: f32,
     pub fullscreen: bool,
     pub draw_debug: bool,
+    // How long the server can go without answering before the client disconnects
+    pub active_timeout_ms: u64,
 }
 
 impl Default for Settings {
@@ -17,6 +19,7 @@ impl Default for Settings {
             camera_speed: 12.5,
             fullscreen: false,
             draw_debug: true,
+            active_timeout_ms: 10000,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+
+use common::construct_access::ConstructAccess;
+use common::fixed_update::{SetupFixedTimeStepSchedule, SetupRapier};
+use common::network_id::NetworkId;
+use common::part::{PartId, Parts};
+use common::ship::{Ship, ShipBundle};
+use common::transport::loopback::{LinkConditions, LoopbackNetwork};
+use ship_designer_client::app_setup::SetupClientSpecific;
+use ship_designer_client::client_state::{ClientState, ConnectionSettings};
+use ship_designer_client::connection_state::ConnectionState;
+use ship_designer_server::app_setup::{SetupBevyPlugins as SetupServerBevyPlugins, SetupServerSpecific, setup_hardcoded_parts};
+use ship_designer_server::network_id_generator::NetworkIdGenerator;
+use ship_designer_server::part::spawn_part;
+use ship_designer_server::server_state::ServerState;
+
+use crate::scaffolding::{FixedUpdate, SetupBevyPlugins};
+
+// Clients that haven't loaded into the game after this many steps are assumed to be stuck
+const MAX_CONNECT_STEPS: u32 = 120;
+
+// The same construct the server binary starts with, a single part at the origin
+fn spawn_construct(
+    mut commands: Commands,
+    mut network_id_generator: ResMut<NetworkIdGenerator>,
+    parts: Res<Parts>,
+) {
+    let construct = commands.spawn(ShipBundle {
+        network_id: network_id_generator.generate(),
+        // Anyone who joins can build on it
+        access: ConstructAccess::open(),
+        ..Default::default()
+    }).id();
+
+    let part_handle = parts.get_handle(PartId::from(0));
+
+    spawn_part(
+        &mut commands,
+        &parts,
+        part_handle,
+        Transform::from_xyz(0.0, 0.0, 0.0),
+        network_id_generator.generate(),
+        construct
+    );
+}
+
+fn server_app(network: &LoopbackNetwork) -> App {
+    let mut app = App::new();
+
+    // The clients set up logging for the whole process
+    SetupServerBevyPlugins::setup_bevy_plugins(&mut app)
+        .setup_fixed_timestep_schedule()
+        .setup_rapier()
+        .setup_server_specific()
+        .add_systems(Startup, spawn_construct.after(setup_hardcoded_parts))
+        .insert_non_send_resource(ServerState::new(network.server()));
+
+    app
+}
+
+fn client_app(network: &LoopbackNetwork, player_name: String) -> App {
+    let mut app = App::new();
+
+    SetupBevyPlugins::setup_bevy_plugins(&mut app)
+        .setup_fixed_timestep_schedule()
+        .setup_rapier()
+        .setup_client_specific()
+        .insert_resource(ConnectionSettings { player_name, ..Default::default() })
+        .insert_resource(ConnectionState::new(network.connect()));
+
+    // Skip the main menu, as if connect had been clicked
+    app.world.resource_mut::<NextState<ClientState>>().set(ClientState::Connecting);
+
+    app
+}
+
+// A server and its clients in one process, connected over a loopback network
+// Each step runs one fixed update on the server and then on every client, so message timing only depends on the link conditions
+pub struct Lockstep {
+    pub network: LoopbackNetwork,
+    pub server: App,
+    pub clients: Vec<App>,
+}
+
+impl Lockstep {
+    pub fn new(client_count: usize) -> Self {
+        Self::with_conditions(client_count, LinkConditions::default(), 0)
+    }
+
+    pub fn with_conditions(client_count: usize, conditions: LinkConditions, seed: u64) -> Self {
+        let network = LoopbackNetwork::new(conditions, seed);
+        let server = server_app(&network);
+        let clients = (0..client_count)
+            .map(|index| client_app(&network, format!("Player {}", index + 1)))
+            .collect();
+
+        Self { network, server, clients }
+    }
+
+    pub fn step(&mut self) {
+        self.server.fixed_update();
+
+        for client in self.clients.iter_mut() {
+            client.fixed_update();
+        }
+    }
+
+    // Returns whether the condition was met within max_steps
+    pub fn step_until(&mut self, max_steps: u32, mut condition: impl FnMut(&mut Self) -> bool) -> bool {
+        for _ in 0..max_steps {
+            if condition(self) {
+                return true;
+            }
+
+            self.step();
+        }
+
+        condition(self)
+    }
+
+    // Steps until every client has loaded the initial state
+    pub fn connect_all(&mut self) {
+        let connected = self.step_until(MAX_CONNECT_STEPS, |lockstep| {
+            lockstep.clients.iter().all(|client| *client.world.resource::<State<ClientState>>().get() == ClientState::InGame)
+        });
+
+        assert!(connected, "Clients didn't load into the game within {} steps", MAX_CONNECT_STEPS);
+    }
+}
+
+// The network ID of the first construct in the app's world
+pub fn construct_network_id(app: &mut App) -> NetworkId {
+    *app.world.query_filtered::<&NetworkId, With<Ship>>()
+        .iter(&app.world)
+        .next()
+        .expect("There is no construct")
+}
\ No newline at end of file
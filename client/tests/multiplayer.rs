@@ -0,0 +1,260 @@
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+use common::compact_transform::CompactTransform;
+use common::missile::SpawnMissileRequest;
+use common::network_id::NetworkId;
+use common::network_message::FromPlayer;
+use common::part::events::{PlacePartRequest, VoxelUpdate};
+use common::part::materials::Material;
+use common::part::voxel_changes::VoxelChanges;
+use common::part::{PartHandle, PartId, Parts, VoxelPos};
+use common::player::PlayerId;
+use common::ship::Ship;
+use common::transport::loopback::LinkConditions;
+use lockstep::{Lockstep, construct_network_id};
+use ship_designer_client::client_state::ClientState;
+use ship_designer_client::part::{ProvisionalPart, PROVISIONAL_PART_TIMEOUT_TICKS};
+use ship_designer_client::player_connection::InitialStateProgress;
+use ship_designer_server::network_id_generator::NetworkIdGenerator;
+use ship_designer_server::part::spawn_part;
+use ship_designer_server::server_state::ServerState;
+
+mod lockstep;
+mod scaffolding;
+
+fn part_network_ids(app: &mut App) -> HashSet<NetworkId> {
+    app.world.query_filtered::<&NetworkId, With<PartHandle>>()
+        .iter(&app.world)
+        .copied()
+        .collect()
+}
+
+// Whether the part has lost any voxels, or has been destroyed entirely
+fn part_is_damaged(app: &mut App, network_id: NetworkId) -> bool {
+    let mut part_query = app.world.query::<(&NetworkId, &PartHandle)>();
+    let parts = app.world.resource::<Parts>();
+
+    let Some((_, part_handle)) = part_query.iter(&app.world).find(|(id, _)| **id == network_id) else {
+        return true;
+    };
+
+    parts.get(part_handle).unwrap().voxels().contains(&Material::Empty)
+}
+
+// The part's revision and voxels, if it still exists
+fn part_voxels(app: &mut App, network_id: NetworkId) -> Option<(u32, Vec<Material>)> {
+    let mut part_query = app.world.query::<(&NetworkId, &PartHandle)>();
+    let parts = app.world.resource::<Parts>();
+
+    let (_, part_handle) = part_query.iter(&app.world).find(|(id, _)| **id == network_id)?;
+    let part = parts.get(part_handle).unwrap();
+
+    Some((part.revision(), part.voxels().to_vec()))
+}
+
+// Empties a voxel of the part on the server, and returns the VoxelUpdate for it without sending it
+fn damage_part(app: &mut App, network_id: NetworkId, pos: VoxelPos) -> VoxelUpdate {
+    app.world.resource_scope(|world, mut parts: Mut<Parts>| {
+        let mut part_query = world.query::<(&NetworkId, &mut PartHandle)>();
+        let (_, mut part_handle) = part_query.iter_mut(world).find(|(id, _)| **id == network_id).unwrap();
+        let part = parts.get_mut(&mut part_handle).unwrap();
+
+        let old_voxels = part.voxels().to_vec();
+        part.set(pos, Material::Empty);
+        let changes = VoxelChanges::between(&old_voxels, part.voxels());
+
+        VoxelUpdate { network_id, revision: part.next_revision(), changes }
+    })
+}
+
+// Each damaged cube is sent with all of its voxels, so they are too big to share a chunk
+const DAMAGED_PART_COUNT: u32 = 40;
+
+fn spawn_damaged_parts(
+    mut commands: Commands,
+    mut network_id_generator: ResMut<NetworkIdGenerator>,
+    mut parts: ResMut<Parts>,
+    construct_query: Query<Entity, With<Ship>>,
+) {
+    let construct = construct_query.single();
+
+    for i in 0..DAMAGED_PART_COUNT {
+        let mut part = parts.clone_part_from_part_id(PartId::from(0));
+        part.set(VoxelPos::new((i % 10) as u8, (i / 10) as u8, 0), Material::Empty);
+        let part_handle = parts.add(part);
+
+        spawn_part(
+            &mut commands,
+            &parts,
+            part_handle,
+            Transform::from_xyz(0.0, 0.0, 2.0 * (i + 1) as f32),
+            network_id_generator.generate(),
+            construct
+        );
+    }
+}
+
+#[test]
+fn every_client_sees_every_player() {
+    let mut lockstep = Lockstep::new(3);
+    lockstep.connect_all();
+
+    for client in lockstep.clients.iter_mut() {
+        assert_eq!(client.world.query::<&PlayerId>().iter(&client.world).len(), 3);
+    }
+}
+
+#[test]
+fn part_placed_by_one_client_appears_on_another() {
+    let mut lockstep = Lockstep::new(2);
+    lockstep.connect_all();
+    lockstep.network.set_conditions(LinkConditions { latency: 2, jitter: 2, loss: 0.2, reorder: 0.2 });
+
+    let construct_network_id = construct_network_id(&mut lockstep.clients[0]);
+    let parts_before = part_network_ids(&mut lockstep.clients[1]);
+
+    lockstep.clients[0].world.resource_mut::<Events<PlacePartRequest>>().send(PlacePartRequest {
+        request_seq: 1,
+        part_id: PartId::from(0),
+        part_transform: CompactTransform::from(Transform::from_xyz(0.0, 0.0, 2.0)),
+        construct_network_id,
+    });
+
+    let placed = lockstep.step_until(60, |lockstep| {
+        lockstep.clients.iter_mut().all(|client| part_network_ids(client).len() > parts_before.len())
+    });
+    assert!(placed);
+
+    let server_parts = part_network_ids(&mut lockstep.server);
+    assert_eq!(part_network_ids(&mut lockstep.clients[0]), server_parts);
+    assert_eq!(part_network_ids(&mut lockstep.clients[1]), server_parts);
+}
+
+#[test]
+fn unanswered_provisional_part_expires() {
+    let mut lockstep = Lockstep::new(1);
+    lockstep.connect_all();
+
+    let construct_network_id = construct_network_id(&mut lockstep.clients[0]);
+
+    // Nothing gets through, so the server never answers
+    lockstep.network.set_conditions(LinkConditions { loss: 1.0, ..Default::default() });
+    lockstep.clients[0].world.resource_mut::<Events<PlacePartRequest>>().send(PlacePartRequest {
+        request_seq: 1,
+        part_id: PartId::from(0),
+        part_transform: CompactTransform::from(Transform::from_xyz(0.0, 0.0, 2.0)),
+        construct_network_id,
+    });
+
+    lockstep.step();
+    assert_eq!(lockstep.clients[0].world.query::<&ProvisionalPart>().iter(&lockstep.clients[0].world).len(), 1);
+
+    let expired = lockstep.step_until(PROVISIONAL_PART_TIMEOUT_TICKS + 2, |lockstep| {
+        lockstep.clients[0].world.query::<&ProvisionalPart>().iter(&lockstep.clients[0].world).len() == 0
+    });
+    assert!(expired);
+}
+
+#[test]
+fn missile_explosion_reaches_every_client() {
+    let mut lockstep = Lockstep::new(3);
+    lockstep.connect_all();
+
+    let target = *part_network_ids(&mut lockstep.server).iter().next().unwrap();
+
+    // Dropped straight onto the part at the origin
+    lockstep.clients[0].world.resource_mut::<Events<SpawnMissileRequest>>().send(SpawnMissileRequest {
+        transform: CompactTransform::from(Transform::from_xyz(0.0, 3.0, 0.0)),
+        velocity: Vec3::new(0.0, -30.0, 0.0),
+    });
+
+    let exploded = lockstep.step_until(60, |lockstep| {
+        part_is_damaged(&mut lockstep.server, target)
+            && lockstep.clients.iter_mut().all(|client| part_is_damaged(client, target))
+    });
+    assert!(exploded);
+
+    // Only the changed voxels were sent, which has to leave every client with the server's voxels
+    let server_voxels = part_voxels(&mut lockstep.server, target);
+    for client in lockstep.clients.iter_mut() {
+        assert_eq!(part_voxels(client, target), server_voxels);
+    }
+}
+
+#[test]
+fn large_construct_is_streamed_before_the_game_starts() {
+    let mut lockstep = Lockstep::new(1);
+    lockstep.server.add_systems(PostStartup, spawn_damaged_parts);
+
+    let header_arrived = lockstep.step_until(60, |lockstep| lockstep.clients[0].world.contains_resource::<InitialStateProgress>());
+    assert!(header_arrived);
+
+    // The parts are still on their way, and the server holds back the player's packets until it has loaded them
+    let progress = lockstep.clients[0].world.resource::<InitialStateProgress>();
+    assert_eq!(progress.part_count, DAMAGED_PART_COUNT + 1);
+    assert!(progress.loaded_parts < progress.part_count);
+    assert_eq!(*lockstep.clients[0].world.resource::<State<ClientState>>().get(), ClientState::LoadingInitialState);
+
+    let player_id = *lockstep.server.world.query::<&PlayerId>().single(&lockstep.server.world);
+    assert!(lockstep.server.world.non_send_resource::<ServerState>().is_loading(player_id));
+
+    lockstep.connect_all();
+
+    let acknowledged = lockstep.step_until(10, |lockstep| {
+        !lockstep.server.world.non_send_resource::<ServerState>().is_loading(player_id)
+    });
+    assert!(acknowledged);
+
+    let server_parts = part_network_ids(&mut lockstep.server);
+    assert_eq!(part_network_ids(&mut lockstep.clients[0]), server_parts);
+
+    for network_id in server_parts {
+        assert_eq!(part_voxels(&mut lockstep.clients[0], network_id), part_voxels(&mut lockstep.server, network_id));
+    }
+}
+
+#[test]
+fn request_sent_while_loading_is_handled_once_loaded() {
+    let mut lockstep = Lockstep::new(1);
+    lockstep.server.add_systems(PostStartup, spawn_damaged_parts);
+
+    let header_arrived = lockstep.step_until(60, |lockstep| lockstep.clients[0].world.contains_resource::<InitialStateProgress>());
+    assert!(header_arrived);
+
+    // Sent on another channel than the initial state, so only the server holding it back keeps it from being handled early
+    lockstep.clients[0].world.resource_mut::<Events<SpawnMissileRequest>>().send(SpawnMissileRequest {
+        transform: CompactTransform::from(Transform::from_xyz(0.0, 100.0, 0.0)),
+        velocity: Vec3::new(0.0, 30.0, 0.0),
+    });
+
+    let player_id = *lockstep.server.world.query::<&PlayerId>().single(&lockstep.server.world);
+    let mut received_while_loading = false;
+    let received = lockstep.step_until(120, |lockstep| {
+        let received = !lockstep.server.world.resource::<Events<FromPlayer<SpawnMissileRequest>>>().is_empty();
+        received_while_loading |= received && lockstep.server.world.non_send_resource::<ServerState>().is_loading(player_id);
+        received
+    });
+
+    assert!(received);
+    assert!(!received_while_loading);
+}
+
+#[test]
+fn part_that_missed_a_voxel_update_is_sent_all_of_its_voxels() {
+    let mut lockstep = Lockstep::new(1);
+    lockstep.connect_all();
+
+    let target = *part_network_ids(&mut lockstep.server).iter().next().unwrap();
+
+    // The first update never reaches the client, so the second one can't be applied on top of its voxels
+    damage_part(&mut lockstep.server, target, VoxelPos::new(0, 0, 0));
+    let voxel_update = damage_part(&mut lockstep.server, target, VoxelPos::new(1, 0, 0));
+    assert!(voxel_update.changes.is_delta());
+    lockstep.server.world.send_event(voxel_update);
+
+    let synced = lockstep.step_until(60, |lockstep| {
+        part_voxels(&mut lockstep.clients[0], target) == part_voxels(&mut lockstep.server, target)
+    });
+    assert!(synced);
+}
\ No newline at end of file
@@ -0,0 +1,96 @@
+use bevy::utils::HashMap;
+use packets::Packet;
+use packets::batch::PacketBatcher;
+
+use crate::channels::Channel;
+
+// Packets waiting to be sent to one endpoint this tick
+// Each channel is batched separately, since uflow only keeps datagrams in order within a channel
+#[derive(Debug, Default)]
+pub struct OutgoingBatches {
+    batchers: HashMap<Channel, PacketBatcher>,
+}
+
+impl OutgoingBatches {
+    pub fn push(&mut self, channel: Channel, packet: &Packet) {
+        self.batchers.entry(channel).or_default().push(packet);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.batchers.values().all(PacketBatcher::is_empty)
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = (Channel, Box<[u8]>)> + '_ {
+        self.batchers.iter_mut()
+            .flat_map(|(&channel, batcher)| batcher.drain().map(move |batch| (channel, batch)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use packets::{Packet, PacketSerialize, PacketDeserialize, PacketType};
+    use packets::batch::Unbatcher;
+
+    use crate::batching::OutgoingBatches;
+    use crate::channels::Channel;
+
+    fn numbered_packet(number: u32) -> Packet {
+        let mut packet = Packet::new(PacketType::VoxelUpdate);
+        number.serialize(&mut packet);
+
+        packet
+    }
+
+    // The numbers of the packets received on the channel, in the order uflow would deliver them
+    fn received_numbers(batches: &[(Channel, Box<[u8]>)], channel: Channel) -> Vec<u32> {
+        batches.iter()
+            .filter(|(batch_channel, _)| *batch_channel == channel)
+            .flat_map(|(_, batch)| Unbatcher::new(batch.clone()))
+            .map(|packet| u32::deserialize(&mut packet.unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn order_is_kept_within_each_channel() {
+        let mut outgoing_batches = OutgoingBatches::default();
+
+        for number in 0..10 {
+            let channel = if number % 2 == 0 { Channel::PartCommands } else { Channel::Missile };
+            outgoing_batches.push(channel, &numbered_packet(number));
+        }
+
+        let batches: Vec<_> = outgoing_batches.drain().collect();
+
+        assert_eq!(received_numbers(&batches, Channel::PartCommands), vec![0, 2, 4, 6, 8]);
+        assert_eq!(received_numbers(&batches, Channel::Missile), vec![1, 3, 5, 7, 9]);
+        assert!(outgoing_batches.is_empty());
+    }
+
+    #[test]
+    fn order_is_kept_across_batches() {
+        let mut outgoing_batches = OutgoingBatches::default();
+
+        for number in 0..1000 {
+            outgoing_batches.push(Channel::Missile, &numbered_packet(number));
+            outgoing_batches.push(Channel::PartCommands, &numbered_packet(number + 1000));
+        }
+
+        let batches: Vec<_> = outgoing_batches.drain().collect();
+
+        assert!(batches.len() > 2);
+        assert_eq!(received_numbers(&batches, Channel::Missile), (0..1000).collect::<Vec<_>>());
+        assert_eq!(received_numbers(&batches, Channel::PartCommands), (1000..2000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn one_datagram_per_channel_for_small_packets() {
+        let mut outgoing_batches = OutgoingBatches::default();
+
+        for number in 0..100 {
+            outgoing_batches.push(Channel::Missile, &numbered_packet(number));
+        }
+        outgoing_batches.push(Channel::PartCommands, &numbered_packet(0));
+
+        assert_eq!(outgoing_batches.drain().count(), 2);
+    }
+}
\ No newline at end of file
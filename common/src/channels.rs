@@ -1,9 +1,22 @@
 use num_enum::IntoPrimitive;
+use uflow::SendMode;
 
-#[derive(IntoPrimitive)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, IntoPrimitive)]
 #[repr(usize)]
 pub enum Channel {
     PlayerConnectionEvents,
     PartCommands,
     Missile,
+    // Sent every few ticks, so a lost packet is replaced by the next one instead of being resent
+    PlayerMovement,
+    ConstructStates,
+}
+
+impl Channel {
+    pub fn send_mode(&self) -> SendMode {
+        match self {
+            Channel::PlayerMovement | Channel::ConstructStates => SendMode::Unreliable,
+            _ => SendMode::Reliable,
+        }
+    }
 }
\ No newline at end of file
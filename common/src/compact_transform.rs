@@ -1,6 +1,8 @@
 use bevy::prelude::{Transform, Vec3, Quat, GlobalTransform};
 use packets_derive::{PacketSerialize, PacketDeserialize};
 
+use crate::part::VOXEL_SIZE;
+
 #[derive(Clone, Copy, Debug, PacketSerialize, PacketDeserialize)]
 pub struct CompactTransform {
     pub translation: Vec3,
@@ -45,4 +47,183 @@ impl From<GlobalTransform> for CompactTransform {
             rotation,
         }
     }
+}
+
+// Compact encoding for transforms relative to a construct, used with #[packet(with = quantized)]
+// Translations on the half voxel grid (the centers of odd sized parts are between voxels) are sent as steps
+// on that grid, other translations fall back to full precision
+// Rotations are always sent with smallest-three compression, which is close enough to keep quarter turns aligned
+pub mod quantized {
+    use bevy::prelude::{Vec3, Quat};
+    use packets::{Packet, PacketSerialize, PacketDeserialize, PacketError};
+    use packets::schema::{fingerprint, combine};
+
+    use super::{CompactTransform, VOXEL_SIZE};
+
+    const TRANSLATION_STEP: f32 = VOXEL_SIZE / 2.0;
+    const GRID_TOLERANCE: f32 = 1e-4;
+
+    // Each of the three smallest components gets ROTATION_BITS bits, after 2 bits for the index of the largest
+    const ROTATION_BITS: u32 = 15;
+    const ROTATION_BYTES: usize = 6;
+    const ROTATION_MASK: u64 = (1 << ROTATION_BITS) - 1;
+    // One less than the largest value so that 0 is exactly representable
+    const ROTATION_SCALE: f32 = (ROTATION_MASK - 1) as f32;
+    // The smallest three components of a unit quaternion are within ±1/√2
+    const MAX_SMALLEST: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+    // Folded into the fingerprint of every field sent with this encoding
+    // The grid step is in micrometres, since floats can't be hashed in a const
+    pub const FINGERPRINT: u64 = combine(&[
+        fingerprint("on grid: bool, translation: [i16; 3] steps or Vec3, rotation: largest index then smallest three, little endian"),
+        (TRANSLATION_STEP * 1_000_000.0) as u64,
+        ROTATION_BITS as u64,
+        ROTATION_BYTES as u64,
+    ]);
+
+    fn quantize_translation(translation: Vec3) -> Option<[i16; 3]> {
+        let steps = (translation / TRANSLATION_STEP).round();
+        let on_grid = (steps * TRANSLATION_STEP - translation).abs().max_element() <= GRID_TOLERANCE;
+        let in_range = steps.abs().max_element() <= i16::MAX as f32;
+
+        if on_grid && in_range {
+            Some(steps.to_array().map(|step| step as i16))
+        } else {
+            None
+        }
+    }
+
+    fn quantize_rotation(rotation: Quat) -> u64 {
+        let mut components = rotation.normalize().to_array();
+        let largest = (0..4)
+            .max_by(|&a, &b| components[a].abs().total_cmp(&components[b].abs()))
+            .unwrap();
+
+        // q and -q are the same rotation, so the largest component can always be positive
+        if components[largest] < 0.0 {
+            components = components.map(|component| -component);
+        }
+
+        let mut bits = largest as u64;
+        for (_, component) in components.iter().enumerate().filter(|&(i, _)| i != largest) {
+            let normalized = (component / MAX_SMALLEST * 0.5 + 0.5).clamp(0.0, 1.0);
+            bits = (bits << ROTATION_BITS) | (normalized * ROTATION_SCALE).round() as u64;
+        }
+
+        bits
+    }
+
+    fn dequantize_rotation(bits: u64) -> Quat {
+        let largest = (bits >> (3 * ROTATION_BITS)) as usize & 3;
+        let mut components = [0.0; 4];
+        let mut shift = 3 * ROTATION_BITS;
+
+        for i in (0..4).filter(|&i| i != largest) {
+            shift -= ROTATION_BITS;
+            let quantized = (bits >> shift) & ROTATION_MASK;
+            components[i] = (quantized as f32 / ROTATION_SCALE - 0.5) * 2.0 * MAX_SMALLEST;
+        }
+
+        let smallest_sum: f32 = components.iter().map(|component| component * component).sum();
+        components[largest] = (1.0 - smallest_sum).max(0.0).sqrt();
+
+        Quat::from_array(components).normalize()
+    }
+
+    pub fn serialize(value: &CompactTransform, packet: &mut Packet) {
+        match quantize_translation(value.translation) {
+            Some(steps) => {
+                true.serialize(packet);
+                steps.serialize(packet);
+            },
+            None => {
+                false.serialize(packet);
+                value.translation.serialize(packet);
+            }
+        }
+
+        packet.write_bytes(&quantize_rotation(value.rotation).to_le_bytes()[..ROTATION_BYTES]);
+    }
+
+    pub fn deserialize(packet: &mut Packet) -> Result<CompactTransform, PacketError> {
+        let on_grid = bool::deserialize(packet)?;
+
+        let translation = if on_grid {
+            let steps = <[i16; 3]>::deserialize(packet)?;
+            Vec3::from_array(steps.map(|step| step as f32)) * TRANSLATION_STEP
+        } else {
+            Vec3::deserialize(packet)?
+        };
+
+        let mut rotation_bytes = [0; 8];
+        rotation_bytes[..ROTATION_BYTES].copy_from_slice(packet.next_bytes(ROTATION_BYTES)?);
+        let rotation = dequantize_rotation(u64::from_le_bytes(rotation_bytes));
+
+        Ok(CompactTransform { translation, rotation })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use bevy::prelude::{Vec3, Quat};
+    use packets::{Packet, PacketType, PacketSerialize};
+
+    use crate::compact_transform::{CompactTransform, quantized};
+
+    // angle_between loses too much precision near 0 in f32
+    fn rotation_error(a: Quat, b: Quat) -> f32 {
+        (a * Vec3::ONE - b * Vec3::ONE).length()
+    }
+
+    fn round_trip(transform: CompactTransform) -> (CompactTransform, usize) {
+        let mut packet = Packet::new(PacketType::PlacePart);
+        quantized::serialize(&transform, &mut packet);
+
+        // The first byte is the packet type
+        let size = Box::<[u8]>::from(&packet).len() - 1;
+
+        (quantized::deserialize(&mut packet).unwrap(), size)
+    }
+
+    #[test]
+    fn grid_transform_is_quantized() {
+        let rotations = [
+            Quat::IDENTITY,
+            Quat::from_rotation_x(PI / 2.0),
+            Quat::from_rotation_y(-PI / 2.0).mul_quat(Quat::from_rotation_z(PI / 2.0)),
+            Quat::from_rotation_x(PI).mul_quat(Quat::from_rotation_y(PI / 2.0)),
+        ];
+
+        for rotation in rotations {
+            let transform = CompactTransform::new(Vec3::new(0.35, -1.2, 12.0), rotation);
+            let (decoded, size) = round_trip(transform);
+
+            assert_eq!(size, 13);
+            assert!((decoded.translation - transform.translation).abs().max_element() < 1e-5);
+            assert!(rotation_error(decoded.rotation, rotation) < 5e-4);
+        }
+    }
+
+    #[test]
+    fn off_grid_translation_keeps_full_precision() {
+        let transform = CompactTransform::new(Vec3::new(0.123, 4.567, -8.9), Quat::from_rotation_y(0.3));
+        let (decoded, size) = round_trip(transform);
+
+        assert_eq!(size, 19);
+        assert_eq!(decoded.translation, transform.translation);
+        assert!(rotation_error(decoded.rotation, transform.rotation) < 5e-4);
+    }
+
+    #[test]
+    fn quantized_is_smaller_than_full() {
+        let transform = CompactTransform::from_xyz(1.0, 2.0, 3.0);
+
+        let mut packet = Packet::new(PacketType::PlacePart);
+        transform.serialize(&mut packet);
+        let full_size = Box::<[u8]>::from(&packet).len() - 1;
+
+        assert!(round_trip(transform).1 < full_size / 2);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,188 @@
+use bevy::prelude::*;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use packets_derive::{IntoPacket, TryFromPacket, PacketSerialize, PacketDeserialize};
+
+use crate::channels::Channel;
+use crate::network_id::NetworkId;
+use crate::network_message::{AddNetworkMessage, Direction, NetworkMessage};
+use crate::player::PlayerId;
+
+// Ordered so that each role can do everything the roles before it can
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, IntoPrimitive, TryFromPrimitive, PacketSerialize, PacketDeserialize)]
+#[repr(u8)]
+pub enum ConstructRole {
+    #[default]
+    Viewer,
+    Builder,
+    Owner,
+}
+
+// Who is allowed to change a construct, decided by whoever spawns it
+// By default nobody owns a construct and nobody can build on it
+#[derive(Component, Clone, Debug, Default, PartialEq, PacketSerialize, PacketDeserialize)]
+pub struct ConstructAccess {
+    owner: Option<PlayerId>,
+    // The role of players that haven't been given one
+    default_role: ConstructRole,
+    // Roles given out by the owner
+    #[packet(varint)]
+    roles: Vec<(PlayerId, ConstructRole)>,
+}
+
+impl ConstructAccess {
+    pub fn owned_by(owner: PlayerId) -> Self {
+        Self { owner: Some(owner), ..Default::default() }
+    }
+
+    // Every player can build on the construct, but nobody can hand out roles
+    pub fn open() -> Self {
+        Self { default_role: ConstructRole::Builder, ..Default::default() }
+    }
+
+    pub fn owner(&self) -> Option<PlayerId> {
+        self.owner
+    }
+
+    pub fn role(&self, player_id: PlayerId) -> ConstructRole {
+        if self.owner == Some(player_id) {
+            return ConstructRole::Owner;
+        }
+
+        self.roles.iter()
+            .find(|(id, _)| *id == player_id)
+            .map_or(self.default_role, |(_, role)| *role)
+    }
+
+    pub fn can_build(&self, player_id: PlayerId) -> bool {
+        self.role(player_id) >= ConstructRole::Builder
+    }
+
+    // Granting the owner role hands the construct over, the previous owner stays on as a builder
+    pub fn grant(&mut self, player_id: PlayerId, role: ConstructRole) {
+        if self.owner == Some(player_id) {
+            return;
+        }
+
+        self.revoke(player_id);
+
+        if role == ConstructRole::Owner {
+            if let Some(previous_owner) = self.owner.replace(player_id) {
+                self.roles.push((previous_owner, ConstructRole::Builder));
+            }
+        } else {
+            self.roles.push((player_id, role));
+        }
+    }
+
+    // The owner can't be revoked, only replaced by granting someone else the owner role
+    pub fn revoke(&mut self, player_id: PlayerId) {
+        self.roles.retain(|(id, _)| *id != player_id);
+    }
+
+    // Forgets a player that left, if it was the owner the construct is left without one
+    // Returns whether anything changed
+    pub fn remove_player(&mut self, player_id: PlayerId) -> bool {
+        if self.owner == Some(player_id) {
+            self.owner = None;
+            return true;
+        }
+
+        let role_count = self.roles.len();
+        self.revoke(player_id);
+
+        self.roles.len() != role_count
+    }
+}
+
+// Sent by the owner of a construct to give another player a role on it
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
+#[PacketType(GrantConstructRole)]
+pub struct GrantConstructRole {
+    pub construct_network_id: NetworkId,
+    pub player_id: PlayerId,
+    pub role: ConstructRole,
+}
+
+// Sent by the owner of a construct to take a player's role away, leaving it with the construct's default role
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
+#[PacketType(RevokeConstructRole)]
+pub struct RevokeConstructRole {
+    pub construct_network_id: NetworkId,
+    pub player_id: PlayerId,
+}
+
+// Broadcast by the server whenever a construct's access changes
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
+#[PacketType(ConstructAccessChanged)]
+pub struct ConstructAccessChanged {
+    pub construct_network_id: NetworkId,
+    pub access: ConstructAccess,
+}
+
+impl NetworkMessage for GrantConstructRole {}
+impl NetworkMessage for RevokeConstructRole {}
+impl NetworkMessage for ConstructAccessChanged {}
+
+pub struct ConstructAccessPlugin;
+
+impl Plugin for ConstructAccessPlugin {
+    fn build(&self, app: &mut App) {
+        // Sent with the part commands, so that clients see access changes in order with the parts they allow
+        app.add_player_message::<GrantConstructRole>(Channel::PartCommands)
+            .add_player_message::<RevokeConstructRole>(Channel::PartCommands)
+            .add_network_message::<ConstructAccessChanged>(Direction::ServerToClient, Channel::PartCommands);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::player::PlayerId;
+
+    use super::{ConstructAccess, ConstructRole};
+
+    #[test]
+    fn default_role_applies_to_players_without_one() {
+        let access = ConstructAccess::default();
+        assert_eq!(access.owner(), None);
+        assert!(!access.can_build(PlayerId::from(1)));
+
+        let mut access = ConstructAccess::open();
+        assert!(access.can_build(PlayerId::from(1)));
+
+        // Building doesn't make anyone the owner
+        assert_eq!(access.owner(), None);
+
+        access.grant(PlayerId::from(2), ConstructRole::Viewer);
+        assert!(!access.can_build(PlayerId::from(2)));
+    }
+
+    #[test]
+    fn roles_can_be_granted_and_revoked() {
+        let mut access = ConstructAccess::owned_by(PlayerId::from(1));
+
+        access.grant(PlayerId::from(2), ConstructRole::Builder);
+        assert!(access.can_build(PlayerId::from(2)));
+
+        access.revoke(PlayerId::from(2));
+        assert_eq!(access.role(PlayerId::from(2)), ConstructRole::Viewer);
+
+        // Handing over ownership keeps the previous owner as a builder
+        access.grant(PlayerId::from(2), ConstructRole::Owner);
+        assert_eq!(access.owner(), Some(PlayerId::from(2)));
+        assert_eq!(access.role(PlayerId::from(1)), ConstructRole::Builder);
+    }
+
+    #[test]
+    fn owner_leaving_leaves_construct_without_owner() {
+        let mut access = ConstructAccess::owned_by(PlayerId::from(1));
+        access.grant(PlayerId::from(2), ConstructRole::Builder);
+
+        assert!(access.remove_player(PlayerId::from(1)));
+        assert_eq!(access.owner(), None);
+        assert_eq!(access.role(PlayerId::from(1)), ConstructRole::Viewer);
+        assert_eq!(access.role(PlayerId::from(2)), ConstructRole::Builder);
+
+        assert!(access.remove_player(PlayerId::from(2)));
+        assert_eq!(access.role(PlayerId::from(2)), ConstructRole::Viewer);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,37 @@
+use bevy::prelude::*;
+use packets_derive::{IntoPacket, TryFromPacket, PacketSerialize, PacketDeserialize};
+
+use crate::channels::Channel;
+use crate::compact_transform::CompactTransform;
+use crate::network_id::NetworkId;
+use crate::network_message::{AddNetworkMessage, Direction, NetworkMessage};
+
+// Construct states are sent every this many ticks, they change less suddenly than players
+pub const CONSTRUCT_STATE_INTERVAL: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PacketSerialize, PacketDeserialize)]
+pub struct ConstructState {
+    pub network_id: NetworkId,
+    pub transform: CompactTransform,
+    pub linear_velocity: Vec3,
+    pub angular_velocity: Vec3,
+}
+
+// Sent on an unreliable channel like player states, so older snapshots can be lost or arrive late
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
+#[PacketType(ConstructStates)]
+pub struct ConstructStates {
+    pub tick: u32,
+    #[packet(varint)]
+    pub constructs: Vec<ConstructState>,
+}
+
+impl NetworkMessage for ConstructStates {}
+
+pub struct ConstructReplicationPlugin;
+
+impl Plugin for ConstructReplicationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<ConstructStates>(Direction::ServerToClient, Channel::ConstructStates);
+    }
+}
\ No newline at end of file
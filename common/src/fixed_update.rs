@@ -18,8 +18,22 @@ pub enum FixedUpdateSet {
     LastFlush,
 }
 
+// Decodes received packets into events, runs in FixedUpdateSet::PreUpdate
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct NetworkReceiveSet;
+
+// Turns events into packets to send, runs in FixedUpdateSet::PostUpdate
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
 pub struct NetworkSendSet;
 
+// Number of fixed updates run so far, used to stamp snapshots
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FixedTick(pub u32);
+
+fn advance_tick(mut tick: ResMut<FixedTick>) {
+    tick.0 = tick.0.wrapping_add(1);
+}
+
 // A set for `propagate_transforms` to mark it as ambiguous with `sync_simple_transforms`.
 // Used instead of the `SystemTypeSet` as that would not allow multiple instances of the system.
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
@@ -31,6 +45,8 @@ pub trait SetupFixedTimeStepSchedule {
 
 impl SetupFixedTimeStepSchedule for App {
     fn setup_fixed_timestep_schedule(&mut self) -> &mut Self {
+        self.init_resource::<FixedTick>();
+
         self.edit_schedule(FixedUpdate, |schedule| {
             schedule.configure_sets((
                 FixedUpdateSet::PreUpdate,
@@ -46,6 +62,9 @@ impl SetupFixedTimeStepSchedule for App {
                 FixedUpdateSet::LastFlush,
             ).chain());
 
+            schedule.configure_set(NetworkReceiveSet.in_set(FixedUpdateSet::PreUpdate));
+            schedule.configure_set(NetworkSendSet.in_set(FixedUpdateSet::PostUpdate));
+
             schedule.configure_set(TransformSystem::TransformPropagate.in_set(FixedUpdateSet::PostUpdate));
             schedule.configure_set(PropagateTransformsSet.in_set(TransformSystem::TransformPropagate));
 
@@ -54,6 +73,8 @@ impl SetupFixedTimeStepSchedule for App {
             schedule.add_systems(apply_deferred.in_set(FixedUpdateSet::PostUpdateFlush));
             schedule.add_systems(apply_deferred.in_set(FixedUpdateSet::LastFlush));
 
+            schedule.add_systems(advance_tick.in_set(FixedUpdateSet::Last));
+
             schedule.add_systems(sync_simple_transforms.in_set(TransformSystem::TransformPropagate)
                 .ambiguous_with(PropagateTransformsSet)
             );
@@ -1,13 +1,21 @@
 pub mod fixed_update;
+pub mod batching;
 pub mod channels;
+pub mod construct_access;
+pub mod construct_replication;
 pub mod entity_lookup;
 pub mod network_id;
+pub mod network_message;
 pub mod player;
 pub mod player_connection;
+pub mod player_movement;
+pub mod protocol;
 pub mod predefined_parts;
 pub mod part;
 pub mod compact_transform;
 pub mod ship;
+pub mod snapshot;
+pub mod transport;
 pub mod missile;
 
 pub const PHYSICS_TIMESTEP: f32 = 1.0 / 60.0;
\ No newline at end of file
@@ -2,9 +2,10 @@ use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 use packets_derive::{IntoPacket, TryFromPacket};
 
+use crate::channels::Channel;
 use crate::compact_transform::CompactTransform;
-use crate::fixed_update::AddFixedEvent;
 use crate::network_id::NetworkId;
+use crate::network_message::{AddNetworkMessage, Direction, NetworkMessage};
 
 #[derive(Component)]
 pub struct Missile {
@@ -59,13 +60,17 @@ pub struct SpawnMissileCommand {
     pub network_id: NetworkId,
 }
 
-#[derive(IntoPacket, TryFromPacket, Event)]
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
 #[PacketType(ExplodeMissile)]
 pub struct ExplodeMissileCommand {
     pub network_id: NetworkId,
     pub transform: CompactTransform,
 }
 
+impl NetworkMessage for SpawnMissileRequest {}
+impl NetworkMessage for SpawnMissileCommand {}
+impl NetworkMessage for ExplodeMissileCommand {}
+
 pub struct MissilePlugin;
 
 impl Plugin for MissilePlugin {
@@ -73,8 +78,8 @@ impl Plugin for MissilePlugin {
         let mut packet = packets::Packet::new(packets::PacketType::SpawnMissile);
         <u8 as packets::PacketSerialize>::serialize(&10, &mut packet);
 
-        app.add_fixed_event::<SpawnMissileRequest>();
-        app.add_fixed_event::<SpawnMissileCommand>();
-        app.add_fixed_event::<ExplodeMissileCommand>();
+        app.add_player_message::<SpawnMissileRequest>(Channel::Missile)
+            .add_network_message::<SpawnMissileCommand>(Direction::ServerToClient, Channel::Missile)
+            .add_network_message::<ExplodeMissileCommand>(Direction::ServerToClient, Channel::Missile);
     }
 }
\ No newline at end of file
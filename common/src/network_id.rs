@@ -4,6 +4,7 @@ use packets_derive::{PacketSerialize, PacketDeserialize};
 
 #[derive(Clone, Copy, Component, Debug, PartialEq, Eq, Hash, PacketSerialize, PacketDeserialize)]
 pub struct NetworkId {
+    #[packet(varint)]
     id: u32
 }
 
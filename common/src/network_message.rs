@@ -0,0 +1,357 @@
+use bevy::prelude::*;
+use packets::{Packet, PacketError, PacketType, TypedPacket};
+
+use crate::channels::Channel;
+use crate::fixed_update::{AddFixedEvent, FixedUpdateSet, NetworkReceiveSet, NetworkSendSet};
+use crate::player::PlayerId;
+
+// Which end of the connection an app is, inserted by the server and client app setup
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetworkSide {
+    Server,
+    Client,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    ClientToServer,
+    ServerToClient,
+}
+
+impl Direction {
+    fn sender(&self) -> NetworkSide {
+        match self {
+            Direction::ClientToServer => NetworkSide::Client,
+            Direction::ServerToClient => NetworkSide::Server,
+        }
+    }
+
+    fn receiver(&self) -> NetworkSide {
+        match self {
+            Direction::ClientToServer => NetworkSide::Server,
+            Direction::ServerToClient => NetworkSide::Client,
+        }
+    }
+}
+
+// The players a message sent by the server is delivered to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Recipients {
+    All,
+    Only(PlayerId),
+    AllExcept(PlayerId),
+}
+
+impl Recipients {
+    pub fn includes(&self, player_id: PlayerId) -> bool {
+        match *self {
+            Recipients::All => true,
+            Recipients::Only(recipient) => player_id == recipient,
+            Recipients::AllExcept(excluded) => player_id != excluded,
+        }
+    }
+}
+
+pub trait NetworkMessage: Event + TypedPacket {
+    // Only used for messages sent by the server, clients always send to the server
+    fn recipients(&self) -> Recipients {
+        Recipients::All
+    }
+}
+
+// A message received by the server, along with the player that sent it
+#[derive(Debug)]
+pub struct FromPlayer<T> {
+    pub sender: PlayerId,
+    pub message: T,
+}
+
+impl<T: Send + Sync + 'static> Event for FromPlayer<T> {}
+
+pub struct OutgoingPacket {
+    pub packet: Packet,
+    pub channel: Channel,
+    pub recipients: Recipients,
+}
+
+// Packets produced from message events, waiting to be sent by the server or client
+#[derive(Resource, Default)]
+pub struct OutgoingPackets {
+    packets: Vec<OutgoingPacket>,
+}
+
+impl OutgoingPackets {
+    pub fn push(&mut self, packet: OutgoingPacket) {
+        self.packets.push(packet);
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = OutgoingPacket> + '_ {
+        self.packets.drain(..)
+    }
+}
+
+// Packets received this tick, waiting to be decoded into message events
+// The sender is None for packets received from the server
+#[derive(Resource, Default)]
+pub struct IncomingPackets {
+    packets: Vec<(Option<PlayerId>, Packet)>,
+}
+
+impl IncomingPackets {
+    pub fn push(&mut self, sender: Option<PlayerId>, packet: Packet) {
+        self.packets.push((sender, packet));
+    }
+
+    // Takes the packets of one type along with their senders, leaving the rest
+    fn take(&mut self, packet_type: PacketType) -> Vec<(Option<PlayerId>, Packet)> {
+        let (taken, remaining) = std::mem::take(&mut self.packets)
+            .into_iter()
+            .partition(|(_, packet)| packet.packet_type() == packet_type);
+
+        self.packets = remaining;
+
+        taken
+    }
+}
+
+fn receive_messages<T>(
+    mut incoming_packets: ResMut<IncomingPackets>,
+    mut message_writer: EventWriter<T>,
+)
+where
+    T: NetworkMessage + TryFrom<Packet, Error = PacketError>
+{
+    for (sender, packet) in incoming_packets.take(T::PACKET_TYPE) {
+        match T::try_from(packet) {
+            Ok(message) => {
+                message_writer.send(message);
+            },
+            // Malformed or oversized packets are dropped rather than trusted
+            Err(err) => match sender {
+                Some(player_id) => warn!("Dropped {:?} packet from player {:?}: {}", T::PACKET_TYPE, player_id, err),
+                None => warn!("Dropped {:?} packet from server: {}", T::PACKET_TYPE, err),
+            }
+        }
+    }
+}
+
+fn receive_player_messages<T>(
+    mut incoming_packets: ResMut<IncomingPackets>,
+    mut message_writer: EventWriter<FromPlayer<T>>,
+)
+where
+    T: NetworkMessage + TryFrom<Packet, Error = PacketError>
+{
+    for (sender, packet) in incoming_packets.take(T::PACKET_TYPE) {
+        let Some(sender) = sender else {
+            warn!("Dropped {:?} packet without a sender", T::PACKET_TYPE);
+            continue;
+        };
+
+        match T::try_from(packet) {
+            Ok(message) => {
+                message_writer.send(FromPlayer { sender, message });
+            },
+            Err(err) => warn!("Dropped {:?} packet from player {:?}: {}", T::PACKET_TYPE, sender, err),
+        }
+    }
+}
+
+fn queue_messages<T>(
+    channel: Channel,
+) -> impl FnMut(EventReader<T>, ResMut<OutgoingPackets>)
+where
+    T: NetworkMessage,
+    for<'a> &'a T: Into<Packet>
+{
+    move |mut message_reader: EventReader<T>, mut outgoing_packets: ResMut<OutgoingPackets>| {
+        for message in message_reader.iter() {
+            outgoing_packets.push(OutgoingPacket {
+                packet: message.into(),
+                channel,
+                recipients: message.recipients(),
+            });
+        }
+    }
+}
+
+fn discard_unhandled_packets(mut incoming_packets: ResMut<IncomingPackets>) {
+    for (_, packet) in incoming_packets.packets.drain(..) {
+        warn!("Received unexpected {:?} packet", packet.packet_type());
+    }
+}
+
+fn is_side(side: NetworkSide) -> impl Fn(Option<Res<NetworkSide>>) -> bool {
+    move |network_side: Option<Res<NetworkSide>>| network_side.is_some_and(|network_side| *network_side == side)
+}
+
+fn init_network_resources(app: &mut App) {
+    if !app.world.contains_resource::<IncomingPackets>() {
+        app.init_resource::<IncomingPackets>()
+            .init_resource::<OutgoingPackets>()
+            .add_systems(FixedUpdate, discard_unhandled_packets
+                .in_set(FixedUpdateSet::PreUpdate)
+                .after(NetworkReceiveSet)
+            );
+    }
+}
+
+pub trait AddNetworkMessage {
+    fn add_network_message<T>(&mut self, direction: Direction, channel: Channel) -> &mut Self
+    where
+        T: NetworkMessage + TryFrom<Packet, Error = PacketError>,
+        for<'a> &'a T: Into<Packet>;
+
+    fn add_player_message<T>(&mut self, channel: Channel) -> &mut Self
+    where
+        T: NetworkMessage + TryFrom<Packet, Error = PacketError>,
+        for<'a> &'a T: Into<Packet>;
+}
+
+impl AddNetworkMessage for App {
+    // Registers T as a fixed event that the sending side turns into packets and the receiving side decodes from packets
+    fn add_network_message<T>(&mut self, direction: Direction, channel: Channel) -> &mut Self
+    where
+        T: NetworkMessage + TryFrom<Packet, Error = PacketError>,
+        for<'a> &'a T: Into<Packet>
+    {
+        init_network_resources(self);
+
+        self.add_fixed_event::<T>()
+            .add_systems(FixedUpdate, (
+                receive_messages::<T>
+                    .run_if(is_side(direction.receiver()))
+                    .in_set(NetworkReceiveSet),
+                queue_messages::<T>(channel)
+                    .run_if(is_side(direction.sender()))
+                    .in_set(NetworkSendSet),
+            ))
+    }
+
+    // Like a client to server network message, but the server receives it as FromPlayer<T> so it knows who sent it
+    fn add_player_message<T>(&mut self, channel: Channel) -> &mut Self
+    where
+        T: NetworkMessage + TryFrom<Packet, Error = PacketError>,
+        for<'a> &'a T: Into<Packet>
+    {
+        init_network_resources(self);
+
+        self.add_fixed_event::<T>()
+            .add_fixed_event::<FromPlayer<T>>()
+            .add_systems(FixedUpdate, (
+                receive_player_messages::<T>
+                    .run_if(is_side(NetworkSide::Server))
+                    .in_set(NetworkReceiveSet),
+                queue_messages::<T>(channel)
+                    .run_if(is_side(NetworkSide::Client))
+                    .in_set(NetworkSendSet),
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use packets::{Packet, PacketSerialize, PacketType};
+
+    use crate::channels::Channel;
+    use crate::compact_transform::CompactTransform;
+    use crate::fixed_update::SetupFixedTimeStepSchedule;
+    use crate::network_id::NetworkId;
+    use crate::network_message::{AddNetworkMessage, Direction, FromPlayer, IncomingPackets, NetworkSide, OutgoingPackets, Recipients};
+    use crate::part::events::{DeletePartCommand, PlacePartRequest};
+    use crate::player::PlayerId;
+    use crate::player_connection::PlayerConnected;
+
+    fn network_test_app(side: NetworkSide) -> App {
+        let mut app = App::new();
+
+        app.setup_fixed_timestep_schedule()
+            .insert_resource(side)
+            .add_network_message::<DeletePartCommand>(Direction::ServerToClient, Channel::PartCommands)
+            .add_network_message::<PlayerConnected>(Direction::ServerToClient, Channel::PlayerConnectionEvents)
+            .add_player_message::<PlacePartRequest>(Channel::PartCommands);
+
+        app
+    }
+
+    #[test]
+    fn received_packets_become_events() {
+        let mut app = network_test_app(NetworkSide::Client);
+
+        let packet = Packet::from(&DeletePartCommand(NetworkId::from(3)));
+        app.world.resource_mut::<IncomingPackets>().push(None, packet);
+        app.world.run_schedule(FixedUpdate);
+
+        let events = app.world.resource::<Events<DeletePartCommand>>();
+        let received: Vec<_> = events.get_reader().iter(events).map(|command| command.0).collect();
+        assert_eq!(received, vec![NetworkId::from(3)]);
+        assert!(app.world.resource::<IncomingPackets>().packets.is_empty());
+    }
+
+    #[test]
+    fn oversized_packets_are_dropped() {
+        let mut app = network_test_app(NetworkSide::Client);
+
+        // A name longer than PlayerName allows
+        let mut packet = Packet::new(PacketType::PlayerConnected);
+        PlayerId::from(1).serialize(&mut packet);
+        packets::varint::serialize(&"a".repeat(65), &mut packet);
+        Transform::IDENTITY.serialize(&mut packet);
+
+        app.world.resource_mut::<IncomingPackets>().push(Some(1.into()), packet);
+        app.world.run_schedule(FixedUpdate);
+
+        let events = app.world.resource::<Events<PlayerConnected>>();
+        assert_eq!(events.get_reader().iter(events).count(), 0);
+        assert!(app.world.resource::<IncomingPackets>().packets.is_empty());
+    }
+
+    #[test]
+    fn events_become_outgoing_packets() {
+        let mut app = network_test_app(NetworkSide::Server);
+
+        app.world.send_event(PlayerConnected {
+            id: 1.into(),
+            name: "Player".to_string().into(),
+            transform: Transform::IDENTITY,
+        });
+        app.world.run_schedule(FixedUpdate);
+
+        let outgoing_packets: Vec<_> = app.world.resource_mut::<OutgoingPackets>().drain().collect();
+        assert_eq!(outgoing_packets.len(), 1);
+        assert_eq!(outgoing_packets[0].channel, Channel::PlayerConnectionEvents);
+        assert_eq!(outgoing_packets[0].recipients, Recipients::AllExcept(1.into()));
+    }
+
+    #[test]
+    fn player_messages_keep_their_sender() {
+        let mut app = network_test_app(NetworkSide::Server);
+
+        let request = PlacePartRequest {
+            request_seq: 7,
+            part_id: 0.into(),
+            part_transform: CompactTransform::from(Transform::IDENTITY),
+            construct_network_id: NetworkId::from(1),
+        };
+        app.world.resource_mut::<IncomingPackets>().push(Some(2.into()), Packet::from(&request));
+        app.world.resource_mut::<IncomingPackets>().push(None, Packet::from(&request));
+        app.world.run_schedule(FixedUpdate);
+
+        let events = app.world.resource::<Events<FromPlayer<PlacePartRequest>>>();
+        let received: Vec<_> = events.get_reader().iter(events)
+            .map(|request| (request.sender, request.message.request_seq))
+            .collect();
+        assert_eq!(received, vec![(PlayerId::from(2), 7)]);
+    }
+
+    #[test]
+    fn messages_are_not_sent_by_their_receiver() {
+        let mut app = network_test_app(NetworkSide::Client);
+
+        app.world.send_event(DeletePartCommand(NetworkId::from(3)));
+        app.world.run_schedule(FixedUpdate);
+
+        assert_eq!(app.world.resource_mut::<OutgoingPackets>().drain().count(), 0);
+    }
+}
\ No newline at end of file
@@ -1,38 +1,127 @@
 use bevy::prelude::*;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 use crate::network_id::NetworkId;
-use packets_derive::{IntoPacket, TryFromPacket};
-use crate::part::{Material, PartId};
+use crate::network_message::{NetworkMessage, Recipients};
+use crate::player::PlayerId;
+use packets_derive::{IntoPacket, TryFromPacket, PacketSerialize, PacketDeserialize};
+use crate::part::{PartId, VoxelPos};
+use crate::part::voxel_changes::VoxelChanges;
 use crate::compact_transform::CompactTransform;
 
 #[derive(Clone, Debug, IntoPacket, TryFromPacket, Event)]
 #[PacketType(PlacePart)]
 pub struct PlacePartRequest {
+    // Chosen by the client, which shows the part straight away and matches it up with the server's answer
+    pub request_seq: u32,
     pub part_id: PartId,
+    #[packet(with = crate::compact_transform::quantized)]
     pub part_transform: CompactTransform,
     pub construct_network_id: NetworkId
 }
 
-#[derive(IntoPacket, TryFromPacket, Event)]
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
 #[PacketType(PlacePart)]
 pub struct PlacePartCommand {
     pub part_id: PartId,
+    #[packet(with = crate::compact_transform::quantized)]
     pub transform: CompactTransform,
     pub part_network_id: NetworkId,
-    pub construct_network_id: NetworkId
+    pub construct_network_id: NetworkId,
+    // Lets the requesting player confirm its provisional part instead of spawning another one
+    pub requester: PlayerId,
+    pub request_seq: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive, PacketSerialize, PacketDeserialize)]
+#[repr(u8)]
+pub enum BuildRejectionReason {
+    // The part would be inside of another part or a player
+    Overlap,
+    UnknownPart,
+    UnknownConstruct,
+    NoPermission,
+    // The part is further than MAX_BUILD_DISTANCE from the requesting player
+    OutOfRange,
+    // The voxel isn't inside of the part
+    UnknownVoxel,
+}
+
+// Sent to the requesting player instead of a PlacePartCommand or DeletePartCommand, so it can roll back what it showed
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
+#[PacketType(BuildRequestRejected)]
+pub struct BuildRequestRejected {
+    pub requester: PlayerId,
+    pub request_seq: u32,
+    pub reason: BuildRejectionReason,
 }
 
-#[derive(IntoPacket, TryFromPacket, Event)]
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
 #[PacketType(DeletePart)]
-pub struct DeletePartRequest(pub NetworkId);
+pub struct DeletePartRequest {
+    // From the same sequence as placement requests, so a rejection can be matched up with either
+    pub request_seq: u32,
+    pub part_network_id: NetworkId,
+}
 
-#[derive(IntoPacket, TryFromPacket, Event)]
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
 #[PacketType(DeletePart)]
 pub struct DeletePartCommand(pub NetworkId);
 
-#[derive(IntoPacket, TryFromPacket, Event)]
+// Empties one voxel of a part, which the server answers with a VoxelUpdate like any other change to its voxels
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
+#[PacketType(DeleteVoxel)]
+pub struct DeleteVoxelRequest {
+    // From the same sequence as the other build requests
+    pub request_seq: u32,
+    pub part_network_id: NetworkId,
+    pub voxel_pos: VoxelPos,
+}
+
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
 #[PacketType(VoxelUpdate)]
 pub struct VoxelUpdate {
     pub network_id: NetworkId,
-    pub voxels: Vec<Material>
+    // The part's revision after the changes, one more than the update before
+    #[packet(varint)]
+    pub revision: u32,
+    pub changes: VoxelChanges,
+}
+
+// Sent by a client whose copy of the part missed a VoxelUpdate, so deltas can't be applied to it anymore
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
+#[PacketType(FullVoxels)]
+pub struct FullVoxelsRequest(pub NetworkId);
+
+// Every voxel of the part, sent only to the client that asked for them
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
+#[PacketType(FullVoxels)]
+pub struct FullVoxelsCommand {
+    pub requester: PlayerId,
+    pub network_id: NetworkId,
+    // The part's current revision, which the following VoxelUpdates build on
+    #[packet(varint)]
+    pub revision: u32,
+    // Always VoxelChanges::Full
+    pub changes: VoxelChanges,
+}
+
+impl NetworkMessage for PlacePartRequest {}
+impl NetworkMessage for PlacePartCommand {}
+impl NetworkMessage for DeletePartRequest {}
+impl NetworkMessage for DeletePartCommand {}
+impl NetworkMessage for DeleteVoxelRequest {}
+impl NetworkMessage for VoxelUpdate {}
+impl NetworkMessage for FullVoxelsRequest {}
+
+impl NetworkMessage for FullVoxelsCommand {
+    fn recipients(&self) -> Recipients {
+        Recipients::Only(self.requester)
+    }
+}
+
+impl NetworkMessage for BuildRequestRejected {
+    fn recipients(&self) -> Recipients {
+        Recipients::Only(self.requester)
+    }
 }
\ No newline at end of file
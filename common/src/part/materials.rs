@@ -2,32 +2,15 @@ use bevy::utils::HashMap;
 use bevy::prelude::*;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
-use packets::{Packet, PacketSerialize, PacketDeserialize, PacketError};
+use packets_derive::{PacketSerialize, PacketDeserialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, IntoPrimitive, TryFromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, IntoPrimitive, TryFromPrimitive, PacketSerialize, PacketDeserialize)]
 #[repr(u8)]
 pub enum Material {
     Empty,
     Aluminum
 }
 
-impl PacketSerialize for Material {
-    fn serialize(&self, packet: &mut Packet) {
-        u8::from(*self).serialize(packet);
-    }
-}
-
-impl PacketDeserialize for Material {
-    fn deserialize(packet: &mut Packet) -> Result<Self, PacketError> {
-        let material_byte = u8::deserialize(packet)?;
-
-        match Material::try_from(material_byte) {
-            Ok(material) => Ok(material),
-            Err(_) => Err(PacketError::InvalidPacketError(packet.clone()))
-        }
-    }
-}
-
 #[derive(Resource)]
 pub struct MaterialResistances(HashMap<Material, f32>);
 
@@ -47,7 +30,7 @@ impl MaterialResistances {
 
 #[cfg(test)]
 mod tests {
-    use packets::{Packet, PacketSerialize, PacketDeserialize, PacketType};
+    use packets::{Packet, PacketSerialize, PacketDeserialize, PacketError, PacketType};
 
     use crate::part::materials::Material;
 
@@ -62,4 +45,13 @@ mod tests {
         
         assert_eq!(x, y);
     }
+
+    #[test]
+    fn material_unknown_tag() {
+        let mut packet = Packet::new(PacketType::VoxelUpdate);
+
+        200u8.serialize(&mut packet);
+
+        assert!(matches!(Material::deserialize(&mut packet), Err(PacketError::InvalidPacketError(_))));
+    }
 }
\ No newline at end of file
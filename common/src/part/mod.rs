@@ -7,26 +7,33 @@ use bevy::reflect::Reflect;
 use bevy::utils::{hashbrown::hash_map, HashMap};
 use crossbeam_channel::{Sender, Receiver};
 
-use packets::{Packet, PacketSerialize, PacketDeserialize, PacketError};
-
 use events::*;
 use colliders::{RegenerateColliders, remove_unused_colliders};
 use materials::Material;
+use voxel_changes::VoxelChanges;
 use packets_derive::{PacketSerialize, PacketDeserialize};
 
+use crate::channels::Channel;
 use crate::fixed_update::{AddFixedEvent, FixedUpdateSet};
+use crate::network_message::{AddNetworkMessage, Direction};
 
 use self::colliders::PartCollider;
 
 pub mod colliders;
 pub mod events;
 pub mod materials;
+pub mod voxel_changes;
 
 // Voxels are 10^3 cm^3
 pub const VOXEL_SIZE: f32 = 0.1;
 
+// How far from their player someone can place parts, in meters
+// About as far as single voxels can still be told apart on screen, so players move to what they build
+pub const MAX_BUILD_DISTANCE: f32 = 15.0;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect, PacketSerialize, PacketDeserialize)]
 pub struct PartId {
+    #[packet(varint)]
     id: u32
 }
 
@@ -42,7 +49,7 @@ impl From<u32> for PartId {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PacketSerialize, PacketDeserialize)]
 pub struct VoxelPos {
     pub x: u8,
     pub y: u8,
@@ -72,13 +79,17 @@ pub struct Part {
     width: u8,
     height: u8,
     depth: u8,
+    #[packet(varint)]
     voxels: Vec<Material>,
-    parent_part_id: Option<PartId>
+    parent_part_id: Option<PartId>,
+    // Counts the voxel updates sent for the part, so clients can tell when they've missed one
+    #[packet(varint)]
+    revision: u32,
 }
 
 impl Part {
     pub fn new (width: u8, height: u8, depth: u8, voxels: Vec<Material>, parent_part_id: Option<PartId>) -> Self {
-        Self { width, height, depth, voxels, parent_part_id }
+        Self { width, height, depth, voxels, parent_part_id, revision: 0 }
     }
 
     pub fn voxel_to_index(&self, x: u8, y: u8, z: u8) -> usize {
@@ -108,6 +119,10 @@ impl Part {
         self.voxels[i]
     }
 
+    pub fn contains(&self, pos: VoxelPos) -> bool {
+        pos.x < self.width && pos.y < self.height && pos.z < self.depth
+    }
+
     pub fn set(&mut self, pos: VoxelPos, material: Material) {
         let i = self.voxel_to_index(pos.x, pos.y, pos.z);
         self.voxels[i] = material;
@@ -142,13 +157,32 @@ impl Part {
         self.parent_part_id
     }
 
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+
+    // For the server, when it sends the part's changed voxels
+    pub fn next_revision(&mut self) -> u32 {
+        self.revision = self.revision.wrapping_add(1);
+        self.revision
+    }
+
+    // Brings the part up to the revision of the VoxelUpdate the changes came in
+    pub fn apply_changes(&mut self, changes: &VoxelChanges, revision: u32) -> Result<(), String> {
+        changes.apply(&mut self.voxels)?;
+        self.revision = revision;
+
+        Ok(())
+    }
+
     pub fn clone_as_child_of(&self, parent_part_id: PartId) -> Self {
         Self {
             width: self.width,
             height: self.height,
             depth: self.depth,
             voxels: self.voxels.clone(),
-            parent_part_id: Some(parent_part_id)
+            parent_part_id: Some(parent_part_id),
+            revision: self.revision,
         }
     }
 
@@ -187,41 +221,12 @@ impl Drop for PartHandle {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, PacketSerialize, PacketDeserialize)]
 pub enum PartNetworkRepr {
     Predefined(PartId),
     Child(Part)
 }
 
-impl PacketSerialize for PartNetworkRepr {
-    fn serialize(&self, packet: &mut Packet) {
-        match self {
-            Self::Predefined(part_id) => {
-                false.serialize(packet);
-                part_id.serialize(packet);
-            },
-            Self::Child(part) => {
-                true.serialize(packet);
-                part.serialize(packet);
-            }
-        }
-    }
-}
-
-impl PacketDeserialize for PartNetworkRepr {
-    fn deserialize(packet: &mut Packet) -> Result<Self, PacketError> {
-        let is_child = bool::deserialize(packet)?;
-
-        if is_child {
-            let part = Part::deserialize(packet)?;
-            Ok(Self::Child(part))
-        } else {
-            let part_id = PartId::deserialize(packet)?;
-            Ok(Self::Predefined(part_id))
-        }
-    }
-}
-
 #[derive(Event)]
 pub struct FreedParts(pub Box<[PartId]>);
 
@@ -344,7 +349,10 @@ pub struct DeletePart(pub Entity);
 
 impl Command for DeletePart {
     fn apply(self, world: &mut World) {
-        let construct = world.get::<Parent>(self.0).unwrap().get();
+        // Several deletions of the same part can be queued in one tick, only the first does anything
+        let Some(construct) = world.get::<Parent>(self.0).map(|parent| parent.get()) else {
+            return;
+        };
     
         // Remove colliders
         let children = world.get::<Children>(construct).unwrap().to_vec();
@@ -368,11 +376,15 @@ impl Plugin for PartPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Parts::new())
             .insert_resource(materials::MaterialResistances::new())
-            .add_fixed_event::<PlacePartRequest>()
-            .add_fixed_event::<PlacePartCommand>()
-            .add_fixed_event::<DeletePartRequest>()
-            .add_fixed_event::<DeletePartCommand>()
-            .add_fixed_event::<VoxelUpdate>()
+            .add_player_message::<PlacePartRequest>(Channel::PartCommands)
+            .add_network_message::<PlacePartCommand>(Direction::ServerToClient, Channel::PartCommands)
+            .add_network_message::<BuildRequestRejected>(Direction::ServerToClient, Channel::PartCommands)
+            .add_player_message::<DeletePartRequest>(Channel::PartCommands)
+            .add_network_message::<DeletePartCommand>(Direction::ServerToClient, Channel::PartCommands)
+            .add_player_message::<DeleteVoxelRequest>(Channel::PartCommands)
+            .add_network_message::<VoxelUpdate>(Direction::ServerToClient, Channel::PartCommands)
+            .add_player_message::<FullVoxelsRequest>(Channel::PartCommands)
+            .add_network_message::<FullVoxelsCommand>(Direction::ServerToClient, Channel::PartCommands)
             .add_fixed_event::<FreedParts>()
             .add_fixed_event::<RegenerateColliders>()
             .add_systems(FixedUpdate, (
@@ -386,7 +398,8 @@ impl Plugin for PartPlugin {
 mod tests {
     use packets::{Packet, PacketSerialize, PacketDeserialize, PacketType};
 
-    use crate::part::PartNetworkRepr;
+    use crate::part::{Part, PartNetworkRepr};
+    use crate::part::materials::Material;
 
     #[test]
     fn part_network_repr_serialize_deserialize() {
@@ -399,4 +412,16 @@ mod tests {
         
         assert_eq!(x, y);
     }
+
+    #[test]
+    fn part_network_repr_child_serialize_deserialize() {
+        let mut packet = Packet::new(PacketType::VoxelUpdate);
+
+        let x = PartNetworkRepr::Child(Part::new(1, 2, 1, vec![Material::Aluminum, Material::Empty], Some(0.into())));
+        x.serialize(&mut packet);
+
+        let y = PartNetworkRepr::deserialize(&mut packet).unwrap();
+        
+        assert_eq!(x, y);
+    }
 }
\ No newline at end of file
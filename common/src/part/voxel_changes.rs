@@ -0,0 +1,215 @@
+use packets_derive::{PacketSerialize, PacketDeserialize};
+
+use crate::part::materials::Material;
+
+// Consecutive voxels with the same material
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PacketSerialize, PacketDeserialize)]
+pub struct VoxelRun {
+    #[packet(varint)]
+    pub length: u32,
+    pub material: Material,
+}
+
+// A run of changed voxels, starting this many voxels after the end of the previous run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PacketSerialize, PacketDeserialize)]
+pub struct ChangedRun {
+    #[packet(varint)]
+    pub skip: u32,
+    pub run: VoxelRun,
+}
+
+// How a part's voxels changed, in whichever form is smaller on the wire
+#[derive(Debug, Clone, PartialEq, Eq, PacketSerialize, PacketDeserialize)]
+pub enum VoxelChanges {
+    // Only the voxels that changed
+    Delta(#[packet(varint)] Vec<ChangedRun>),
+    // Every voxel of the part, run-length encoded
+    Full(#[packet(varint)] Vec<VoxelRun>),
+}
+
+fn varint_len(value: u32) -> usize {
+    (u32::BITS - value.leading_zeros()).max(1).div_ceil(7) as usize
+}
+
+fn runs(voxels: &[Material]) -> Vec<VoxelRun> {
+    let mut runs: Vec<VoxelRun> = Vec::new();
+
+    for &material in voxels {
+        match runs.last_mut() {
+            Some(run) if run.material == material => run.length += 1,
+            _ => runs.push(VoxelRun { length: 1, material }),
+        }
+    }
+
+    runs
+}
+
+fn changed_runs(old: &[Material], new: &[Material]) -> Vec<ChangedRun> {
+    let mut changed_runs: Vec<ChangedRun> = Vec::new();
+    let mut unchanged = 0;
+
+    for (&old_material, &material) in old.iter().zip(new) {
+        if old_material == material {
+            unchanged += 1;
+            continue;
+        }
+
+        match changed_runs.last_mut() {
+            Some(changed) if unchanged == 0 && changed.run.material == material => changed.run.length += 1,
+            _ => changed_runs.push(ChangedRun { skip: unchanged, run: VoxelRun { length: 1, material } }),
+        }
+
+        unchanged = 0;
+    }
+
+    changed_runs
+}
+
+impl VoxelChanges {
+    // The changes that turn old into new
+    pub fn between(old: &[Material], new: &[Material]) -> Self {
+        let full = runs(new);
+
+        if old.len() != new.len() {
+            return Self::Full(full);
+        }
+
+        let delta = changed_runs(old, new);
+
+        // Both forms start with the same tag and count, so only the runs are compared
+        let delta_len: usize = delta.iter().map(|changed| varint_len(changed.skip) + varint_len(changed.run.length) + 1).sum();
+        let full_len: usize = full.iter().map(|run| varint_len(run.length) + 1).sum();
+
+        if delta_len + varint_len(delta.len() as u32) <= full_len + varint_len(full.len() as u32) {
+            Self::Delta(delta)
+        } else {
+            Self::Full(full)
+        }
+    }
+
+    // Every voxel, for a part whose previous revision isn't known
+    pub fn full(voxels: &[Material]) -> Self {
+        Self::Full(runs(voxels))
+    }
+
+    // Whether the changes only make sense on top of the previous revision of the part
+    pub fn is_delta(&self) -> bool {
+        matches!(self, Self::Delta(_))
+    }
+
+    // Leaves the voxels untouched if the changes don't fit them
+    pub fn apply(&self, voxels: &mut [Material]) -> Result<(), String> {
+        match self {
+            Self::Delta(changed_runs) => {
+                let mut ranges = Vec::with_capacity(changed_runs.len());
+                let mut end = 0usize;
+
+                for changed in changed_runs {
+                    let start = end.checked_add(changed.skip as usize);
+                    end = match start.and_then(|start| start.checked_add(changed.run.length as usize)) {
+                        Some(run_end) if run_end <= voxels.len() => run_end,
+                        _ => return Err(format!("changed voxels go past the end of the part's {} voxels", voxels.len())),
+                    };
+
+                    ranges.push((end - changed.run.length as usize..end, changed.run.material));
+                }
+
+                for (range, material) in ranges {
+                    voxels[range].fill(material);
+                }
+            },
+            Self::Full(runs) => {
+                let length = runs.iter().try_fold(0usize, |length, run| length.checked_add(run.length as usize));
+                if length != Some(voxels.len()) {
+                    return Err(format!("expected {} voxels, but the runs don't add up to that", voxels.len()));
+                }
+
+                let mut start = 0;
+                for run in runs {
+                    let end = start + run.length as usize;
+                    voxels[start..end].fill(run.material);
+                    start = end;
+                }
+            },
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use packets::{Packet, PacketSerialize, PacketDeserialize, PacketType};
+
+    use crate::part::materials::Material::{self, Aluminum, Empty};
+    use super::{ChangedRun, VoxelChanges, VoxelRun};
+
+    fn encoded_len(changes: &VoxelChanges) -> usize {
+        let mut packet = Packet::new(PacketType::VoxelUpdate);
+        changes.serialize(&mut packet);
+        Box::<[u8]>::from(&packet).len()
+    }
+
+    #[test]
+    fn few_changes_are_sent_as_a_delta() {
+        let old = vec![Aluminum; 1000];
+        let mut new = old.clone();
+        new[10] = Empty;
+        new[11] = Empty;
+        new[500] = Empty;
+
+        let changes = VoxelChanges::between(&old, &new);
+
+        assert_eq!(changes, VoxelChanges::Delta(vec![
+            ChangedRun { skip: 10, run: VoxelRun { length: 2, material: Empty } },
+            ChangedRun { skip: 488, run: VoxelRun { length: 1, material: Empty } },
+        ]));
+        assert!(encoded_len(&changes) < 16);
+
+        let mut voxels = old.clone();
+        changes.apply(&mut voxels).unwrap();
+        assert_eq!(voxels, new);
+    }
+
+    #[test]
+    fn scattered_changes_fall_back_to_the_full_state() {
+        let old: Vec<Material> = (0..100).map(|i| if i % 2 == 0 { Empty } else { Aluminum }).collect();
+        let new = vec![Empty; 100];
+
+        let changes = VoxelChanges::between(&old, &new);
+
+        assert!(!changes.is_delta());
+
+        let mut voxels = old.clone();
+        changes.apply(&mut voxels).unwrap();
+        assert_eq!(voxels, new);
+    }
+
+    #[test]
+    fn changes_that_dont_fit_are_rejected() {
+        let mut voxels = vec![Aluminum; 10];
+
+        let delta = VoxelChanges::Delta(vec![
+            ChangedRun { skip: 0, run: VoxelRun { length: 1, material: Empty } },
+            ChangedRun { skip: 8, run: VoxelRun { length: 2, material: Empty } },
+        ]);
+        assert!(delta.apply(&mut voxels).is_err());
+
+        let full = VoxelChanges::Full(vec![VoxelRun { length: 9, material: Empty }]);
+        assert!(full.apply(&mut voxels).is_err());
+
+        assert_eq!(voxels, vec![Aluminum; 10]);
+    }
+
+    #[test]
+    fn voxel_changes_serialize_deserialize() {
+        let mut packet = Packet::new(PacketType::VoxelUpdate);
+
+        let x = VoxelChanges::Delta(vec![ChangedRun { skip: 300, run: VoxelRun { length: 2, material: Empty } }]);
+        x.serialize(&mut packet);
+
+        let y = VoxelChanges::deserialize(&mut packet).unwrap();
+
+        assert_eq!(x, y);
+    }
+}
\ No newline at end of file
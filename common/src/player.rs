@@ -17,6 +17,8 @@ impl From<u8> for PlayerId {
 
 #[derive(Clone, Debug, Component, PacketSerialize, PacketDeserialize, Reflect)]
 pub struct PlayerName {
+    // Same as MAX_LEN, since longer names are rejected anyway
+    #[packet(varint, max_len = 24)]
     name: String
 }
 
@@ -26,6 +28,41 @@ impl From<String> for PlayerName {
     }
 }
 
+impl PlayerName {
+    // In characters, which are all ASCII so this is also the length in bytes
+    pub const MAX_LEN: usize = 24;
+
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+
+    // Checks that the name can be shown to other players
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("the name is empty".to_string());
+        }
+
+        if self.name.len() > Self::MAX_LEN {
+            return Err(format!("the name is longer than {} characters", Self::MAX_LEN));
+        }
+
+        if let Some(c) = self.name.chars().find(|&c| !(c.is_ascii_alphanumeric() || c == ' ' || c == '_' || c == '-')) {
+            return Err(format!("the name contains {:?}, only letters, digits, spaces, '_' and '-' are allowed", c));
+        }
+
+        if self.name.trim() != self.name {
+            return Err("the name starts or ends with a space".to_string());
+        }
+
+        Ok(())
+    }
+
+    // Names that only differ in case are too easily confused
+    pub fn is_taken_by(&self, other: &PlayerName) -> bool {
+        self.name.eq_ignore_ascii_case(&other.name)
+    }
+}
+
 impl std::fmt::Display for PlayerName {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.name.fmt(f)
@@ -59,4 +96,38 @@ impl Default for PlayerBundle {
             external_impulse: ExternalImpulse::default(),
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlayerName;
+
+    fn validate(name: &str) -> Result<(), String> {
+        PlayerName::from(name.to_string()).validate()
+    }
+
+    #[test]
+    fn valid_names_are_accepted() {
+        assert!(validate("Player").is_ok());
+        assert!(validate("space_cadet-2 b").is_ok());
+        assert!(validate(&"a".repeat(PlayerName::MAX_LEN)).is_ok());
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        assert!(validate("").is_err());
+        assert!(validate("   ").is_err());
+        assert!(validate(" Player").is_err());
+        assert!(validate(&"a".repeat(PlayerName::MAX_LEN + 1)).is_err());
+        assert!(validate("Pl\u{0430}yer").is_err());
+        assert!(validate("Player\n").is_err());
+    }
+
+    #[test]
+    fn names_differing_in_case_are_taken() {
+        let name = PlayerName::from("Player".to_string());
+
+        assert!(name.is_taken_by(&PlayerName::from("pLAYER".to_string())));
+        assert!(!name.is_taken_by(&PlayerName::from("Player2".to_string())));
+    }
 }
\ No newline at end of file
@@ -1,12 +1,15 @@
 use bevy::prelude::*;
 
+use crate::channels::Channel;
 use crate::network_id::NetworkId;
+use crate::network_message::{AddNetworkMessage, Direction, NetworkMessage, Recipients};
 use packets_derive::{IntoPacket, TryFromPacket};
 use crate::player::{PlayerName, PlayerId};
 use crate::part::PartNetworkRepr;
 use crate::compact_transform::CompactTransform;
+use crate::construct_access::ConstructAccess;
 
-#[derive(IntoPacket, TryFromPacket, Event)]
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
 #[PacketType(PlayerConnected)]
 pub struct PlayerConnected {
     pub id: PlayerId,
@@ -14,16 +17,79 @@ pub struct PlayerConnected {
     pub transform: Transform,
 }
 
-#[derive(IntoPacket, TryFromPacket, Event)]
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
 #[PacketType(PlayerDisconnected)]
 pub struct PlayerDisconnected(pub PlayerId);
 
-#[derive(IntoPacket, TryFromPacket, Event)]
+// Everything a new player needs except the constructs' parts, which follow in InitialStateChunks
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
 #[PacketType(InitialState)]
 pub struct InitialState {
     pub player_id: PlayerId,
+    #[packet(varint)]
     pub players: Vec<(PlayerId, PlayerName, Transform)>,
-    pub construct_network_id: NetworkId,
+    #[packet(varint)]
+    pub constructs: Vec<(NetworkId, CompactTransform, ConstructAccess)>,
+    // How many parts the chunks will hold across all constructs, for showing progress
+    // Parts deleted while the chunks are being sent are left out, so fewer can arrive
+    #[packet(varint)]
+    pub part_count: u32,
+}
+
+// Some of one construct's parts, as they are when the chunk is sent
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
+#[PacketType(InitialStateChunk)]
+pub struct InitialStateChunk {
+    pub player_id: PlayerId,
+    // None if there were no parts to send, in which case this is the only chunk
+    pub construct_network_id: Option<NetworkId>,
+    #[packet(varint)]
     pub parts: Vec<(PartNetworkRepr, CompactTransform, NetworkId)>,
-    pub construct_transform: CompactTransform
+    // The player can start playing once the last chunk has been loaded
+    pub last: bool,
+}
+
+// Sent by the client once it has loaded the last chunk, until then the server holds back its other packets
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
+#[PacketType(InitialStateLoaded)]
+pub struct InitialStateLoaded {
+    // Checked against the number of chunks the server sent
+    #[packet(varint)]
+    pub chunk_count: u32,
+}
+
+// The new player learns about existing players from its initial state instead
+impl NetworkMessage for PlayerConnected {
+    fn recipients(&self) -> Recipients {
+        Recipients::AllExcept(self.id)
+    }
+}
+
+impl NetworkMessage for PlayerDisconnected {}
+
+impl NetworkMessage for InitialState {
+    fn recipients(&self) -> Recipients {
+        Recipients::Only(self.player_id)
+    }
+}
+
+impl NetworkMessage for InitialStateChunk {
+    fn recipients(&self) -> Recipients {
+        Recipients::Only(self.player_id)
+    }
+}
+
+impl NetworkMessage for InitialStateLoaded {}
+
+pub struct PlayerConnectionPlugin;
+
+impl Plugin for PlayerConnectionPlugin {
+    fn build(&self, app: &mut App) {
+        // The initial state is sent on the part commands channel so that it stays in order with the part commands
+        app.add_network_message::<PlayerConnected>(Direction::ServerToClient, Channel::PlayerConnectionEvents)
+            .add_network_message::<PlayerDisconnected>(Direction::ServerToClient, Channel::PlayerConnectionEvents)
+            .add_network_message::<InitialState>(Direction::ServerToClient, Channel::PartCommands)
+            .add_network_message::<InitialStateChunk>(Direction::ServerToClient, Channel::PartCommands)
+            .add_player_message::<InitialStateLoaded>(Channel::PartCommands);
+    }
 }
\ No newline at end of file
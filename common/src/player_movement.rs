@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+use packets_derive::{IntoPacket, TryFromPacket, PacketSerialize, PacketDeserialize};
+
+use crate::PHYSICS_TIMESTEP;
+use crate::channels::Channel;
+use crate::compact_transform::CompactTransform;
+use crate::network_message::{AddNetworkMessage, Direction, NetworkMessage};
+use crate::player::PlayerId;
+
+// The largest impulses a player can apply to itself each tick, the server clamps received input to these
+pub const MAX_MOVE_IMPULSE: f32 = PHYSICS_TIMESTEP * 50.0;
+pub const MAX_TORQUE_IMPULSE: f32 = PHYSICS_TIMESTEP * 100.0;
+
+// Sent by the client every tick it controls its player, the server applies it to the sender's player
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
+#[PacketType(PlayerInput)]
+pub struct PlayerInput {
+    // Increases by one with every input, so the client can tell which inputs a snapshot includes
+    pub sequence: u32,
+    pub impulse: Vec3,
+    pub torque_impulse: Vec3,
+}
+
+impl PlayerInput {
+    pub fn clamped(&self) -> (Vec3, Vec3) {
+        (self.impulse.clamp_length_max(MAX_MOVE_IMPULSE), self.torque_impulse.clamp_length_max(MAX_TORQUE_IMPULSE))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PacketSerialize, PacketDeserialize)]
+pub struct PlayerState {
+    pub id: PlayerId,
+    // The sequence number of the newest input from this player that the server has applied
+    pub last_input: u32,
+    pub transform: CompactTransform,
+}
+
+// Broadcast by the server every tick on an unreliable channel, so older snapshots can be lost or arrive late
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
+#[PacketType(PlayerStates)]
+pub struct PlayerStates {
+    pub tick: u32,
+    #[packet(varint)]
+    pub players: Vec<PlayerState>,
+}
+
+impl NetworkMessage for PlayerInput {}
+impl NetworkMessage for PlayerStates {}
+
+pub struct PlayerMovementPlugin;
+
+impl Plugin for PlayerMovementPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_player_message::<PlayerInput>(Channel::PlayerMovement)
+            .add_network_message::<PlayerStates>(Direction::ServerToClient, Channel::PlayerMovement);
+    }
+}
\ No newline at end of file
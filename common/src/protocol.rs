@@ -0,0 +1,147 @@
+use std::fmt::Debug;
+
+use bevy::prelude::*;
+
+use packets::{Packet, PacketError, PacketSchema, PacketType};
+use packets::schema::combine;
+use packets_derive::{IntoPacket, TryFromPacket};
+
+use crate::construct_access::{GrantConstructRole, RevokeConstructRole, ConstructAccessChanged};
+use crate::construct_replication::ConstructStates;
+use crate::network_message::Direction;
+use crate::missile::{SpawnMissileRequest, SpawnMissileCommand, ExplodeMissileCommand};
+use crate::part::events::{PlacePartRequest, PlacePartCommand, DeletePartRequest, DeletePartCommand, DeleteVoxelRequest, VoxelUpdate, BuildRequestRejected, FullVoxelsRequest, FullVoxelsCommand};
+use crate::player::PlayerName;
+use crate::player_movement::{PlayerInput, PlayerStates};
+use crate::player_connection::{PlayerConnected, PlayerDisconnected, InitialState, InitialStateChunk, InitialStateLoaded};
+
+// Generates PROTOCOL_FINGERPRINT and decode_message from one table of every packet type and the messages it carries
+// decode_message matches on every packet type, so a new one can't be left out of the fingerprint
+macro_rules! protocol {
+    (
+        handshake { $($handshake_type:ident => $handshake_message:ty;)* }
+        $($packet_type:ident => $message:ty $(, $command:ty)?;)*
+    ) => {
+        // Fingerprint of every packet exchanged after the handshake
+        pub const PROTOCOL_FINGERPRINT: u64 = combine(&[
+            $(<$message>::FINGERPRINT, $(<$command>::FINGERPRINT,)?)*
+        ]);
+
+        // Decodes a packet into the message it carries, for debugging tools like the packet inspector
+        // Requests and commands share packet types, so the direction decides which one the packet holds
+        pub fn decode_message(packet: Packet, direction: Direction) -> Result<Box<dyn Debug>, PacketError> {
+            let client_to_server = direction == Direction::ClientToServer;
+
+            match packet.packet_type() {
+                $(PacketType::$handshake_type => <$handshake_message>::try_from(packet).map(boxed),)*
+                $(PacketType::$packet_type => protocol!(@decode packet, client_to_server, $message $(, $command)?),)*
+            }
+        }
+    };
+    (@decode $packet:ident, $client_to_server:ident, $message:ty) => {
+        <$message>::try_from($packet).map(boxed)
+    };
+    (@decode $packet:ident, $client_to_server:ident, $request:ty, $command:ty) => {
+        if $client_to_server {
+            <$request>::try_from($packet).map(boxed)
+        } else {
+            <$command>::try_from($packet).map(boxed)
+        }
+    };
+}
+
+fn boxed<T: Debug + 'static>(message: T) -> Box<dyn Debug> {
+    Box::new(message)
+}
+
+// Packet types shared by a request and a command list the request first
+protocol! {
+    // Exchanged before the protocol fingerprint has been checked, so they aren't part of it
+    handshake {
+        Handshake => Handshake;
+        ConnectionRejected => ConnectionRejected;
+    }
+    PlacePart => PlacePartRequest, PlacePartCommand;
+    DeletePart => DeletePartRequest, DeletePartCommand;
+    InitialState => InitialState;
+    PlayerConnected => PlayerConnected;
+    PlayerDisconnected => PlayerDisconnected;
+    VoxelUpdate => VoxelUpdate;
+    SpawnMissile => SpawnMissileRequest, SpawnMissileCommand;
+    ExplodeMissile => ExplodeMissileCommand;
+    ClientHello => ClientHello;
+    PlayerInput => PlayerInput;
+    PlayerStates => PlayerStates;
+    ConstructStates => ConstructStates;
+    BuildRequestRejected => BuildRequestRejected;
+    GrantConstructRole => GrantConstructRole;
+    RevokeConstructRole => RevokeConstructRole;
+    ConstructAccessChanged => ConstructAccessChanged;
+    InitialStateChunk => InitialStateChunk;
+    InitialStateLoaded => InitialStateLoaded;
+    FullVoxels => FullVoxelsRequest, FullVoxelsCommand;
+    DeleteVoxel => DeleteVoxelRequest;
+}
+
+// The first packet a client sends after connecting
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
+#[PacketType(Handshake)]
+pub struct Handshake {
+    pub fingerprint: u64,
+}
+
+impl Default for Handshake {
+    fn default() -> Self {
+        Self { fingerprint: PROTOCOL_FINGERPRINT }
+    }
+}
+
+// Sent by the client right after its handshake, the server only creates the player once this arrives
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
+#[PacketType(ClientHello)]
+pub struct ClientHello {
+    pub name: PlayerName,
+}
+
+// Sent to a client right before the server disconnects it
+#[derive(Debug, IntoPacket, TryFromPacket, Event)]
+#[PacketType(ConnectionRejected)]
+pub struct ConnectionRejected {
+    pub reason: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use packets::{Packet, PacketError};
+
+    use crate::network_id::NetworkId;
+    use crate::network_message::Direction;
+    use crate::part::events::{DeletePartRequest, VoxelUpdate};
+    use crate::part::voxel_changes::VoxelChanges;
+    use crate::protocol::decode_message;
+
+    #[test]
+    fn direction_selects_message() {
+        let packet = Packet::from(&DeletePartRequest { request_seq: 1, part_network_id: NetworkId::from(3) });
+
+        let request = decode_message(packet.clone(), Direction::ClientToServer).unwrap();
+        let command = decode_message(packet, Direction::ServerToClient).unwrap();
+
+        assert!(format!("{:?}", request).starts_with("DeletePartRequest {"));
+        assert!(format!("{:?}", command).starts_with("DeletePartCommand("));
+    }
+
+    #[test]
+    fn truncated_message_is_error() {
+        let packet = Packet::from(&VoxelUpdate { network_id: NetworkId::from(3), revision: 1, changes: VoxelChanges::Delta(Vec::new()) });
+        let mut data: Vec<u8> = Box::<[u8]>::from(&packet).into();
+        data.pop();
+
+        let packet = Packet::try_from(data.into_boxed_slice()).unwrap();
+        let err = decode_message(packet, Direction::ServerToClient).unwrap_err();
+
+        assert!(matches!(err, PacketError::BoundsError(_)));
+        // The message, its changes field, the variant and the variant's runs
+        assert_eq!(err.location().unwrap().path().count(), 4);
+    }
+}
\ No newline at end of file
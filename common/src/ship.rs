@@ -1,6 +1,7 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
+use crate::construct_access::ConstructAccess;
 use crate::network_id::NetworkId;
 
 #[derive(Component)]
@@ -14,6 +15,7 @@ pub struct ShipBundle {
     pub rigid_body: RigidBody,
     pub velocity: Velocity,
     pub ship: Ship,
+    pub access: ConstructAccess,
 }
 
 impl Default for ShipBundle {
@@ -25,6 +27,7 @@ impl Default for ShipBundle {
             rigid_body: RigidBody::Dynamic,
             velocity: Velocity::default(),
             ship: Ship,
+            access: ConstructAccess::default(),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,149 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+// How far behind the newest snapshot remote entities are shown, in ticks
+// Gives snapshots that arrive late or out of order time to fill in the gaps
+pub const INTERPOLATION_DELAY: f32 = 6.0;
+
+// Snapshots older than this many ticks behind the newest one are dropped
+const MAX_SNAPSHOT_AGE: u32 = 60;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Snapshot {
+    pub tick: u32,
+    pub translation: Vec3,
+    pub rotation: Quat,
+}
+
+// Server snapshots of an entity's transform, oldest first
+#[derive(Component, Debug, Default)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl SnapshotBuffer {
+    // Snapshots can arrive out of order, so they are inserted by tick and duplicates are ignored
+    pub fn push(&mut self, snapshot: Snapshot) {
+        let index = self.snapshots.partition_point(|existing| existing.tick < snapshot.tick);
+        if self.snapshots.get(index).is_some_and(|existing| existing.tick == snapshot.tick) {
+            return;
+        }
+
+        self.snapshots.insert(index, snapshot);
+
+        let newest_tick = self.snapshots.back().unwrap().tick;
+        while self.snapshots.front().is_some_and(|oldest| newest_tick - oldest.tick > MAX_SNAPSHOT_AGE) {
+            self.snapshots.pop_front();
+        }
+    }
+
+    pub fn newest(&self) -> Option<&Snapshot> {
+        self.snapshots.back()
+    }
+
+    // The transform at the given tick, holding the oldest or newest snapshot outside of the buffered range
+    pub fn sample(&self, tick: f32) -> Option<(Vec3, Quat)> {
+        let after_index = self.snapshots.partition_point(|snapshot| (snapshot.tick as f32) < tick);
+
+        let after = match self.snapshots.get(after_index) {
+            Some(after) => after,
+            None => {
+                let newest = self.snapshots.back()?;
+                return Some((newest.translation, newest.rotation));
+            }
+        };
+
+        let Some(before) = after_index.checked_sub(1).and_then(|index| self.snapshots.get(index)) else {
+            return Some((after.translation, after.rotation));
+        };
+
+        let t = (tick - before.tick as f32) / (after.tick - before.tick) as f32;
+
+        Some((before.translation.lerp(after.translation, t), before.rotation.slerp(after.rotation, t)))
+    }
+}
+
+// Decides which tick remote entities are shown at, trailing the newest snapshot by INTERPOLATION_DELAY
+#[derive(Resource, Debug, Default)]
+pub struct InterpolationClock {
+    newest_tick: Option<u32>,
+    playback_tick: f32,
+}
+
+impl InterpolationClock {
+    pub fn observe(&mut self, tick: u32) {
+        self.newest_tick = Some(self.newest_tick.map_or(tick, |newest| newest.max(tick)));
+    }
+
+    // None until the first snapshot has arrived
+    pub fn playback_tick(&self) -> Option<f32> {
+        self.newest_tick.map(|_| self.playback_tick)
+    }
+
+    // Moves the clock forward by a tick, returning None until the first snapshot has arrived
+    pub fn advance(&mut self) -> Option<f32> {
+        let newest_tick = self.newest_tick? as f32;
+        let target = newest_tick - INTERPOLATION_DELAY;
+
+        if (self.playback_tick - target).abs() > INTERPOLATION_DELAY {
+            // Jump on the first snapshot, or after the server stalled or raced ahead
+            self.playback_tick = target;
+        } else {
+            // Otherwise drift towards the target so that playback stays smooth
+            self.playback_tick += 1.0 + (target - self.playback_tick) * 0.05;
+        }
+
+        self.playback_tick = self.playback_tick.min(newest_tick);
+
+        Some(self.playback_tick)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::{InterpolationClock, Snapshot, SnapshotBuffer, INTERPOLATION_DELAY};
+
+    fn snapshot(tick: u32, x: f32) -> Snapshot {
+        Snapshot { tick, translation: Vec3::new(x, 0.0, 0.0), rotation: Quat::IDENTITY }
+    }
+
+    #[test]
+    fn sample_interpolates_between_snapshots() {
+        let mut buffer = SnapshotBuffer::default();
+        buffer.push(snapshot(10, 0.0));
+        buffer.push(snapshot(14, 4.0));
+
+        assert_eq!(buffer.sample(11.0).unwrap().0.x, 1.0);
+        assert_eq!(buffer.sample(5.0).unwrap().0.x, 0.0);
+        assert_eq!(buffer.sample(20.0).unwrap().0.x, 4.0);
+    }
+
+    #[test]
+    fn out_of_order_snapshots_are_sorted() {
+        let mut buffer = SnapshotBuffer::default();
+        buffer.push(snapshot(12, 2.0));
+        buffer.push(snapshot(10, 0.0));
+        buffer.push(snapshot(12, 100.0));
+
+        assert_eq!(buffer.newest().unwrap().translation.x, 2.0);
+        assert_eq!(buffer.sample(11.0).unwrap().0.x, 1.0);
+    }
+
+    #[test]
+    fn clock_trails_newest_snapshot() {
+        let mut clock = InterpolationClock::default();
+        assert_eq!(clock.advance(), None);
+
+        clock.observe(100);
+        assert_eq!(clock.advance(), Some(100.0 - INTERPOLATION_DELAY));
+
+        // Without new snapshots the clock stops at the newest one
+        for _ in 0..(INTERPOLATION_DELAY as usize * 2) {
+            clock.advance();
+        }
+        assert_eq!(clock.advance(), Some(100.0));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,456 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use bevy::utils::HashMap;
+use uflow::SendMode;
+use uflow::client::Event as ClientEvent;
+use uflow::server::Event as ServerEvent;
+
+use crate::PHYSICS_TIMESTEP;
+use crate::channels::Channel;
+use crate::transport::{ClientTransport, ServerTransport};
+
+const SERVER_ADDRESS: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 36756);
+const FIRST_CLIENT_PORT: u16 = 50000;
+
+// Reliable datagrams that keep getting lost are delivered after this many resends anyway
+const MAX_RESENDS: u32 = 8;
+
+// The conditions every datagram is sent under, in both directions
+// Delays are counted in steps of the receiving end, which the game steps once per tick
+#[derive(Clone, Debug, Default)]
+pub struct LinkConditions {
+    // Steps added to every delivery, on top of the receiver's next step
+    pub latency: u32,
+    // Each datagram is delayed by up to this many more steps
+    pub jitter: u32,
+    // The chance of a datagram being lost, reliable datagrams are resent a round trip later instead
+    pub loss: f32,
+    // The chance of an unreliable datagram being held back until after the ones sent after it
+    // uflow keeps every channel in order, so this is harsher than a real connection
+    pub reorder: f32,
+}
+
+// A small xorshift generator, so that the same seed loses and delays the same datagrams
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Xorshift gets stuck on zero
+        Self(seed.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // Uniform in [0, 1)
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // Uniform in [0, max]
+    fn up_to(&mut self, max: u32) -> u64 {
+        self.next_u64() % (max as u64 + 1)
+    }
+}
+
+enum Delivery {
+    Data(Box<[u8]>),
+    Disconnect,
+}
+
+struct InFlight {
+    deliver_at: u64,
+    // Breaks ties between datagrams delivered on the same step, in the order they were sent
+    sequence: u64,
+    delivery: Delivery,
+}
+
+// Datagrams travelling in one direction over a link
+#[derive(Default)]
+struct Lane {
+    in_flight: Vec<InFlight>,
+    // Reliable channels are delivered in order, so nothing is delivered before what was sent ahead of it
+    reliable_deliver_at: HashMap<Channel, u64>,
+    // Set once the sending end has disconnected, later datagrams are dropped
+    closed: bool,
+}
+
+impl Lane {
+    fn take_due(&mut self, step: u64) -> Vec<Delivery> {
+        self.in_flight.sort_by_key(|in_flight| (in_flight.deliver_at, in_flight.sequence));
+        let due = self.in_flight.partition_point(|in_flight| in_flight.deliver_at <= step);
+
+        self.in_flight.drain(..due).map(|in_flight| in_flight.delivery).collect()
+    }
+
+    fn last_delivery(&self) -> u64 {
+        self.in_flight.iter().map(|in_flight| in_flight.deliver_at).max().unwrap_or(0)
+    }
+}
+
+struct Link {
+    to_server: Lane,
+    to_client: Lane,
+    client_steps: u64,
+    client_events: Vec<ClientEvent>,
+}
+
+struct Network {
+    conditions: LinkConditions,
+    rng: Rng,
+    sequence: u64,
+    server_steps: u64,
+    server_events: Vec<ServerEvent>,
+    links: HashMap<SocketAddr, Link>,
+    next_client_port: u16,
+}
+
+impl Network {
+    // Returns None if the datagram is lost
+    fn delay(&mut self, send_mode: SendMode) -> Option<u64> {
+        let conditions = &self.conditions;
+        let mut delay = conditions.latency as u64 + self.rng.up_to(conditions.jitter);
+
+        match send_mode {
+            SendMode::Reliable | SendMode::Persistent => {
+                // The sender notices the missing acknowledgement a round trip later and resends
+                let mut resends = 0;
+                while resends < MAX_RESENDS && self.rng.next_f32() < conditions.loss {
+                    delay += conditions.latency as u64 * 2 + 1;
+                    resends += 1;
+                }
+            },
+            SendMode::Unreliable | SendMode::TimeSensitive => {
+                if self.rng.next_f32() < conditions.loss {
+                    return None;
+                }
+
+                if self.rng.next_f32() < conditions.reorder {
+                    delay += conditions.jitter as u64 + 1;
+                }
+            }
+        }
+
+        Some(delay)
+    }
+
+    fn send(&mut self, client_address: SocketAddr, to_server: bool, data: Box<[u8]>, channel: Channel) {
+        let Some(delay) = self.delay(channel.send_mode()) else { return };
+
+        self.sequence += 1;
+        let sequence = self.sequence;
+        let server_steps = self.server_steps;
+
+        let Some(link) = self.links.get_mut(&client_address) else { return };
+        let (lane, receiver_steps) = match to_server {
+            true => (&mut link.to_server, server_steps),
+            false => (&mut link.to_client, link.client_steps),
+        };
+
+        if lane.closed {
+            return;
+        }
+
+        let mut deliver_at = receiver_steps + 1 + delay;
+        if matches!(channel.send_mode(), SendMode::Reliable | SendMode::Persistent) {
+            let reliable_deliver_at = lane.reliable_deliver_at.entry(channel).or_default();
+            deliver_at = deliver_at.max(*reliable_deliver_at);
+            *reliable_deliver_at = deliver_at;
+        }
+
+        lane.in_flight.push(InFlight { deliver_at, sequence, delivery: Delivery::Data(data) });
+    }
+
+    // Queues a disconnect behind everything already sent, and stops anything being sent back
+    fn disconnect(&mut self, client_address: SocketAddr, from_server: bool) {
+        self.sequence += 1;
+        let sequence = self.sequence;
+        let latency = self.conditions.latency as u64;
+        let server_steps = self.server_steps;
+
+        let Some(link) = self.links.get_mut(&client_address) else { return };
+        let (lane, back_lane, receiver_steps) = match from_server {
+            true => (&mut link.to_client, &mut link.to_server, link.client_steps),
+            false => (&mut link.to_server, &mut link.to_client, server_steps),
+        };
+
+        if lane.closed {
+            return;
+        }
+
+        let deliver_at = lane.last_delivery().max(receiver_steps + 1 + latency);
+        lane.in_flight.push(InFlight { deliver_at, sequence, delivery: Delivery::Disconnect });
+        lane.closed = true;
+
+        back_lane.in_flight.clear();
+        back_lane.closed = true;
+
+        // The disconnecting end finds out on its next step
+        match from_server {
+            true => self.server_events.push(ServerEvent::Disconnect(client_address)),
+            false => link.client_events.push(ClientEvent::Disconnect),
+        }
+    }
+
+    fn step_server(&mut self) -> Vec<ServerEvent> {
+        self.server_steps += 1;
+
+        let mut events: Vec<ServerEvent> = self.server_events.drain(..).collect();
+        let mut disconnected = Vec::new();
+
+        for (&client_address, link) in self.links.iter_mut() {
+            for delivery in link.to_server.take_due(self.server_steps) {
+                match delivery {
+                    Delivery::Data(data) => events.push(ServerEvent::Receive(client_address, data)),
+                    Delivery::Disconnect => {
+                        events.push(ServerEvent::Disconnect(client_address));
+                        disconnected.push(client_address);
+                    }
+                }
+            }
+        }
+
+        for client_address in disconnected {
+            self.links.remove(&client_address);
+        }
+
+        events
+    }
+
+    fn step_client(&mut self, client_address: SocketAddr) -> Vec<ClientEvent> {
+        let Some(link) = self.links.get_mut(&client_address) else {
+            return Vec::new();
+        };
+
+        link.client_steps += 1;
+
+        let mut events: Vec<ClientEvent> = link.client_events.drain(..).collect();
+        let mut disconnected = false;
+
+        for delivery in link.to_client.take_due(link.client_steps) {
+            match delivery {
+                Delivery::Data(data) => events.push(ClientEvent::Receive(data)),
+                Delivery::Disconnect => {
+                    events.push(ClientEvent::Disconnect);
+                    disconnected = true;
+                }
+            }
+        }
+
+        // A client that disconnected itself keeps the link until the server has been told
+        if disconnected {
+            self.links.remove(&client_address);
+        }
+
+        events
+    }
+}
+
+// Connects a server and its clients within one process, with simulated network conditions
+// Cloning it gives another handle to the same network
+#[derive(Clone)]
+pub struct LoopbackNetwork {
+    network: Arc<Mutex<Network>>,
+}
+
+impl LoopbackNetwork {
+    pub fn new(conditions: LinkConditions, seed: u64) -> Self {
+        Self {
+            network: Arc::new(Mutex::new(Network {
+                conditions,
+                rng: Rng::new(seed),
+                sequence: 0,
+                server_steps: 0,
+                server_events: Vec::new(),
+                links: HashMap::new(),
+                next_client_port: FIRST_CLIENT_PORT,
+            })),
+        }
+    }
+
+    // Datagrams already in flight keep the conditions they were sent under
+    pub fn set_conditions(&self, conditions: LinkConditions) {
+        self.network.lock().unwrap().conditions = conditions;
+    }
+
+    pub fn server(&self) -> LoopbackServer {
+        LoopbackServer { network: self.clone() }
+    }
+
+    // Both ends see the connection on their next step
+    pub fn connect(&self) -> LoopbackClient {
+        let mut network = self.network.lock().unwrap();
+
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), network.next_client_port);
+        network.next_client_port += 1;
+
+        network.links.insert(address, Link {
+            to_server: Lane::default(),
+            to_client: Lane::default(),
+            client_steps: 0,
+            client_events: vec![ClientEvent::Connect],
+        });
+        network.server_events.push(ServerEvent::Connect(address));
+
+        LoopbackClient { network: self.clone(), address }
+    }
+}
+
+pub struct LoopbackServer {
+    network: LoopbackNetwork,
+}
+
+impl ServerTransport for LoopbackServer {
+    fn step(&mut self) -> Vec<ServerEvent> {
+        self.network.network.lock().unwrap().step_server()
+    }
+
+    fn send(&mut self, client_address: SocketAddr, data: Box<[u8]>, channel: Channel) {
+        self.network.network.lock().unwrap().send(client_address, false, data, channel);
+    }
+
+    fn disconnect(&mut self, client_address: SocketAddr) {
+        self.network.network.lock().unwrap().disconnect(client_address, true);
+    }
+
+    fn flush(&mut self) {}
+
+    fn address(&self) -> SocketAddr {
+        SERVER_ADDRESS
+    }
+}
+
+pub struct LoopbackClient {
+    network: LoopbackNetwork,
+    address: SocketAddr,
+}
+
+impl LoopbackClient {
+    // The address the server knows this client by
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+impl ClientTransport for LoopbackClient {
+    fn step(&mut self) -> Vec<ClientEvent> {
+        self.network.network.lock().unwrap().step_client(self.address)
+    }
+
+    fn send(&mut self, data: Box<[u8]>, channel: Channel) {
+        self.network.network.lock().unwrap().send(self.address, true, data, channel);
+    }
+
+    fn disconnect(&mut self) {
+        self.network.network.lock().unwrap().disconnect(self.address, false);
+    }
+
+    fn flush(&mut self) {}
+
+    fn rtt_s(&self) -> Option<f64> {
+        let network = self.network.network.lock().unwrap();
+        let round_trip_steps = (network.conditions.latency + 1) * 2 + network.conditions.jitter;
+
+        Some(round_trip_steps as f64 * PHYSICS_TIMESTEP as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uflow::client::Event as ClientEvent;
+    use uflow::server::Event as ServerEvent;
+
+    use crate::channels::Channel;
+    use crate::transport::{ClientTransport, ServerTransport};
+
+    use super::{LinkConditions, LoopbackNetwork};
+
+    fn received_numbers(events: Vec<ServerEvent>) -> Vec<u8> {
+        events.into_iter()
+            .filter_map(|event| match event {
+                ServerEvent::Receive(_, data) => Some(data[0]),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn both_ends_see_connect_and_disconnect() {
+        let network = LoopbackNetwork::new(LinkConditions { latency: 2, ..Default::default() }, 1);
+        let mut server = network.server();
+        let mut client = network.connect();
+
+        assert!(matches!(server.step()[..], [ServerEvent::Connect(address)] if address == client.address()));
+        assert!(matches!(client.step()[..], [ClientEvent::Connect]));
+
+        server.disconnect(client.address());
+        assert!(matches!(server.step()[..], [ServerEvent::Disconnect(_)]));
+
+        // The disconnect takes as long as a datagram to arrive
+        assert!(client.step().is_empty());
+        assert!(client.step().is_empty());
+        assert!(matches!(client.step()[..], [ClientEvent::Disconnect]));
+    }
+
+    #[test]
+    fn latency_delays_delivery() {
+        let network = LoopbackNetwork::new(LinkConditions { latency: 3, ..Default::default() }, 1);
+        let mut server = network.server();
+        let mut client = network.connect();
+        server.step();
+
+        client.send(Box::new([7]), Channel::PartCommands);
+
+        for _ in 0..3 {
+            assert!(received_numbers(server.step()).is_empty());
+        }
+        assert_eq!(received_numbers(server.step()), vec![7]);
+    }
+
+    #[test]
+    fn reliable_datagrams_arrive_in_order_despite_loss() {
+        let conditions = LinkConditions { latency: 2, jitter: 3, loss: 0.5, reorder: 0.5 };
+        let network = LoopbackNetwork::new(conditions, 7);
+        let mut server = network.server();
+        let mut client = network.connect();
+
+        let mut received = Vec::new();
+        for number in 0..50 {
+            client.send(Box::new([number]), Channel::PartCommands);
+            received.extend(received_numbers(server.step()));
+        }
+        for _ in 0..200 {
+            received.extend(received_numbers(server.step()));
+        }
+
+        assert_eq!(received, (0..50).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn unreliable_datagrams_are_lost_and_reordered() {
+        let conditions = LinkConditions { latency: 1, jitter: 4, loss: 0.2, reorder: 0.2 };
+        let network = LoopbackNetwork::new(conditions, 7);
+        let mut server = network.server();
+        let mut client = network.connect();
+
+        let mut received = Vec::new();
+        for number in 0..100 {
+            client.send(Box::new([number]), Channel::PlayerMovement);
+            received.extend(received_numbers(server.step()));
+        }
+        for _ in 0..20 {
+            received.extend(received_numbers(server.step()));
+        }
+
+        let mut sorted = received.clone();
+        sorted.sort();
+
+        assert!(received.len() < 100);
+        assert_ne!(received, sorted);
+    }
+}
\ No newline at end of file
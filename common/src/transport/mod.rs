@@ -0,0 +1,90 @@
+use std::net::SocketAddr;
+
+use uflow::client::{Client, Event as ClientEvent};
+use uflow::server::{Event as ServerEvent, Server};
+
+use crate::channels::Channel;
+
+pub mod loopback;
+
+// The server's end of the connections to its clients, uflow's server unless the connections are simulated
+// Events use uflow's types, so the packet handling is the same for every transport
+pub trait ServerTransport {
+    // Returns the connections, disconnections and datagrams since the last step
+    fn step(&mut self) -> Vec<ServerEvent>;
+
+    fn send(&mut self, client_address: SocketAddr, data: Box<[u8]>, channel: Channel);
+
+    // Disconnects the client once everything sent to it has been delivered
+    fn disconnect(&mut self, client_address: SocketAddr);
+
+    // Sends queued datagrams now instead of on the next step
+    fn flush(&mut self);
+
+    fn address(&self) -> SocketAddr;
+}
+
+// The client's end of its connection to the server
+pub trait ClientTransport: Send + Sync {
+    // Returns the connection, disconnection and datagrams since the last step
+    fn step(&mut self) -> Vec<ClientEvent>;
+
+    fn send(&mut self, data: Box<[u8]>, channel: Channel);
+
+    // Disconnects once everything sent to the server has been delivered
+    fn disconnect(&mut self);
+
+    // Sends queued datagrams now instead of on the next step
+    fn flush(&mut self);
+
+    // The round trip time to the server in seconds, None until it has been measured
+    fn rtt_s(&self) -> Option<f64>;
+}
+
+impl ServerTransport for Server {
+    fn step(&mut self) -> Vec<ServerEvent> {
+        Server::step(self).collect()
+    }
+
+    fn send(&mut self, client_address: SocketAddr, data: Box<[u8]>, channel: Channel) {
+        if let Some(remote_client) = self.client(&client_address) {
+            remote_client.borrow_mut().send(data, channel.into(), channel.send_mode());
+        }
+    }
+
+    fn disconnect(&mut self, client_address: SocketAddr) {
+        if let Some(remote_client) = self.client(&client_address) {
+            remote_client.borrow_mut().disconnect();
+        }
+    }
+
+    fn flush(&mut self) {
+        Server::flush(self);
+    }
+
+    fn address(&self) -> SocketAddr {
+        Server::address(self)
+    }
+}
+
+impl ClientTransport for Client {
+    fn step(&mut self) -> Vec<ClientEvent> {
+        Client::step(self).collect()
+    }
+
+    fn send(&mut self, data: Box<[u8]>, channel: Channel) {
+        Client::send(self, data, channel.into(), channel.send_mode());
+    }
+
+    fn disconnect(&mut self) {
+        Client::disconnect(self);
+    }
+
+    fn flush(&mut self) {
+        Client::flush(self);
+    }
+
+    fn rtt_s(&self) -> Option<f64> {
+        Client::rtt_s(self)
+    }
+}
\ No newline at end of file
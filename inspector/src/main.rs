@@ -0,0 +1,172 @@
+use std::fs;
+use std::io::Read;
+use std::process::ExitCode;
+
+use common::network_message::Direction;
+use common::protocol::decode_message;
+use packets::batch::{Unbatcher, BATCH_TAG};
+use packets::hex;
+
+const USAGE: &str = "\
+Decodes captured datagrams into the messages they carry
+
+Usage: packet-inspector [OPTIONS] [FILE]
+
+Reads hex dumps from FILE, or from stdin if no file is given, one datagram per line.
+Anything before the last ':' on a line is ignored, so server log lines can be pasted as they are.
+
+Options:
+    --from <client|server>  Which side sent the datagrams [default: server]
+    --binary                FILE holds a single raw datagram instead of hex dumps
+    -h, --help              Print this message";
+
+const BYTES_PER_ROW: usize = 16;
+
+struct Options {
+    direction: Direction,
+    binary: bool,
+    path: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options { direction: Direction::ServerToClient, binary: false, path: None };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" => {
+                options.direction = match args.next().as_deref() {
+                    Some("client") => Direction::ClientToServer,
+                    Some("server") => Direction::ServerToClient,
+                    _ => return Err("--from expects client or server".to_string()),
+                };
+            },
+            "--binary" => options.binary = true,
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            _ if options.path.is_none() => options.path = Some(arg),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+
+    if options.binary && options.path.is_none() {
+        return Err("--binary needs a file".to_string());
+    }
+
+    Ok(options)
+}
+
+fn read_datagrams(options: &Options) -> Result<Vec<Result<Vec<u8>, String>>, String> {
+    if options.binary {
+        let path = options.path.as_ref().unwrap();
+        let data = fs::read(path).map_err(|err| format!("failed to read {}: {}", path, err))?;
+
+        return Ok(vec![Ok(data)]);
+    }
+
+    let text = match &options.path {
+        Some(path) => fs::read_to_string(path).map_err(|err| format!("failed to read {}: {}", path, err))?,
+        None => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text).map_err(|err| format!("failed to read stdin: {}", err))?;
+            text
+        }
+    };
+
+    Ok(text.lines()
+        .map(|line| line.rsplit(':').next().unwrap().trim())
+        .filter(|dump| !dump.is_empty() && !dump.starts_with('#'))
+        .map(hex::decode)
+        .collect())
+}
+
+// Prints the bytes of a packet, pointing at the byte where decoding stopped
+fn print_dump(bytes: &[u8], marked: Option<usize>) {
+    for (row, chunk) in bytes.chunks(BYTES_PER_ROW).enumerate() {
+        let row_start = row * BYTES_PER_ROW;
+        println!("    {:04x}  {}", row_start, hex::encode(chunk));
+
+        // Bounds errors point just past the end of the packet
+        let is_last_row = row_start + chunk.len() == bytes.len();
+        if let Some(marked) = marked.filter(|&marked| {
+            (row_start..row_start + chunk.len()).contains(&marked) || (is_last_row && marked >= bytes.len())
+        }) {
+            let column = (marked - row_start).min(BYTES_PER_ROW);
+            println!("          {}^^", " ".repeat(column * 3));
+        }
+    }
+}
+
+// Returns false if any packet in the datagram failed to decode
+fn inspect_datagram(number: usize, data: Vec<u8>, direction: Direction) -> bool {
+    let batched = data.first() == Some(&BATCH_TAG);
+    println!("Datagram {} ({} bytes{})", number, data.len(), if batched { ", batched" } else { "" });
+
+    let mut decoded = true;
+
+    for packet in Unbatcher::new(data.into_boxed_slice()) {
+        let packet = match packet {
+            Ok(packet) => packet,
+            Err(err) => {
+                println!("  error: {}", err);
+                decoded = false;
+                continue;
+            }
+        };
+
+        let bytes: Box<[u8]> = (&packet).into();
+
+        match decode_message(packet, direction) {
+            Ok(message) => {
+                for line in format!("{:#?}", message).lines() {
+                    println!("  {}", line);
+                }
+            },
+            Err(err) => {
+                println!("  error: {}", err);
+                // Offsets in errors don't count the packet type, which is the first byte of the dump
+                print_dump(&bytes, err.location().map(|location| location.offset + 1));
+                decoded = false;
+            }
+        }
+    }
+
+    decoded
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let options = match parse_args(args.into_iter()) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let datagrams = match read_datagrams(&options) {
+        Ok(datagrams) => datagrams,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut decoded = true;
+
+    for (i, datagram) in datagrams.into_iter().enumerate() {
+        match datagram {
+            Ok(data) => decoded &= inspect_datagram(i + 1, data, options.direction),
+            Err(err) => {
+                println!("Datagram {}: {}", i + 1, err);
+                decoded = false;
+            }
+        }
+    }
+
+    if decoded { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
\ No newline at end of file
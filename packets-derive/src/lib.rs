@@ -1,313 +1,551 @@
-use proc_macro::{TokenStream, Span};
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput, Data, DataStruct, Fields, Ident, Variant};
+use syn::{parse_macro_input, DeriveInput, Data, DataEnum, Field, Fields, Ident, LitInt, Path, Token, Variant};
 
-#[proc_macro_derive(PacketSerialize)]
+#[proc_macro_derive(PacketSerialize, attributes(packet))]
 pub fn packet_serialize_derive(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as syn::DeriveInput);
     impl_packet_serialize(&ast)
 }
 
 fn impl_packet_serialize(ast: &DeriveInput) -> TokenStream {
-    match &ast.data {
-        Data::Struct(data) => impl_packet_serialize_struct(&ast.ident, &data),
-        _ => unimplemented!(),
-    }
-}
-
-fn impl_packet_serialize_struct(name: &Ident, data: &DataStruct) -> TokenStream {
-    match &data.fields {
-        Fields::Named(fields) => {
-            let mut serialize_calls = quote!();
-            
-            for field in fields.named.iter() {
-                let field_name = field.ident.as_ref().unwrap();
-                serialize_calls.extend(quote!(
-                    (&self.#field_name).serialize(packet);
-                ));
+    let name = &ast.ident;
+    let serialize_calls = match serialize_calls(ast, quote!(self)) {
+        Ok(serialize_calls) => serialize_calls,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let schema = match schema_fingerprint(ast, None) {
+        Ok(schema) => schema,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let gen = quote! {
+        impl packets::PacketSerialize for #name {
+            fn serialize(&self, packet: &mut packets::Packet) {
+                #serialize_calls
             }
+        }
 
-            let gen = quote! {
-                impl packets::PacketSerialize for #name {
-                    fn serialize(&self, packet: &mut packets::Packet) {
-                        #serialize_calls
-                    }
-                }
-            };
-
-            gen.into()
-        },
-        Fields::Unnamed(fields) => {
-            let mut serialize_calls = quote!();
-
-            for i in 0..fields.unnamed.len() {
-                let index = syn::Index::from(i);
-                serialize_calls.extend(quote!(
-                    self.#index.serialize(packet);
-                ));
-            }
-
-            let gen = quote! {
-                impl packets::PacketSerialize for #name {
-                    fn serialize(&self, packet: &mut packets::Packet) {
-                        #serialize_calls
-                    }
-                }
-            };
-
-            gen.into()
-        },
-        Fields::Unit => {
-            let gen = quote! {
-                impl packets::PacketSerialize for #name {
-                    fn serialize(&self, packet: &mut packets::Packet) {}
-                }
-            };
-
-            gen.into()
+        impl packets::PacketSchema for #name {
+            const FINGERPRINT: u64 = #schema;
         }
-    }
+    };
+
+    gen.into()
 }
 
-#[proc_macro_derive(PacketDeserialize)]
+#[proc_macro_derive(PacketDeserialize, attributes(packet))]
 pub fn packet_deserialize_derive(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as syn::DeriveInput);
     impl_packet_deserialize(&ast)
 }
 
 fn impl_packet_deserialize(ast: &DeriveInput) -> TokenStream {
-    match &ast.data {
-        Data::Struct(data) => impl_packet_deserialize_struct(&ast.ident, &data),
-        _ => unimplemented!(),
-    }
+    let name = &ast.ident;
+    let deserialize_calls = match deserialize_calls(ast) {
+        Ok(deserialize_calls) => deserialize_calls,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let gen = quote! {
+        impl packets::PacketDeserialize for #name {
+            fn deserialize(packet: &mut packets::Packet) -> Result<Self, packets::PacketError> {
+                #deserialize_calls
+            }
+        }
+    };
+
+    gen.into()
 }
 
-fn impl_packet_deserialize_struct(name: &Ident, data: &DataStruct) -> TokenStream {
-    match &data.fields {
-        Fields::Named(fields) => {
-            let mut deserialize_calls = quote!();
-            
-            for field in fields.named.iter() {
-                let field_name = field.ident.as_ref().unwrap();
-                let field_type = &field.ty;
-                deserialize_calls.extend(quote!(
-                    let #field_name = <#field_type as packets::PacketDeserialize>::deserialize(packet)?;
-                ));
-            }
+#[proc_macro_derive(IntoPacket, attributes(PacketType, packet))]
+pub fn into_packet_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    impl_into_packet(&ast)
+}
+
+fn impl_into_packet(ast: &DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let packet_type = ast.attrs.iter()
+        .find(|&attr| attr.path().is_ident("PacketType"))
+        .expect("PacketType attribute not found")
+        .parse_args::<Variant>()
+        .unwrap();
 
-            let field_exprs = fields.named.iter().map(|field| {
-                let field_name = field.ident.as_ref().unwrap();
-                quote!(#field_name)
-            });
+    let serialize_calls = match serialize_calls(ast, quote!(value)) {
+        Ok(serialize_calls) => serialize_calls,
+        Err(err) => return err.to_compile_error().into(),
+    };
 
-            let gen = quote! {
-                impl packets::PacketDeserialize for #name {
-                    fn deserialize(packet: &mut packets::Packet) -> Result<Self, packets::PacketError> {
-                        #deserialize_calls
+    let schema = match schema_fingerprint(ast, Some(&packet_type)) {
+        Ok(schema) => schema,
+        Err(err) => return err.to_compile_error().into(),
+    };
 
-                        Ok(#name { #(#field_exprs),* })
-                    }
+    let gen = quote! {
+        impl From<&#name> for packets::Packet {
+            fn from(value: &#name) -> Self {
+                let mut packet = packets::Packet::new(packets::PacketType::#packet_type);
+
+                {
+                    let packet = &mut packet;
+                    #serialize_calls
                 }
-            };
 
-            gen.into()
-        },
-        Fields::Unnamed(fields) => {
-            let mut deserialize_calls = quote!();
-
-            let field_names: Vec<Ident> = fields.unnamed.iter()
-                .enumerate()
-                .map(|(i, _)| Ident::new(&format!("field_{}", i), Span::call_site().into()))
-                .collect();
-
-            for (i, field) in fields.unnamed.iter().enumerate() {
-                let field_name = &field_names[i];
-                let field_type = &field.ty;
-                
-                deserialize_calls.extend(quote!(
-                    let #field_name = <#field_type as packets::PacketDeserialize>::deserialize(packet)?;
-                ));
+                packet
             }
+        }
 
-            let field_names_iter = field_names.iter();
+        impl packets::TypedPacket for #name {
+            const PACKET_TYPE: packets::PacketType = packets::PacketType::#packet_type;
+        }
 
-            let gen = quote! {
-                impl packets::PacketDeserialize for #name {
-                    fn deserialize(packet: mut packets::Packet) -> Result<Self, packets::PacketError> {
-                        #deserialize_calls
+        impl packets::PacketSchema for #name {
+            const FINGERPRINT: u64 = #schema;
+        }
+    };
 
-                        Ok(#name(#(#field_names_iter),*))
-                    }
-                }
-            };
+    gen.into()
+}
 
-            gen.into()
-        },
-        Fields::Unit => {
-            let gen = quote! {
-                impl packets::PacketDeserialize for #name {
-                    fn deserialize(packet: &mut packets::Packet) -> Result<Self, packets::PacketError> {
-                        Ok(#name);
-                    }
+#[proc_macro_derive(TryFromPacket, attributes(packet))]
+pub fn try_from_packet_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    impl_try_from_packet(&ast)
+}
+
+fn impl_try_from_packet(ast: &DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let deserialize_calls = match deserialize_calls(ast) {
+        Ok(deserialize_calls) => deserialize_calls,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let gen = quote! {
+        impl TryFrom<packets::Packet> for #name {
+            type Error = packets::PacketError;
+
+            fn try_from(mut packet: packets::Packet) -> Result<Self, Self::Error> {
+                fn deserialize(packet: &mut packets::Packet) -> Result<#name, packets::PacketError> {
+                    #deserialize_calls
                 }
-            };
 
-            gen.into()
+                // The type's name is the root of the path in errors
+                deserialize(&mut packet).map_err(|err| err.in_field(stringify!(#name)))
+            }
         }
-    }
-}
+    };
 
-#[proc_macro_derive(IntoPacket, attributes(PacketType))]
-pub fn into_packet_derive(input: TokenStream) -> TokenStream {
-    let ast = parse_macro_input!(input as DeriveInput);
-    impl_into_packet(&ast)
+    gen.into()
 }
 
-fn impl_into_packet(ast: &DeriveInput) -> TokenStream {
-    let packet_type = ast.attrs.iter()
-        .find(|&attr| attr.path().is_ident("PacketType"))
-        .expect("PacketType attribute not found")
-        .parse_args::<Variant>()
-        .unwrap();
+// Generates statements that serialize `value` (a reference to the derived type) into a `&mut Packet` named `packet`
+fn serialize_calls(ast: &DeriveInput, value: TokenStream2) -> syn::Result<TokenStream2> {
+    let mut serialize_calls = quote!();
 
-    match &ast.data {
-        Data::Struct(data) => impl_into_packet_struct(&ast.ident, &data, packet_type),
-        _ => unimplemented!(),
+    // Types with versioned fields are prefixed with the newest version they know about
+    if let Some(version) = schema_version(ast)? {
+        serialize_calls.extend(quote!(
+            packets::PacketSerialize::serialize(&#version, packet);
+        ));
     }
-}
 
-fn impl_into_packet_struct(name: &Ident, data: &DataStruct, packet_type: Variant) -> TokenStream {
-    match &data.fields {
-        Fields::Named(fields) => {
-            let mut serialize_calls = quote!();
-            
-            for field in fields.named.iter() {
-                let field_name = field.ident.as_ref().unwrap();
-                serialize_calls.extend(quote!(
-                    packets::PacketSerialize::serialize(&value.#field_name, &mut packet);
-                ));
+    match &ast.data {
+        Data::Struct(data) => {
+            for (i, field) in data.fields.iter().enumerate() {
+                let member = match &field.ident {
+                    Some(field_name) => quote!(#field_name),
+                    None => {
+                        let index = syn::Index::from(i);
+                        quote!(#index)
+                    }
+                };
+
+                serialize_calls.extend(serialize_field(field, quote!(&#value.#member))?);
             }
 
-            let gen = quote!(
-                impl From<&#name> for packets::Packet {
-                    fn from(value: &#name) -> Self {
-                        let mut packet = packets::Packet::new(packets::PacketType::#packet_type);
+            if serialize_calls.is_empty() {
+                serialize_calls.extend(quote!(let _ = packet;));
+            }
 
-                        #serialize_calls
+            Ok(serialize_calls)
+        },
+        Data::Enum(data) => {
+            let name = &ast.ident;
+            check_variant_count(name, data)?;
+
+            let mut match_arms = quote!();
+
+            for (tag, variant) in data.variants.iter().enumerate() {
+                let variant_name = &variant.ident;
+                let tag = tag as u8;
+                let bindings = field_bindings(&variant.fields);
+                let mut patterns = Vec::new();
+                let mut serialize_variant = quote!();
+
+                for (field, binding) in variant.fields.iter().zip(bindings.iter()) {
+                    // Skipped fields are never read, so they aren't bound
+                    let skip = field_options(field)?.skip;
+
+                    patterns.push(match (&field.ident, skip) {
+                        (Some(field_name), true) => quote!(#field_name: _),
+                        (None, true) => quote!(_),
+                        (_, false) => quote!(#binding),
+                    });
+
+                    serialize_variant.extend(serialize_field(field, quote!(#binding))?);
+                }
 
-                        packet
-                    }
+                let pattern = match &variant.fields {
+                    Fields::Named(_) => quote!(#name::#variant_name { #(#patterns),* }),
+                    Fields::Unnamed(_) => quote!(#name::#variant_name(#(#patterns),*)),
+                    Fields::Unit => quote!(#name::#variant_name),
+                };
+
+                match_arms.extend(quote!(
+                    #pattern => {
+                        packets::PacketSerialize::serialize(&#tag, packet);
+                        #serialize_variant
+                    },
+                ));
+            }
+
+            serialize_calls.extend(quote!(
+                match #value {
+                    #match_arms
                 }
-            );
+            ));
 
-            gen.into()
+            Ok(serialize_calls)
         },
-        Fields::Unnamed(fields) => {
-            let mut serialize_calls = quote!();
+        Data::Union(_) => Err(syn::Error::new_spanned(ast, "Unions cannot be sent in packets")),
+    }
+}
 
-            for i in 0..fields.unnamed.len() {
-                let index = syn::Index::from(i);
-                serialize_calls.extend(quote!(
-                    packets::PacketSerialize::serialize(&value.#index, &mut packet);
-                ));
+// Generates an expression that deserializes the derived type from a `&mut Packet` named `packet`
+fn deserialize_calls(ast: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &ast.ident;
+
+    let read_version = match schema_version(ast)? {
+        Some(_) => quote!(
+            let __packet_version = <u8 as packets::PacketDeserialize>::deserialize(packet)?;
+        ),
+        None => quote!(),
+    };
+
+    match &ast.data {
+        Data::Struct(data) => {
+            let deserialize_struct = deserialize_fields(quote!(#name), None, &data.fields)?;
+
+            let reads_packet = !read_version.is_empty() || data.fields.iter()
+                .map(field_options)
+                .collect::<syn::Result<Vec<_>>>()?
+                .iter()
+                .any(|options| !options.skip);
+
+            if reads_packet {
+                Ok(quote!(
+                    #read_version
+                    #deserialize_struct
+                ))
+            } else {
+                Ok(quote!(
+                    let _ = packet;
+                    #deserialize_struct
+                ))
             }
+        },
+        Data::Enum(data) => {
+            check_variant_count(name, data)?;
 
-            let gen = quote!(
-                impl From<&#name> for packets::Packet {
-                    fn from(value: &#name) -> Self {
-                        let mut packet = packets::Packet::new(packets::PacketType::#packet_type);
+            let mut match_arms = quote!();
 
-                        #serialize_calls
+            for (tag, variant) in data.variants.iter().enumerate() {
+                let variant_name = &variant.ident;
+                let tag = tag as u8;
+                let deserialize_variant = deserialize_fields(quote!(#name::#variant_name), Some(variant_name), &variant.fields)?;
 
-                        packet
-                    }
-                }
-            );
+                match_arms.extend(quote!(
+                    #tag => { #deserialize_variant },
+                ));
+            }
+
+            Ok(quote!(
+                #read_version
+                let tag = <u8 as packets::PacketDeserialize>::deserialize(packet)?;
 
-            gen.into()
+                match tag {
+                    #match_arms
+                    _ => Err(packets::PacketError::InvalidPacketError(packet.last_read_location())),
+                }
+            ))
         },
-        Fields::Unit => unimplemented!(),
+        Data::Union(_) => Err(syn::Error::new_spanned(ast, "Unions cannot be received in packets")),
     }
 }
 
-#[proc_macro_derive(TryFromPacket)]
-pub fn try_from_packet_derive(input: TokenStream) -> TokenStream {
-    let ast = parse_macro_input!(input as DeriveInput);
-    impl_try_from_packet(&ast)
+fn deserialize_fields(constructor: TokenStream2, variant: Option<&Ident>, fields: &Fields) -> syn::Result<TokenStream2> {
+    let bindings = field_bindings(fields);
+    let mut deserialize_calls = quote!();
+
+    for (i, (field, binding)) in fields.iter().zip(bindings.iter()).enumerate() {
+        // Errors are tagged with the field's name or index, then the variant's name
+        let field_name = match &field.ident {
+            Some(field_name) => field_name.to_string(),
+            None => i.to_string(),
+        };
+        let path = match variant {
+            Some(variant_name) => quote!(err.in_field(#field_name).in_field(stringify!(#variant_name))),
+            None => quote!(err.in_field(#field_name)),
+        };
+
+        let deserialize_call = deserialize_field(field, path)?;
+        deserialize_calls.extend(quote!(
+            let #binding = #deserialize_call;
+        ));
+    }
+
+    let deserialize_fields = match fields {
+        Fields::Named(_) => quote!(
+            #deserialize_calls
+
+            Ok(#constructor { #(#bindings),* })
+        ),
+        Fields::Unnamed(_) => quote!(
+            #deserialize_calls
+
+            Ok(#constructor(#(#bindings),*))
+        ),
+        Fields::Unit => quote!(Ok(#constructor)),
+    };
+
+    Ok(deserialize_fields)
 }
 
-fn impl_try_from_packet(ast: &DeriveInput) -> TokenStream {
-    match &ast.data {
-        Data::Struct(data) => impl_try_from_packet_struct(&ast.ident, &data),
-        _ => unimplemented!(),
+// Named fields are bound to their own names, unnamed fields to field_0, field_1, ...
+fn field_bindings(fields: &Fields) -> Vec<Ident> {
+    fields.iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(field_name) => field_name.clone(),
+            None => Ident::new(&format!("field_{}", i), Span::call_site()),
+        })
+        .collect()
+}
+
+fn check_variant_count(name: &Ident, data: &DataEnum) -> syn::Result<()> {
+    // The discriminant tag is written as a single byte
+    if data.variants.len() > u8::MAX as usize + 1 {
+        Err(syn::Error::new_spanned(name, "Enums sent in packets can have at most 256 variants"))
+    } else {
+        Ok(())
     }
 }
 
-fn impl_try_from_packet_struct(name: &Ident, data: &DataStruct) -> TokenStream {
-    match &data.fields {
-        Fields::Named(fields) => {
-            let mut deserialize_calls = quote!();
-            
-            for field in fields.named.iter() {
-                let field_name = field.ident.as_ref().unwrap();
-                let field_type = &field.ty;
-                deserialize_calls.extend(quote!(
-                    let #field_name = <#field_type as packets::PacketDeserialize>::deserialize(&mut packet)?;
-                ));
+// The newest #[packet(since = N)] of any field, or None if no fields are versioned
+fn schema_version(ast: &DeriveInput) -> syn::Result<Option<u8>> {
+    let fields: Vec<&Field> = match &ast.data {
+        Data::Struct(data) => data.fields.iter().collect(),
+        Data::Enum(data) => data.variants.iter().flat_map(|variant| variant.fields.iter()).collect(),
+        Data::Union(_) => Vec::new(),
+    };
+
+    let mut version = None;
+
+    for field in fields {
+        version = version.max(field_options(field)?.since);
+    }
+
+    Ok(version)
+}
+
+// Generates a const expression fingerprinting the wire layout of the derived type
+// The layout of every sent field's type is folded in, so changes to nested types are picked up too
+fn schema_fingerprint(ast: &DeriveInput, packet_type: Option<&Variant>) -> syn::Result<TokenStream2> {
+    let mut layout = String::new();
+    let mut field_fingerprints = Vec::new();
+
+    let mut describe_fields = |layout: &mut String, fields: &Fields| -> syn::Result<()> {
+        for (field, binding) in fields.iter().zip(field_bindings(fields)) {
+            let options = field_options(field)?;
+
+            if options.skip {
+                continue;
             }
 
-            let field_exprs = fields.named.iter().map(|field| {
-                let field_name = field.ident.as_ref().unwrap();
-                quote!(#field_name)
-            });
+            let field_type = &field.ty;
+            layout.push_str(&format!("{}: {}", binding, quote!(#field_type)));
+
+            // The codec's FINGERPRINT describes its encoding, so changing it changes the protocol fingerprint
+            if options.varint {
+                layout.push_str(" varint");
+                field_fingerprints.push(quote!(<#field_type as packets::varint::VarIntSchema>::FINGERPRINT));
+            } else if let Some(with) = &options.with {
+                layout.push_str(&format!(" with {}", quote!(#with)));
+                field_fingerprints.push(quote!(#with::FINGERPRINT));
+            } else {
+                field_fingerprints.push(quote!(<#field_type as packets::PacketSchema>::FINGERPRINT));
+            }
 
-            let gen = quote!(
-                impl TryFrom<packets::Packet> for #name {
-                    type Error = packets::PacketError;
+            if let Some(version) = options.since {
+                layout.push_str(&format!(" since {}", version));
+            }
 
-                    fn try_from(mut packet: packets::Packet) -> Result<Self, Self::Error> {
-                        #deserialize_calls
+            layout.push_str(", ");
+        }
 
-                        Ok(#name { #(#field_exprs),* })
-                    }
-                }
-            );
+        Ok(())
+    };
 
-            gen.into()
+    match &ast.data {
+        Data::Struct(data) => {
+            layout.push_str("struct { ");
+            describe_fields(&mut layout, &data.fields)?;
+            layout.push('}');
         },
-        Fields::Unnamed(fields) => {
-            let mut deserialize_calls = quote!();
-
-            let field_names: Vec<Ident> = fields.unnamed.iter()
-                .enumerate()
-                .map(|(i, _)| Ident::new(&format!("field_{}", i), Span::call_site().into()))
-                .collect();
-
-            for (i, field) in fields.unnamed.iter().enumerate() {
-                let field_name = &field_names[i];
-                let field_type = &field.ty;
-                
-                deserialize_calls.extend(quote!(
-                    let #field_name = <#field_type as packets::PacketDeserialize>::deserialize(&mut packet)?;
-                ));
+        Data::Enum(data) => {
+            layout.push_str("enum { ");
+
+            for variant in data.variants.iter() {
+                layout.push_str(&format!("{} {{ ", variant.ident));
+                describe_fields(&mut layout, &variant.fields)?;
+                layout.push_str("}, ");
             }
 
-            let field_names_iter = field_names.iter();
+            layout.push('}');
+        },
+        Data::Union(_) => return Err(syn::Error::new_spanned(ast, "Unions cannot be sent in packets")),
+    }
 
-            let gen = quote! {
-                impl TryFrom<packets::Packet> for #name {
-                    type Error = packets::PacketError;
+    let packet_type = match packet_type {
+        Some(packet_type) => quote!(packets::PacketType::#packet_type as u64,),
+        None => quote!(),
+    };
+
+    Ok(quote!(
+        packets::schema::combine(&[
+            packets::schema::fingerprint(#layout),
+            #packet_type
+            #(#field_fingerprints,)*
+        ])
+    ))
+}
 
-                    fn try_from(mut packet: packets::Packet) -> Result<Self, packets::PacketError> {
-                        #deserialize_calls
+#[derive(Default)]
+struct FieldOptions {
+    // The field isn't sent and is filled from its default instead
+    skip: bool,
+    // Shorthand for `with = packets::varint`
+    varint: bool,
+    // Expression used for skipped fields and versioned fields missing from older packets
+    default: Option<TokenStream2>,
+    // Module providing `serialize` and `deserialize` functions and a `FINGERPRINT` const for the field
+    with: Option<Path>,
+    // The version of the type the field was added in
+    since: Option<u8>,
+    // Overrides the global limit on the first length read for the field
+    max_len: Option<usize>,
+}
 
-                        Ok(#name(#(#field_names_iter),*))
-                    }
+// Parses the #[packet(...)] attributes on a field
+fn field_options(field: &Field) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("packet")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("varint") {
+                options.varint = true;
+                options.with = Some(syn::parse_quote!(packets::varint));
+            } else if meta.path.is_ident("skip") {
+                options.skip = true;
+            } else if meta.path.is_ident("default") {
+                if meta.input.peek(Token![=]) {
+                    let default_fn: Path = meta.value()?.parse()?;
+                    options.default = Some(quote!(#default_fn()));
+                } else {
+                    options.default = Some(quote!(Default::default()));
+                }
+            } else if meta.path.is_ident("with") {
+                options.with = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("since") {
+                let version: LitInt = meta.value()?.parse()?;
+                let version = version.base10_parse::<u8>()?;
+
+                if version == 0 {
+                    return Err(meta.error("Field versions start at 1"));
                 }
-            };
 
-            gen.into()
-        },
-        Fields::Unit => unimplemented!(),
+                options.since = Some(version);
+            } else if meta.path.is_ident("max_len") {
+                let max_len: LitInt = meta.value()?.parse()?;
+                options.max_len = Some(max_len.base10_parse()?);
+            } else {
+                return Err(meta.error("Unknown packet attribute"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    if options.skip && (options.with.is_some() || options.since.is_some() || options.max_len.is_some()) {
+        return Err(syn::Error::new_spanned(field, "Skipped fields cannot also use with, varint, since or max_len"));
+    }
+
+    if options.default.is_some() && !options.skip && options.since.is_none() {
+        return Err(syn::Error::new_spanned(field, "default only applies to skipped or versioned fields"));
+    }
+
+    Ok(options)
+}
+
+// Generates statements that serialize the field behind the reference `value`
+fn serialize_field(field: &Field, value: TokenStream2) -> syn::Result<TokenStream2> {
+    let options = field_options(field)?;
+
+    if options.skip {
+        return Ok(quote!());
+    }
+
+    match options.with {
+        Some(with) => Ok(quote!(#with::serialize(#value, packet);)),
+        None => Ok(quote!(packets::PacketSerialize::serialize(#value, packet);)),
+    }
+}
+
+// Generates an expression that deserializes the field, passing errors through `path` (an expression of `err`)
+fn deserialize_field(field: &Field, path: TokenStream2) -> syn::Result<TokenStream2> {
+    let options = field_options(field)?;
+    let field_type = &field.ty;
+    let default = options.default.unwrap_or_else(|| quote!(Default::default()));
+
+    if options.skip {
+        return Ok(default);
+    }
+
+    let read = match options.with {
+        Some(with) => quote!(#with::deserialize(packet)),
+        None => quote!(<#field_type as packets::PacketDeserialize>::deserialize(packet)),
+    };
+
+    let read = match options.max_len {
+        Some(max_len) => quote!({
+            packet.limit_next_length(#max_len);
+            let result = #read;
+            packet.clear_length_limit();
+            result
+        }),
+        None => read,
+    };
+
+    let read = quote!(#read.map_err(|err| #path)?);
+
+    match options.since {
+        // Packets written before the field was added don't contain it
+        Some(version) => Ok(quote!(
+            if __packet_version >= #version { #read } else { #default }
+        )),
+        None => Ok(read),
     }
 }
\ No newline at end of file
@@ -0,0 +1,165 @@
+use packets::{Packet, PacketSerialize, PacketDeserialize, PacketError, PacketType};
+use packets_derive::{PacketSerialize, PacketDeserialize};
+
+// Sends an f32 in [0, 1] as a single byte
+mod unit_float {
+    use packets::{Packet, PacketSerialize, PacketDeserialize, PacketError};
+    use packets::schema::fingerprint;
+
+    pub const FINGERPRINT: u64 = fingerprint("u8 of value * 255");
+
+    pub fn serialize(value: &f32, packet: &mut Packet) {
+        ((value.clamp(0.0, 1.0) * 255.0).round() as u8).serialize(packet);
+    }
+
+    pub fn deserialize(packet: &mut Packet) -> Result<f32, PacketError> {
+        Ok(u8::deserialize(packet)? as f32 / 255.0)
+    }
+}
+
+fn unknown_name() -> String {
+    "Unknown".to_string()
+}
+
+#[derive(Debug, PartialEq, PacketSerialize, PacketDeserialize)]
+struct Player {
+    id: u8,
+    #[packet(skip)]
+    cached_score: u32,
+    #[packet(skip, default = unknown_name)]
+    display_name: String,
+    #[packet(with = unit_float)]
+    health: f32,
+}
+
+#[derive(Debug, PartialEq, PacketSerialize, PacketDeserialize)]
+struct SettingsV1 {
+    volume: u8,
+    #[packet(since = 1)]
+    muted: bool,
+}
+
+#[derive(Debug, PartialEq, PacketSerialize, PacketDeserialize)]
+struct SettingsV2 {
+    volume: u8,
+    #[packet(since = 1)]
+    muted: bool,
+    #[packet(since = 2, default = unknown_name)]
+    language: String,
+}
+
+#[derive(Debug, PartialEq, PacketSerialize, PacketDeserialize)]
+struct Chat {
+    #[packet(max_len = 8)]
+    name: String,
+    #[packet(varint, max_len = 2)]
+    recipients: Vec<u32>,
+}
+
+#[derive(Debug, PartialEq, PacketSerialize, PacketDeserialize)]
+struct Profile {
+    #[packet(max_len = 2)]
+    nickname: Option<String>,
+    friends: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, PacketSerialize, PacketDeserialize)]
+enum Action {
+    Wave(#[packet(skip)] u8),
+    Say { #[packet(skip)] loud: bool, text: String },
+}
+
+#[test]
+fn skipped_fields_use_default() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    let x = Player { id: 3, cached_score: 1000, display_name: "Bob".to_string(), health: 1.0 };
+    x.serialize(&mut packet);
+
+    // 1 byte packet type, 1 byte id, 1 byte health
+    assert_eq!(Box::<[u8]>::from(&packet).len(), 3);
+
+    let y = Player::deserialize(&mut packet).unwrap();
+
+    assert_eq!(y, Player { id: 3, cached_score: 0, display_name: unknown_name(), health: 1.0 });
+}
+
+#[test]
+fn with_module_serialize_deserialize() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    let x = Player { id: 3, cached_score: 0, display_name: unknown_name(), health: 0.5 };
+    x.serialize(&mut packet);
+
+    let y = Player::deserialize(&mut packet).unwrap();
+
+    assert!((x.health - y.health).abs() < 1.0 / 255.0);
+}
+
+#[test]
+fn skipped_enum_fields_use_default() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    Action::Wave(5).serialize(&mut packet);
+    Action::Say { loud: true, text: "Hi".to_string() }.serialize(&mut packet);
+
+    assert_eq!(Action::deserialize(&mut packet).unwrap(), Action::Wave(0));
+    assert_eq!(Action::deserialize(&mut packet).unwrap(), Action::Say { loud: false, text: "Hi".to_string() });
+}
+
+#[test]
+fn versioned_fields_serialize_deserialize() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    let x = SettingsV2 { volume: 7, muted: true, language: "en".to_string() };
+    x.serialize(&mut packet);
+
+    let y = SettingsV2::deserialize(&mut packet).unwrap();
+
+    assert_eq!(x, y);
+}
+
+#[test]
+fn older_version_uses_default() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    SettingsV1 { volume: 7, muted: true }.serialize(&mut packet);
+
+    let y = SettingsV2::deserialize(&mut packet).unwrap();
+
+    assert_eq!(y, SettingsV2 { volume: 7, muted: true, language: unknown_name() });
+}
+
+#[test]
+fn field_within_max_len_deserializes() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    let x = Chat { name: "Pilot".to_string(), recipients: vec![1, 2] };
+    x.serialize(&mut packet);
+
+    let y = Chat::deserialize(&mut packet).unwrap();
+
+    assert_eq!(x, y);
+}
+
+#[test]
+fn field_over_max_len_is_rejected() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    Chat { name: "Pilot".to_string(), recipients: vec![1, 2, 3] }.serialize(&mut packet);
+
+    assert!(matches!(Chat::deserialize(&mut packet), Err(PacketError::LimitExceeded { len: 3, limit: 2, .. })));
+}
+
+#[test]
+fn unused_max_len_does_not_limit_next_field() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    // The nickname reads no length, so its limit must not apply to the friends
+    let x = Profile { nickname: None, friends: vec![1, 2, 3, 4] };
+    x.serialize(&mut packet);
+
+    let y = Profile::deserialize(&mut packet).unwrap();
+
+    assert_eq!(x, y);
+}
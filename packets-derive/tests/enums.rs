@@ -0,0 +1,81 @@
+use packets::{Packet, PacketSerialize, PacketDeserialize, PacketError, PacketType};
+use packets_derive::{PacketSerialize, PacketDeserialize, IntoPacket, TryFromPacket};
+
+#[derive(Debug, PartialEq, PacketSerialize, PacketDeserialize)]
+enum Message {
+    Ping,
+    Move(u8, f32),
+    Rename { old: String, new: String },
+}
+
+#[derive(Debug, PartialEq, IntoPacket, TryFromPacket)]
+#[PacketType(PlayerConnected)]
+enum Command {
+    Stop,
+    Go(Message),
+}
+
+#[test]
+fn unit_variant_serialize_deserialize() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    let x = Message::Ping;
+    x.serialize(&mut packet);
+
+    let y = Message::deserialize(&mut packet).unwrap();
+
+    assert_eq!(x, y);
+}
+
+#[test]
+fn tuple_variant_serialize_deserialize() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    let x = Message::Move(3, -1.5);
+    x.serialize(&mut packet);
+
+    let y = Message::deserialize(&mut packet).unwrap();
+
+    assert_eq!(x, y);
+}
+
+#[test]
+fn struct_variant_serialize_deserialize() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    let x = Message::Rename { old: "Player".to_string(), new: "Pilot".to_string() };
+    x.serialize(&mut packet);
+
+    let y = Message::deserialize(&mut packet).unwrap();
+
+    assert_eq!(x, y);
+}
+
+#[test]
+fn variant_tag_is_variant_index() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    Message::Move(7, 0.0).serialize(&mut packet);
+
+    assert_eq!(u8::deserialize(&mut packet).unwrap(), 1);
+    assert_eq!(u8::deserialize(&mut packet).unwrap(), 7);
+}
+
+#[test]
+fn unknown_tag_is_invalid() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    3u8.serialize(&mut packet);
+
+    assert!(matches!(Message::deserialize(&mut packet), Err(PacketError::InvalidPacketError(_))));
+}
+
+#[test]
+fn enum_into_packet_try_from_packet() {
+    let x = Command::Go(Message::Rename { old: "a".to_string(), new: "b".to_string() });
+
+    let data: Box<[u8]> = (&Packet::from(&x)).into();
+    let y = Command::try_from(Packet::try_from(data).unwrap()).unwrap();
+
+    assert_eq!(x, y);
+}
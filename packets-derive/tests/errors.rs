@@ -0,0 +1,63 @@
+use packets::{Packet, PacketSerialize, PacketDeserialize, PacketError, PacketType, PathSegment};
+use packets_derive::{PacketSerialize, PacketDeserialize, IntoPacket, TryFromPacket};
+
+#[derive(Debug, PartialEq, PacketSerialize, PacketDeserialize)]
+enum Shape {
+    Point,
+    Line { length: u16, visible: bool },
+}
+
+#[derive(Debug, PartialEq, IntoPacket, TryFromPacket)]
+#[PacketType(PlayerConnected)]
+struct Drawing {
+    id: u8,
+    shapes: Vec<(u8, Shape)>,
+}
+
+fn corrupt(drawing: &Drawing, offset: usize, value: u8) -> Packet {
+    let mut data: Box<[u8]> = (&Packet::from(drawing)).into();
+    // The first byte is the packet type
+    data[offset + 1] = value;
+
+    Packet::try_from(data).unwrap()
+}
+
+#[test]
+fn error_has_field_path() {
+    let drawing = Drawing {
+        id: 1,
+        shapes: vec![(0, Shape::Point), (1, Shape::Line { length: 5, visible: true })],
+    };
+
+    // Skips the id, the vec's length, the first shape, then the second shape's number, tag and length
+    let packet = corrupt(&drawing, 1 + 8 + 2 + 1 + 1 + 2, 7);
+    let err = Drawing::try_from(packet).unwrap_err();
+
+    let location = err.location().unwrap();
+    assert_eq!(location.packet_type, PacketType::PlayerConnected);
+    assert_eq!(location.offset, 15);
+    assert_eq!(
+        location.path().copied().collect::<Vec<_>>(),
+        vec![
+            PathSegment::Field("Drawing"),
+            PathSegment::Field("shapes"),
+            PathSegment::Index(1),
+            PathSegment::Field("1"),
+            PathSegment::Field("Line"),
+            PathSegment::Field("visible"),
+        ]
+    );
+    assert_eq!(err.to_string(), "The value of the data at byte 15 of PlayerConnected packet in Drawing.shapes[1].1.Line.visible is invalid!");
+}
+
+#[test]
+fn unknown_variant_has_field_path() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    (3u8, 9u8).serialize(&mut packet);
+
+    let err = <(u8, Shape)>::deserialize(&mut packet).unwrap_err();
+
+    assert!(matches!(err, PacketError::InvalidPacketError(_)));
+    assert_eq!(err.location().unwrap().path().copied().collect::<Vec<_>>(), vec![PathSegment::Field("1")]);
+}
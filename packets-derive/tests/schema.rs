@@ -0,0 +1,123 @@
+use packets::PacketSchema;
+use packets_derive::{PacketSerialize, IntoPacket};
+
+mod v1 {
+    use packets_derive::{PacketSerialize, IntoPacket};
+
+    #[derive(PacketSerialize)]
+    pub struct Inner {
+        pub id: u32,
+    }
+
+    #[derive(IntoPacket)]
+    #[PacketType(PlayerConnected)]
+    pub struct Message {
+        pub inner: Inner,
+        pub name: String,
+    }
+
+    pub mod codec {
+        use packets::{Packet, PacketSerialize};
+        use packets::schema::fingerprint;
+
+        pub const FINGERPRINT: u64 = fingerprint("u32");
+
+        pub fn serialize(value: &u32, packet: &mut Packet) {
+            value.serialize(packet);
+        }
+    }
+
+    #[derive(PacketSerialize)]
+    pub struct Coded {
+        #[packet(with = codec)]
+        pub value: u32,
+    }
+}
+
+mod v2 {
+    use packets_derive::{PacketSerialize, IntoPacket};
+
+    #[derive(PacketSerialize)]
+    pub struct Inner {
+        pub id: u16,
+    }
+
+    #[derive(IntoPacket)]
+    #[PacketType(PlayerConnected)]
+    pub struct Message {
+        pub inner: Inner,
+        pub name: String,
+    }
+
+    pub mod codec {
+        use packets::{Packet, PacketSerialize};
+        use packets::schema::fingerprint;
+
+        pub const FINGERPRINT: u64 = fingerprint("u16");
+
+        pub fn serialize(value: &u32, packet: &mut Packet) {
+            (*value as u16).serialize(packet);
+        }
+    }
+
+    #[derive(PacketSerialize)]
+    pub struct Coded {
+        #[packet(with = codec)]
+        pub value: u32,
+    }
+}
+
+#[derive(PacketSerialize)]
+struct Fixed {
+    id: u32,
+}
+
+#[derive(PacketSerialize)]
+struct Varint {
+    #[packet(varint)]
+    id: u32,
+}
+
+#[derive(PacketSerialize)]
+struct Skipped {
+    id: u32,
+    #[packet(skip)]
+    _cache: Vec<u8>,
+}
+
+#[derive(IntoPacket)]
+#[PacketType(PlayerConnected)]
+struct Connected {
+    id: u32,
+}
+
+#[derive(IntoPacket)]
+#[PacketType(PlayerDisconnected)]
+struct Disconnected {
+    id: u32,
+}
+
+#[test]
+fn nested_layout_change_changes_fingerprint() {
+    assert_ne!(v1::Message::FINGERPRINT, v2::Message::FINGERPRINT);
+}
+
+#[test]
+fn codec_change_changes_fingerprint() {
+    assert_ne!(v1::Coded::FINGERPRINT, v2::Coded::FINGERPRINT);
+}
+
+#[test]
+fn encoding_change_changes_fingerprint() {
+    assert_ne!(Fixed::FINGERPRINT, Varint::FINGERPRINT);
+}
+
+#[test]
+fn skipped_fields_dont_change_fingerprint() {
+    assert_eq!(Fixed::FINGERPRINT, Skipped::FINGERPRINT);
+}
+
+#[test]
+fn packet_type_changes_fingerprint() {
+    assert_ne!(Connected::FINGERPRINT, Disconnected::FINGERPRINT);
+}
@@ -0,0 +1,45 @@
+use packets::{Packet, PacketSerialize, PacketDeserialize, PacketType};
+use packets_derive::{PacketSerialize, PacketDeserialize};
+
+#[derive(Debug, PartialEq, PacketSerialize, PacketDeserialize)]
+struct Entity {
+    #[packet(varint)]
+    id: u32,
+    #[packet(varint)]
+    name: String,
+    health: u16,
+}
+
+#[derive(Debug, PartialEq, PacketSerialize, PacketDeserialize)]
+enum Event {
+    Moved(#[packet(varint)] i32, #[packet(varint)] i32),
+    Spawned { #[packet(varint)] ids: Vec<u8> },
+}
+
+#[test]
+fn varint_struct_serialize_deserialize() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    let x = Entity { id: 5, name: "Bob".to_string(), health: 100 };
+    x.serialize(&mut packet);
+
+    // 1 byte packet type, 1 byte id, 1 byte length + 3 bytes name, 2 byte health
+    assert_eq!(Box::<[u8]>::from(&packet).len(), 8);
+
+    let y = Entity::deserialize(&mut packet).unwrap();
+
+    assert_eq!(x, y);
+}
+
+#[test]
+fn varint_enum_serialize_deserialize() {
+    let mut packet = Packet::new(PacketType::PlayerConnected);
+
+    let moved = Event::Moved(-3, 70000);
+    let spawned = Event::Spawned { ids: vec![1, 2, 3] };
+    moved.serialize(&mut packet);
+    spawned.serialize(&mut packet);
+
+    assert_eq!(Event::deserialize(&mut packet).unwrap(), moved);
+    assert_eq!(Event::deserialize(&mut packet).unwrap(), spawned);
+}
@@ -0,0 +1,186 @@
+use super::{Packet, PacketError};
+
+// The first byte of a batched datagram, which no packet type uses
+pub const BATCH_TAG: u8 = u8::MAX;
+
+// Batches are kept under a typical MTU, larger packets are sent in a batch of their own
+pub const MAX_BATCH_SIZE: usize = 1200;
+
+// Each packet in a batch is prefixed with its length
+const FRAME_HEADER_SIZE: usize = 4;
+
+// Packs packets into as few datagrams as possible, keeping them in the order they were pushed
+#[derive(Debug, Default)]
+pub struct PacketBatcher {
+    batches: Vec<Vec<u8>>,
+}
+
+impl PacketBatcher {
+    pub fn push(&mut self, packet: &Packet) {
+        let data: Box<[u8]> = packet.into();
+        let frame_size = FRAME_HEADER_SIZE + data.len();
+
+        let batch = match self.batches.last_mut() {
+            Some(batch) if batch.len() + frame_size <= MAX_BATCH_SIZE => batch,
+            _ => {
+                self.batches.push(vec![BATCH_TAG]);
+                self.batches.last_mut().unwrap()
+            }
+        };
+
+        batch.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        batch.extend_from_slice(&data);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    // The batched datagrams, in the order they should be sent
+    pub fn drain(&mut self) -> impl Iterator<Item = Box<[u8]>> + '_ {
+        self.batches.drain(..).map(Vec::into_boxed_slice)
+    }
+}
+
+// Splits a received datagram back into packets
+// Datagrams that aren't batches, like the handshake, are a single packet
+pub struct Unbatcher {
+    data: Box<[u8]>,
+    index: usize,
+    batched: bool,
+}
+
+impl Unbatcher {
+    pub fn new(data: Box<[u8]>) -> Self {
+        let batched = data.first() == Some(&BATCH_TAG);
+
+        Self { data, index: usize::from(batched), batched }
+    }
+}
+
+impl Iterator for Unbatcher {
+    type Item = Result<Packet, PacketError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.batched {
+            // Afterwards the data is empty, so the iterator ends
+            self.batched = true;
+            return Some(Packet::try_from(std::mem::take(&mut self.data)));
+        }
+
+        if self.index >= self.data.len() {
+            return None;
+        }
+
+        let frame = self.data.get(self.index..self.index + FRAME_HEADER_SIZE)
+            .map(|header| u32::from_le_bytes(header.try_into().unwrap()) as usize)
+            .and_then(|len| {
+                let start = self.index + FRAME_HEADER_SIZE;
+                self.data.get(start..start.checked_add(len)?)
+            });
+
+        match frame {
+            Some(frame) => {
+                self.index += FRAME_HEADER_SIZE + frame.len();
+                Some(Packet::try_from(Box::from(frame)))
+            },
+            None => {
+                // The rest of the batch can't be framed, so it is dropped
+                self.index = self.data.len();
+                Some(Err(PacketError::TruncatedBatch))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Packet, PacketSerialize, PacketDeserialize, PacketError, PacketType};
+    use crate::batch::{PacketBatcher, Unbatcher, MAX_BATCH_SIZE};
+
+    fn numbered_packet(number: u32) -> Packet {
+        let mut packet = Packet::new(PacketType::VoxelUpdate);
+        number.serialize(&mut packet);
+
+        packet
+    }
+
+    fn packet_number(packet: Result<Packet, PacketError>) -> u32 {
+        u32::deserialize(&mut packet.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn batch_keeps_packet_order() {
+        let mut batcher = PacketBatcher::default();
+
+        for number in 0..10 {
+            batcher.push(&numbered_packet(number));
+        }
+
+        let batches: Vec<_> = batcher.drain().collect();
+        assert_eq!(batches.len(), 1);
+
+        let numbers: Vec<_> = Unbatcher::new(batches[0].clone()).map(packet_number).collect();
+        assert_eq!(numbers, (0..10).collect::<Vec<_>>());
+        assert!(batcher.is_empty());
+    }
+
+    #[test]
+    fn full_batches_are_split() {
+        let mut batcher = PacketBatcher::default();
+
+        for number in 0..1000 {
+            batcher.push(&numbered_packet(number));
+        }
+
+        let batches: Vec<_> = batcher.drain().collect();
+        assert!(batches.len() > 1);
+        assert!(batches.iter().all(|batch| batch.len() <= MAX_BATCH_SIZE));
+
+        let numbers: Vec<_> = batches.into_iter().flat_map(Unbatcher::new).map(packet_number).collect();
+        assert_eq!(numbers, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn oversized_packet_gets_own_batch() {
+        let mut batcher = PacketBatcher::default();
+        let mut large_packet = Packet::new(PacketType::InitialState);
+        vec![0u8; MAX_BATCH_SIZE * 2].serialize(&mut large_packet);
+
+        batcher.push(&numbered_packet(0));
+        batcher.push(&large_packet);
+        batcher.push(&numbered_packet(1));
+
+        let batches: Vec<_> = batcher.drain().collect();
+        assert_eq!(batches.len(), 3);
+
+        let packet_types: Vec<_> = batches.into_iter()
+            .flat_map(Unbatcher::new)
+            .map(|packet| packet.unwrap().packet_type())
+            .collect();
+        assert_eq!(packet_types, vec![PacketType::VoxelUpdate, PacketType::InitialState, PacketType::VoxelUpdate]);
+    }
+
+    #[test]
+    fn unbatched_datagram_is_single_packet() {
+        let data: Box<[u8]> = (&numbered_packet(7)).into();
+
+        let numbers: Vec<_> = Unbatcher::new(data).map(packet_number).collect();
+        assert_eq!(numbers, vec![7]);
+    }
+
+    #[test]
+    fn truncated_batch_is_error() {
+        let mut batcher = PacketBatcher::default();
+        batcher.push(&numbered_packet(0));
+        batcher.push(&numbered_packet(1));
+
+        let batch = batcher.drain().next().unwrap();
+        let truncated = Box::from(&batch[..batch.len() - 1]);
+
+        let packets: Vec<_> = Unbatcher::new(truncated).collect();
+        assert_eq!(packets.len(), 2);
+        assert!(packets[0].is_ok());
+        assert!(matches!(packets[1], Err(PacketError::TruncatedBatch)));
+    }
+}
\ No newline at end of file
@@ -1,6 +1,9 @@
 use bevy::prelude::{Transform, Vec3, Quat};
 
-use super::{Packet, PacketSerialize, PacketDeserialize, PacketError};
+use super::{Packet, PacketSerialize, PacketDeserialize, PacketSchema, PacketError};
+use super::schema::{fingerprint, combine};
+
+// Vectors and quaternions are written as fixed-size float arrays without a length prefix
 
 impl PacketSerialize for Vec3 {
     fn serialize(&self, packet: &mut Packet) {
@@ -10,8 +13,8 @@ impl PacketSerialize for Vec3 {
 
 impl PacketDeserialize for Vec3 {
     fn deserialize(packet: &mut Packet) -> Result<Self, PacketError> {
-        let vector: Vec<f32> = Vec::deserialize(packet)?;
-        Ok(Vec3::from_slice(&vector))
+        let vector: [f32; 3] = PacketDeserialize::deserialize(packet)?;
+        Ok(Vec3::from_array(vector))
     }
 }
 
@@ -23,8 +26,8 @@ impl PacketSerialize for Quat {
 
 impl PacketDeserialize for Quat {
     fn deserialize(packet: &mut Packet) -> Result<Self, PacketError> {
-        let quat: Vec<f32> = Vec::deserialize(packet)?;
-        Ok(Quat::from_slice(&quat))
+        let quat: [f32; 4] = PacketDeserialize::deserialize(packet)?;
+        Ok(Quat::from_array(quat))
     }
 }
 
@@ -38,20 +41,50 @@ impl PacketSerialize for Transform {
 
 impl PacketDeserialize for Transform {
     fn deserialize(packet: &mut Packet) -> Result<Self, PacketError> {
-        let translation = Vec3::deserialize(packet)?;
-        let rotation = Quat::deserialize(packet)?;
-        let scale = Vec3::deserialize(packet)?;
+        let translation = Vec3::deserialize(packet).map_err(|err| err.in_field("translation"))?;
+        let rotation = Quat::deserialize(packet).map_err(|err| err.in_field("rotation"))?;
+        let scale = Vec3::deserialize(packet).map_err(|err| err.in_field("scale"))?;
         Ok(Transform { translation, rotation, scale })
     }
 }
 
+impl PacketSchema for Vec3 {
+    const FINGERPRINT: u64 = combine(&[fingerprint("Vec3"), <[f32; 3]>::FINGERPRINT]);
+}
+
+impl PacketSchema for Quat {
+    const FINGERPRINT: u64 = combine(&[fingerprint("Quat"), <[f32; 4]>::FINGERPRINT]);
+}
+
+impl PacketSchema for Transform {
+    const FINGERPRINT: u64 = combine(&[fingerprint("Transform"), Vec3::FINGERPRINT, Quat::FINGERPRINT, Vec3::FINGERPRINT]);
+}
+
 #[cfg(test)]
 mod tests {
     use bevy::prelude::{Vec3, Quat, Transform};
 
-    use crate::{Packet, PacketSerialize, PacketDeserialize};
+    use crate::{Packet, PacketSerialize, PacketDeserialize, PacketError};
     use crate::PacketType::PlayerConnected;
 
+    #[test]
+    fn vec3_has_no_length_prefix() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        Vec3::new(1.0, -5.0, 0.0).serialize(&mut packet);
+
+        assert_eq!(packet.data.len(), 12);
+    }
+
+    #[test]
+    fn truncated_quat_is_bounds_error() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        [1.0f32, 0.0, 0.0].serialize(&mut packet);
+
+        assert!(matches!(Quat::deserialize(&mut packet), Err(PacketError::BoundsError(_))));
+    }
+
     #[test]
     fn vec3_serialize_deserialize() {
         let mut packet = Packet::new(PlayerConnected);
@@ -0,0 +1,56 @@
+// Hex dumps of packets, for logs and the packet inspector
+
+pub fn encode(data: &[u8]) -> String {
+    data.iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Whitespace, commas and 0x prefixes between bytes are ignored
+pub fn decode(hex: &str) -> Result<Vec<u8>, String> {
+    let digits: String = hex.split(|c: char| c.is_whitespace() || c == ',')
+        .map(|byte| byte.trim_start_matches("0x"))
+        .collect();
+
+    if !digits.is_ascii() {
+        return Err("non-hex characters in dump".to_string());
+    }
+
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!("odd number of hex digits ({})", digits.len()));
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            let byte = &digits[i..i + 2];
+            u8::from_str_radix(byte, 16).map_err(|_| format!("invalid hex byte '{}'", byte))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hex::{encode, decode};
+
+    #[test]
+    fn encode_decode() {
+        let data = vec![0, 1, 0xab, 0xff];
+
+        assert_eq!(encode(&data), "00 01 ab ff");
+        assert_eq!(decode(&encode(&data)).unwrap(), data);
+    }
+
+    #[test]
+    fn decode_ignores_separators() {
+        assert_eq!(decode("0x00, 0x1f,\n0xAB").unwrap(), vec![0x00, 0x1f, 0xab]);
+        assert_eq!(decode("001fab").unwrap(), vec![0x00, 0x1f, 0xab]);
+    }
+
+    #[test]
+    fn decode_rejects_invalid_digits() {
+        assert!(decode("0g").is_err());
+        assert!(decode("abc").is_err());
+    }
+}
\ No newline at end of file
@@ -1,9 +1,16 @@
+use std::fmt;
+
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use thiserror::Error;
 
+pub mod batch;
 pub mod bevy_impls;
+pub mod hex;
+pub mod limits;
 pub mod primitive_impls;
+pub mod schema;
 pub mod tuple_impls;
+pub mod varint;
 
 pub trait PacketSerialize {
     fn serialize(&self, packet: &mut Packet);
@@ -13,9 +20,24 @@ pub trait PacketDeserialize: Sized {
     fn deserialize(packet: &mut Packet) -> Result<Self, PacketError>;
 }
 
-#[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive)]
+// Implemented by types that are sent as a whole packet
+pub trait TypedPacket {
+    const PACKET_TYPE: PacketType;
+}
+
+// Describes the wire layout of a type, see the schema module
+pub trait PacketSchema {
+    const FINGERPRINT: u64;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum PacketType {
+    // The handshake packets are exchanged before the protocol fingerprint has been checked,
+    // so their values and layouts must never change
+    // 255 is reserved for batches, see the batch module
+    Handshake,
+    ConnectionRejected,
     PlacePart,
     DeletePart,
     InitialState,
@@ -24,28 +46,123 @@ pub enum PacketType {
     VoxelUpdate,
     SpawnMissile,
     ExplodeMissile,
+    ClientHello,
+    PlayerInput,
+    PlayerStates,
+    ConstructStates,
+    BuildRequestRejected,
+    GrantConstructRole,
+    RevokeConstructRole,
+    ConstructAccessChanged,
+    InitialStateChunk,
+    InitialStateLoaded,
+    FullVoxels,
+    DeleteVoxel,
 }
 
 #[derive(Debug, Clone)]
 pub struct Packet {
     data: Vec<u8>,
     index: usize,
-    packet_type: PacketType
+    packet_type: PacketType,
+    // Where the most recent read started, so invalid values can be pointed at
+    last_read: usize,
+    // Overrides the global limit for the next length read, set by #[packet(max_len = N)]
+    length_limit: Option<usize>
+}
+
+// One step of the path to the field that failed to deserialize
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathSegment {
+    Field(&'static str),
+    Index(usize),
+}
+
+// Where in a packet deserialization failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorLocation {
+    pub packet_type: PacketType,
+    pub offset: usize,
+    // Innermost segment first, since the path is built up while the error is returned
+    path: Vec<PathSegment>,
+}
+
+impl ErrorLocation {
+    pub fn path(&self) -> impl Iterator<Item = &PathSegment> {
+        self.path.iter().rev()
+    }
+}
+
+impl fmt::Display for ErrorLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at byte {} of {:?} packet", self.offset, self.packet_type)?;
+
+        if self.path.is_empty() {
+            return Ok(());
+        }
+
+        write!(f, " in ")?;
+        for (i, segment) in self.path().enumerate() {
+            match segment {
+                PathSegment::Field(name) if i == 0 => write!(f, "{}", name)?,
+                PathSegment::Field(name) => write!(f, ".{}", name)?,
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Error, Debug)]
 pub enum PacketError {
-    #[error("Attempted to read too many bytes from packet {0:?}!")]
-    BoundsError(Packet),
+    #[error("Attempted to read too many bytes {0}!")]
+    BoundsError(ErrorLocation),
 
-    #[error("The value of the data at index {} in packet {0:?} is invalid!", .0.index)]
-    InvalidPacketError(Packet),
+    #[error("The value of the data {0} is invalid!")]
+    InvalidPacketError(ErrorLocation),
+
+    #[error("A length of {len} exceeds the limit of {limit} {location}!")]
+    LimitExceeded { len: u64, limit: usize, location: ErrorLocation },
 
     #[error("The packet type {0} is invalid!")]
     InvalidTypeError(u8),
 
     #[error("Empty packet!")]
-    EmptyPacket
+    EmptyPacket,
+
+    #[error("A packet in the batch is truncated!")]
+    TruncatedBatch
+}
+
+impl PacketError {
+    pub fn location(&self) -> Option<&ErrorLocation> {
+        match self {
+            Self::BoundsError(location) | Self::InvalidPacketError(location) => Some(location),
+            Self::LimitExceeded { location, .. } => Some(location),
+            Self::InvalidTypeError(_) | Self::EmptyPacket | Self::TruncatedBatch => None,
+        }
+    }
+
+    // Called on the way out of a field, so the outermost field is added last
+    pub fn in_field(self, name: &'static str) -> Self {
+        self.with_segment(PathSegment::Field(name))
+    }
+
+    // Called on the way out of a collection element
+    pub fn at_index(self, index: usize) -> Self {
+        self.with_segment(PathSegment::Index(index))
+    }
+
+    fn with_segment(mut self, segment: PathSegment) -> Self {
+        match &mut self {
+            Self::BoundsError(location) | Self::InvalidPacketError(location) => location.path.push(segment),
+            Self::LimitExceeded { location, .. } => location.path.push(segment),
+            Self::InvalidTypeError(_) | Self::EmptyPacket | Self::TruncatedBatch => {}
+        }
+
+        self
+    }
 }
 
 impl Packet {
@@ -53,7 +170,9 @@ impl Packet {
         Packet {
             data: Vec::new(),
             index: 0,
-            packet_type
+            packet_type,
+            last_read: 0,
+            length_limit: None
         }
     }
 
@@ -61,14 +180,62 @@ impl Packet {
         self.packet_type
     }
 
+    // The location of the next byte to be read, for errors
+    pub fn location(&self) -> ErrorLocation {
+        ErrorLocation {
+            packet_type: self.packet_type,
+            offset: self.index,
+            path: Vec::new(),
+        }
+    }
+
+    // The location of the bytes that were just read, for values that turn out to be invalid
+    pub fn last_read_location(&self) -> ErrorLocation {
+        ErrorLocation {
+            offset: self.last_read,
+            ..self.location()
+        }
+    }
+
     pub fn next_bytes(&mut self, num_bytes: usize) -> Result<&[u8], PacketError> {
-        if self.index + num_bytes > self.data.len() {
-            Err(PacketError::BoundsError(self.clone()))
-        } else {
-            let bytes = &self.data[self.index..self.index + num_bytes];
-            self.index += num_bytes;
+        match self.index.checked_add(num_bytes) {
+            Some(end) if end <= self.data.len() => {
+                let bytes = &self.data[self.index..end];
+                self.last_read = self.index;
+                self.index = end;
+
+                Ok(bytes)
+            },
+            _ => Err(PacketError::BoundsError(self.location()))
+        }
+    }
+
+    pub(crate) fn remaining_bytes(&self) -> usize {
+        self.data.len().saturating_sub(self.index)
+    }
+
+    // Used to preallocate collections without trusting a length read from the packet
+    pub(crate) fn capacity_for(&self, len: usize) -> usize {
+        len.min(self.remaining_bytes())
+    }
+
+    // Limits the next length read from the packet, instead of the global limit
+    pub fn limit_next_length(&mut self, limit: usize) {
+        self.length_limit = Some(limit);
+    }
+
+    // Drops a limit that the field it was set for didn't use, so it can't apply to a later field
+    pub fn clear_length_limit(&mut self) {
+        self.length_limit = None;
+    }
+
+    // Checks a length read from the packet against the field's limit, or the global limit if the field has none
+    pub(crate) fn check_length(&mut self, len: u64, global_limit: usize) -> Result<usize, PacketError> {
+        let limit = self.length_limit.take().unwrap_or(global_limit);
 
-            Ok(bytes)
+        match usize::try_from(len) {
+            Ok(len) if len <= limit => Ok(len),
+            _ => Err(PacketError::LimitExceeded { len, limit, location: self.location() })
         }
     }
 
@@ -88,8 +255,8 @@ impl TryFrom<Box<[u8]>> for Packet {
         let packet_type_u8 = data[0];
 
         match PacketType::try_from(packet_type_u8) {
-            // The index is 1 because the first byte of the packet is the type, which has already been read
-            Ok(packet_type) => Ok(Packet { data: data.to_vec(), index: 1, packet_type }),
+            // The type isn't part of the packet's data, so offsets in errors start after it
+            Ok(packet_type) => Ok(Packet { data: data[1..].to_vec(), index: 0, packet_type, last_read: 0, length_limit: None }),
             Err(_) => Err(PacketError::InvalidTypeError(packet_type_u8))
         }
     }
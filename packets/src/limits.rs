@@ -0,0 +1,34 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Upper bounds on the lengths read from packets, checked before anything is allocated
+// Individual fields can override them with #[packet(max_len = N)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub max_collection_len: usize,
+    pub max_string_len: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            // Large enough for the voxels of a 255x255x255 part
+            max_collection_len: 1 << 24,
+            max_string_len: u16::MAX as usize,
+        }
+    }
+}
+
+static MAX_COLLECTION_LEN: AtomicUsize = AtomicUsize::new(1 << 24);
+static MAX_STRING_LEN: AtomicUsize = AtomicUsize::new(u16::MAX as usize);
+
+pub fn set_global_limits(limits: Limits) {
+    MAX_COLLECTION_LEN.store(limits.max_collection_len, Ordering::Relaxed);
+    MAX_STRING_LEN.store(limits.max_string_len, Ordering::Relaxed);
+}
+
+pub fn global_limits() -> Limits {
+    Limits {
+        max_collection_len: MAX_COLLECTION_LEN.load(Ordering::Relaxed),
+        max_string_len: MAX_STRING_LEN.load(Ordering::Relaxed),
+    }
+}
\ No newline at end of file
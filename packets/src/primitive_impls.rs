@@ -1,4 +1,6 @@
-use super::{Packet, PacketSerialize, PacketDeserialize, PacketError};
+use super::{Packet, PacketSerialize, PacketDeserialize, PacketSchema, PacketError};
+use super::limits::global_limits;
+use super::schema::{fingerprint, combine};
 
 impl<T: PacketSerialize> PacketSerialize for &T {
     fn serialize(&self, packet: &mut Packet) {
@@ -15,9 +17,10 @@ impl PacketSerialize for u8 {
 impl PacketDeserialize for u8 {
     fn deserialize(packet: &mut Packet) -> Result<Self, PacketError> {
         if packet.index >= packet.data.len() {
-            Err(PacketError::BoundsError(packet.clone()))
+            Err(PacketError::BoundsError(packet.location()))
         } else {
             let byte = packet.data[packet.index];
+            packet.last_read = packet.index;
             packet.index += 1;
 
             Ok(byte)
@@ -37,7 +40,7 @@ impl PacketDeserialize for bool {
         match val_bytes {
             0 => Ok(false),
             1 => Ok(true),
-            _ => Err(PacketError::InvalidPacketError(packet.clone()))
+            _ => Err(PacketError::InvalidPacketError(packet.last_read_location()))
         }
     }
 }
@@ -106,7 +109,8 @@ impl PacketSerialize for String {
 impl PacketDeserialize for String {
     fn deserialize(packet: &mut Packet) -> Result<Self, PacketError> {
         let string_len = u16::deserialize(packet)?;
-        let string_bytes = packet.next_bytes(string_len.into())?;
+        let string_len = packet.check_length(string_len.into(), global_limits().max_string_len)?;
+        let string_bytes = packet.next_bytes(string_len)?;
 
         Ok(String::from_utf8_lossy(string_bytes).into_owned())
     }
@@ -154,11 +158,12 @@ impl<T: PacketSerialize> PacketSerialize for Vec<T> {
 
 impl<T: PacketDeserialize> PacketDeserialize for Vec<T> {
     fn deserialize(packet: &mut Packet) -> Result<Self, PacketError> {
-        let len = u64::deserialize(packet)? as usize;
-        let mut vector = Vec::with_capacity(len);
+        let len = u64::deserialize(packet)?;
+        let len = packet.check_length(len, global_limits().max_collection_len)?;
+        let mut vector = Vec::with_capacity(packet.capacity_for(len));
 
-        for _ in 0..len {
-            let elem = T::deserialize(packet)?;
+        for i in 0..len {
+            let elem = T::deserialize(packet).map_err(|err| err.at_index(i))?;
             vector.push(elem);
         }
 
@@ -166,6 +171,40 @@ impl<T: PacketDeserialize> PacketDeserialize for Vec<T> {
     }
 }
 
+// Arrays have a fixed length, so unlike slices no length prefix is written
+impl<T: PacketSerialize, const N: usize> PacketSerialize for [T; N] {
+    fn serialize(&self, packet: &mut Packet) {
+        for elem in self {
+            elem.serialize(packet);
+        }
+    }
+}
+
+impl<T: PacketDeserialize, const N: usize> PacketDeserialize for [T; N] {
+    fn deserialize(packet: &mut Packet) -> Result<Self, PacketError> {
+        let mut result = Ok(());
+
+        // Elements after the first error are never read
+        let elems: [Option<T>; N] = std::array::from_fn(|i| {
+            if result.is_err() {
+                return None;
+            }
+
+            match T::deserialize(packet) {
+                Ok(elem) => Some(elem),
+                Err(err) => {
+                    result = Err(err.at_index(i));
+                    None
+                }
+            }
+        });
+
+        result?;
+
+        Ok(elems.map(|elem| elem.unwrap()))
+    }
+}
+
 impl<T: PacketSerialize> PacketSerialize for Option<T> {
     fn serialize(&self, packet: &mut Packet) {
         match self {
@@ -193,9 +232,62 @@ impl<T: PacketDeserialize> PacketDeserialize for Option<T> {
     }
 }
 
+impl<T: PacketSchema + ?Sized> PacketSchema for &T {
+    const FINGERPRINT: u64 = T::FINGERPRINT;
+}
+
+impl PacketSchema for u8 {
+    const FINGERPRINT: u64 = fingerprint("u8");
+}
+
+impl PacketSchema for bool {
+    const FINGERPRINT: u64 = fingerprint("bool");
+}
+
+impl PacketSchema for i16 {
+    const FINGERPRINT: u64 = fingerprint("i16");
+}
+
+impl PacketSchema for u16 {
+    const FINGERPRINT: u64 = fingerprint("u16");
+}
+
+impl PacketSchema for u32 {
+    const FINGERPRINT: u64 = fingerprint("u32");
+}
+
+impl PacketSchema for u64 {
+    const FINGERPRINT: u64 = fingerprint("u64");
+}
+
+impl PacketSchema for String {
+    const FINGERPRINT: u64 = fingerprint("String");
+}
+
+impl PacketSchema for f32 {
+    const FINGERPRINT: u64 = fingerprint("f32");
+}
+
+// Slices and vectors have the same layout
+impl<T: PacketSchema> PacketSchema for [T] {
+    const FINGERPRINT: u64 = combine(&[fingerprint("[T]"), T::FINGERPRINT]);
+}
+
+impl<T: PacketSchema> PacketSchema for Vec<T> {
+    const FINGERPRINT: u64 = <[T]>::FINGERPRINT;
+}
+
+impl<T: PacketSchema, const N: usize> PacketSchema for [T; N] {
+    const FINGERPRINT: u64 = combine(&[fingerprint("[T; N]"), T::FINGERPRINT, N as u64]);
+}
+
+impl<T: PacketSchema> PacketSchema for Option<T> {
+    const FINGERPRINT: u64 = combine(&[fingerprint("Option<T>"), T::FINGERPRINT]);
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{Packet, PacketType::PlayerConnected, PacketSerialize, PacketDeserialize};
+    use crate::{Packet, PacketType::PlayerConnected, PacketSerialize, PacketDeserialize, PacketError};
 
     #[test]
     fn u8_serialize_deserialize() {
@@ -298,7 +390,7 @@ mod tests {
         let mut packet = Packet::new(PlayerConnected);
         
         let x = [1.0, 2.0, 3.0];
-        x.serialize(&mut packet);
+        x.as_slice().serialize(&mut packet);
 
         let y: Vec<f32> = Vec::deserialize(&mut packet).unwrap();
 
@@ -321,6 +413,89 @@ mod tests {
         }
     }
 
+    #[test]
+    fn array_serialize_deserialize() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        let x = [1u16, 2, 3, 4];
+        x.serialize(&mut packet);
+
+        assert_eq!(packet.data.len(), 8);
+
+        let y = <[u16; 4]>::deserialize(&mut packet).unwrap();
+
+        assert_eq!(x, y);
+    }
+
+    #[test]
+    fn truncated_array_is_bounds_error() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        [1u16, 2].serialize(&mut packet);
+
+        assert!(matches!(<[u16; 4]>::deserialize(&mut packet), Err(PacketError::BoundsError(_))));
+    }
+
+    #[test]
+    fn oversized_vec_length_exceeds_limit() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        u64::MAX.serialize(&mut packet);
+
+        assert!(matches!(Vec::<u64>::deserialize(&mut packet), Err(PacketError::LimitExceeded { .. })));
+    }
+
+    #[test]
+    fn vec_element_error_has_index() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        vec![true, false].serialize(&mut packet);
+        packet.data[9] = 2;
+
+        let err = Vec::<bool>::deserialize(&mut packet).unwrap_err();
+
+        assert!(matches!(err, PacketError::InvalidPacketError(_)));
+        assert_eq!(err.to_string(), "The value of the data at byte 9 of PlayerConnected packet in [1] is invalid!");
+    }
+
+    #[test]
+    fn truncated_vec_is_bounds_error() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        4u64.serialize(&mut packet);
+        1u64.serialize(&mut packet);
+
+        assert!(matches!(Vec::<u64>::deserialize(&mut packet), Err(PacketError::BoundsError(_))));
+    }
+
+    #[test]
+    fn field_limit_overrides_global_limit() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        "ab".to_string().serialize(&mut packet);
+        "Test".to_string().serialize(&mut packet);
+
+        let mut limited_packet = packet.clone();
+        limited_packet.limit_next_length(3);
+        limited_packet.next_bytes(4).unwrap();
+        assert!(matches!(String::deserialize(&mut limited_packet), Err(PacketError::LimitExceeded { len: 4, limit: 3, .. })));
+
+        // The override only applies to a single length
+        packet.limit_next_length(3);
+        assert_eq!(String::deserialize(&mut packet).unwrap(), "ab");
+        assert_eq!(String::deserialize(&mut packet).unwrap(), "Test");
+    }
+
+    #[test]
+    fn oversized_string_length_is_bounds_error() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        u16::MAX.serialize(&mut packet);
+        packet.write_bytes(b"Test");
+
+        assert!(matches!(String::deserialize(&mut packet), Err(PacketError::BoundsError(_))));
+    }
+
     #[test]
     fn option_serialize_deserialize() {
         let mut packet = Packet::new(PlayerConnected);
@@ -0,0 +1,65 @@
+// Compile-time fingerprints of packet layouts
+// A client and server only understand each other if the fingerprints of every packet they exchange match
+// The hash is 64-bit FNV-1a, which is simple enough to evaluate in a const context
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+const fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    let mut i = 0;
+
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+        i += 1;
+    }
+
+    hash
+}
+
+// Fingerprints a textual description of a layout
+pub const fn fingerprint(layout: &str) -> u64 {
+    fnv1a(FNV_OFFSET_BASIS, layout.as_bytes())
+}
+
+// Order-sensitive combination of several fingerprints
+pub const fn combine(fingerprints: &[u64]) -> u64 {
+    let mut hash = FNV_OFFSET_BASIS;
+    let mut i = 0;
+
+    while i < fingerprints.len() {
+        hash = fnv1a(hash, &fingerprints[i].to_le_bytes());
+        i += 1;
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Transform, Vec3};
+
+    use crate::PacketSchema;
+    use crate::schema::{fingerprint, combine};
+
+    #[test]
+    fn fingerprint_is_stable() {
+        // Changing the hash changes every protocol fingerprint, so it is pinned here
+        assert_eq!(fingerprint(""), 0xcbf29ce484222325);
+        assert_eq!(fingerprint("a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn combine_is_order_sensitive() {
+        assert_ne!(combine(&[1, 2]), combine(&[2, 1]));
+    }
+
+    #[test]
+    fn layouts_have_distinct_fingerprints() {
+        assert_ne!(u16::FINGERPRINT, i16::FINGERPRINT);
+        assert_ne!(<Vec<u8>>::FINGERPRINT, <Vec<u16>>::FINGERPRINT);
+        assert_ne!(<[u8; 3]>::FINGERPRINT, <[u8; 4]>::FINGERPRINT);
+        assert_ne!(<(u8, u16)>::FINGERPRINT, <(u16, u8)>::FINGERPRINT);
+        assert_ne!(Transform::FINGERPRINT, Vec3::FINGERPRINT);
+    }
+}
\ No newline at end of file
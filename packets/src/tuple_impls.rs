@@ -1,4 +1,5 @@
-use super::{Packet, PacketSerialize, PacketDeserialize, PacketError};
+use super::{Packet, PacketSerialize, PacketDeserialize, PacketSchema, PacketError};
+use super::schema::combine;
 
 impl<A> PacketSerialize for (A,) 
 where
@@ -14,7 +15,7 @@ where
     A: PacketDeserialize
 {
     fn deserialize(packet: &mut Packet) -> Result<Self, PacketError> {
-        let a = A::deserialize(packet)?;
+        let a = A::deserialize(packet).map_err(|err| err.in_field("0"))?;
         Ok((a,))
     }
 }
@@ -36,8 +37,8 @@ where
     B: PacketDeserialize
 {
     fn deserialize(packet: &mut Packet) -> Result<Self, PacketError> {
-        let a = A::deserialize(packet)?;
-        let b = B::deserialize(packet)?;
+        let a = A::deserialize(packet).map_err(|err| err.in_field("0"))?;
+        let b = B::deserialize(packet).map_err(|err| err.in_field("1"))?;
         Ok((a, b))
     }
 }
@@ -62,9 +63,21 @@ where
     C: PacketDeserialize
 {
     fn deserialize(packet: &mut Packet) -> Result<Self, PacketError> {
-        let a = A::deserialize(packet)?;
-        let b = B::deserialize(packet)?;
-        let c = C::deserialize(packet)?;
+        let a = A::deserialize(packet).map_err(|err| err.in_field("0"))?;
+        let b = B::deserialize(packet).map_err(|err| err.in_field("1"))?;
+        let c = C::deserialize(packet).map_err(|err| err.in_field("2"))?;
         Ok((a, b, c))
     }
+}
+
+impl<A: PacketSchema> PacketSchema for (A,) {
+    const FINGERPRINT: u64 = combine(&[A::FINGERPRINT]);
+}
+
+impl<A: PacketSchema, B: PacketSchema> PacketSchema for (A, B) {
+    const FINGERPRINT: u64 = combine(&[A::FINGERPRINT, B::FINGERPRINT]);
+}
+
+impl<A: PacketSchema, B: PacketSchema, C: PacketSchema> PacketSchema for (A, B, C) {
+    const FINGERPRINT: u64 = combine(&[A::FINGERPRINT, B::FINGERPRINT, C::FINGERPRINT]);
 }
\ No newline at end of file
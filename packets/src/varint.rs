@@ -0,0 +1,318 @@
+use super::{Packet, PacketSerialize, PacketDeserialize, PacketSchema, PacketError};
+use super::limits::global_limits;
+use super::schema::{fingerprint, combine};
+
+// Variable-length encoding for integers and collection lengths, used by fields marked with #[packet(varint)]
+// Unsigned integers are written as LEB128, signed integers are zigzag encoded first so small negative numbers stay small
+
+pub trait VarIntSerialize {
+    fn serialize_varint(&self, packet: &mut Packet);
+}
+
+pub trait VarIntDeserialize: Sized {
+    fn deserialize_varint(packet: &mut Packet) -> Result<Self, PacketError>;
+}
+
+// The encoding itself, for fields that name this module with #[packet(with = packets::varint)]
+// #[packet(varint)] folds in the field type's VarIntSchema instead
+pub const FINGERPRINT: u64 = fingerprint("LEB128, zigzag encoded if signed");
+
+// The varint counterpart of PacketSchema
+pub trait VarIntSchema {
+    const FINGERPRINT: u64;
+}
+
+pub fn serialize<T: VarIntSerialize + ?Sized>(value: &T, packet: &mut Packet) {
+    value.serialize_varint(packet);
+}
+
+pub fn deserialize<T: VarIntDeserialize>(packet: &mut Packet) -> Result<T, PacketError> {
+    T::deserialize_varint(packet)
+}
+
+fn write_leb128(mut value: u64, packet: &mut Packet) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            byte.serialize(packet);
+            return;
+        }
+
+        (byte | 0x80).serialize(packet);
+    }
+}
+
+fn read_leb128(packet: &mut Packet, max_bits: u32) -> Result<u64, PacketError> {
+    let mut value = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = u8::deserialize(packet)?;
+        let bits = (byte & 0x7f) as u64;
+
+        // Reject encodings that don't fit in the target type
+        if shift >= max_bits || (max_bits - shift < 7 && bits >> (max_bits - shift) != 0) {
+            return Err(PacketError::InvalidPacketError(packet.last_read_location()));
+        }
+
+        value |= bits << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+
+        shift += 7;
+    }
+}
+
+fn read_length(packet: &mut Packet, global_limit: usize) -> Result<usize, PacketError> {
+    let len = read_leb128(packet, u64::BITS)?;
+    packet.check_length(len, global_limit)
+}
+
+macro_rules! impl_unsigned_varint {
+    ($($t:ty),*) => {$(
+        impl VarIntSerialize for $t {
+            fn serialize_varint(&self, packet: &mut Packet) {
+                write_leb128(*self as u64, packet);
+            }
+        }
+
+        impl VarIntDeserialize for $t {
+            fn deserialize_varint(packet: &mut Packet) -> Result<Self, PacketError> {
+                Ok(read_leb128(packet, <$t>::BITS)? as $t)
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_signed_varint {
+    ($($t:ty => $unsigned:ty),*) => {$(
+        impl VarIntSerialize for $t {
+            fn serialize_varint(&self, packet: &mut Packet) {
+                let zigzag = ((*self << 1) ^ (*self >> (<$t>::BITS - 1))) as $unsigned;
+                write_leb128(zigzag as u64, packet);
+            }
+        }
+
+        impl VarIntDeserialize for $t {
+            fn deserialize_varint(packet: &mut Packet) -> Result<Self, PacketError> {
+                let zigzag = read_leb128(packet, <$unsigned>::BITS)? as $unsigned;
+                Ok(((zigzag >> 1) as $t) ^ -((zigzag & 1) as $t))
+            }
+        }
+    )*};
+}
+
+impl_unsigned_varint!(u16, u32, u64);
+impl_signed_varint!(i16 => u16, i32 => u32, i64 => u64);
+
+macro_rules! impl_varint_schema {
+    ($($t:ty),*) => {$(
+        impl VarIntSchema for $t {
+            const FINGERPRINT: u64 = fingerprint(concat!("varint ", stringify!($t)));
+        }
+    )*};
+}
+
+impl_varint_schema!(u16, u32, u64, i16, i32, i64, String);
+
+impl<T: PacketSchema> VarIntSchema for [T] {
+    const FINGERPRINT: u64 = combine(&[fingerprint("varint [T]"), T::FINGERPRINT]);
+}
+
+impl<T: PacketSchema> VarIntSchema for Vec<T> {
+    const FINGERPRINT: u64 = <[T] as VarIntSchema>::FINGERPRINT;
+}
+
+impl<T: VarIntSchema> VarIntSchema for Option<T> {
+    const FINGERPRINT: u64 = combine(&[fingerprint("varint Option<T>"), T::FINGERPRINT]);
+}
+
+impl VarIntSerialize for String {
+    fn serialize_varint(&self, packet: &mut Packet) {
+        write_leb128(self.len() as u64, packet);
+        packet.write_bytes(self.as_bytes());
+    }
+}
+
+impl VarIntDeserialize for String {
+    fn deserialize_varint(packet: &mut Packet) -> Result<Self, PacketError> {
+        let string_len = read_length(packet, global_limits().max_string_len)?;
+        let string_bytes = packet.next_bytes(string_len)?;
+
+        Ok(String::from_utf8_lossy(string_bytes).into_owned())
+    }
+}
+
+impl<T: PacketSerialize> VarIntSerialize for [T] {
+    fn serialize_varint(&self, packet: &mut Packet) {
+        write_leb128(self.len() as u64, packet);
+        for elem in self {
+            elem.serialize(packet);
+        }
+    }
+}
+
+impl<T: PacketSerialize> VarIntSerialize for Vec<T> {
+    fn serialize_varint(&self, packet: &mut Packet) {
+        self.as_slice().serialize_varint(packet);
+    }
+}
+
+impl<T: PacketDeserialize> VarIntDeserialize for Vec<T> {
+    fn deserialize_varint(packet: &mut Packet) -> Result<Self, PacketError> {
+        let len = read_length(packet, global_limits().max_collection_len)?;
+        let mut vector = Vec::with_capacity(packet.capacity_for(len));
+
+        for i in 0..len {
+            let elem = T::deserialize(packet).map_err(|err| err.at_index(i))?;
+            vector.push(elem);
+        }
+
+        Ok(vector)
+    }
+}
+
+impl<T: VarIntSerialize> VarIntSerialize for Option<T> {
+    fn serialize_varint(&self, packet: &mut Packet) {
+        match self {
+            Self::Some(inner) => {
+                true.serialize(packet);
+                inner.serialize_varint(packet);
+            },
+            Self::None => {
+                false.serialize(packet);
+            }
+        }
+    }
+}
+
+impl<T: VarIntDeserialize> VarIntDeserialize for Option<T> {
+    fn deserialize_varint(packet: &mut Packet) -> Result<Self, PacketError> {
+        let has_some = bool::deserialize(packet)?;
+
+        if has_some {
+            let inner = T::deserialize_varint(packet)?;
+            Ok(Some(inner))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Packet, PacketSerialize, PacketError, PacketType::PlayerConnected};
+    use crate::varint::{serialize, deserialize};
+
+    #[test]
+    fn small_u32_is_one_byte() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        serialize(&127u32, &mut packet);
+
+        assert_eq!(packet.data.len(), 1);
+    }
+
+    #[test]
+    fn u32_serialize_deserialize() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        for x in [0u32, 1, 127, 128, 300, 16384, u32::MAX] {
+            serialize(&x, &mut packet);
+        }
+
+        for x in [0u32, 1, 127, 128, 300, 16384, u32::MAX] {
+            let y: u32 = deserialize(&mut packet).unwrap();
+            assert_eq!(x, y);
+        }
+    }
+
+    #[test]
+    fn u64_max_serialize_deserialize() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        serialize(&u64::MAX, &mut packet);
+
+        assert_eq!(packet.data.len(), 10);
+        assert_eq!(deserialize::<u64>(&mut packet).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn i32_zigzag_serialize_deserialize() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        for x in [0i32, -1, 1, -64, 63, i32::MIN, i32::MAX] {
+            serialize(&x, &mut packet);
+        }
+
+        for x in [0i32, -1, 1, -64, 63, i32::MIN, i32::MAX] {
+            let y: i32 = deserialize(&mut packet).unwrap();
+            assert_eq!(x, y);
+        }
+    }
+
+    #[test]
+    fn small_negative_i16_is_one_byte() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        serialize(&-5i16, &mut packet);
+
+        assert_eq!(packet.data.len(), 1);
+    }
+
+    #[test]
+    fn overflowing_u16_is_invalid() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        serialize(&(u16::MAX as u32 + 1), &mut packet);
+
+        assert!(matches!(deserialize::<u16>(&mut packet), Err(PacketError::InvalidPacketError(_))));
+    }
+
+    #[test]
+    fn unterminated_varint_is_invalid() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        for _ in 0..6 {
+            0xffu8.serialize(&mut packet);
+        }
+
+        assert!(matches!(deserialize::<u32>(&mut packet), Err(PacketError::InvalidPacketError(_))));
+    }
+
+    #[test]
+    fn string_serialize_deserialize() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        let x = "Test".to_string();
+        serialize(&x, &mut packet);
+
+        assert_eq!(packet.data.len(), 5);
+        assert_eq!(deserialize::<String>(&mut packet).unwrap(), x);
+    }
+
+    #[test]
+    fn vec_serialize_deserialize() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        let x: Vec<u8> = (0..200).collect();
+        serialize(&x, &mut packet);
+
+        assert_eq!(packet.data.len(), 202);
+        assert_eq!(deserialize::<Vec<u8>>(&mut packet).unwrap(), x);
+    }
+
+    #[test]
+    fn option_serialize_deserialize() {
+        let mut packet = Packet::new(PlayerConnected);
+
+        let x = Some(300u32);
+        serialize(&x, &mut packet);
+
+        assert_eq!(deserialize::<Option<u32>>(&mut packet).unwrap(), x);
+    }
+}
\ No newline at end of file
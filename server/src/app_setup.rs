@@ -6,16 +6,24 @@ use bevy::diagnostic::DiagnosticsPlugin;
 use bevy::render::mesh::MeshPlugin;
 use bevy::scene::ScenePlugin;
 use common::PHYSICS_TIMESTEP;
-use common::fixed_update::FixedUpdateSet;
+use common::construct_access::ConstructAccessPlugin;
+use common::construct_replication::ConstructReplicationPlugin;
+use common::fixed_update::{FixedUpdateSet, NetworkReceiveSet, NetworkSendSet};
 use common::missile::MissilePlugin;
+use common::network_message::NetworkSide;
 use common::part::{PartPlugin, Parts};
+use common::player_connection::PlayerConnectionPlugin;
+use common::player_movement::PlayerMovementPlugin;
 use common::predefined_parts::add_hardcoded_parts;
 
+use crate::construct_access::ServerConstructAccessPlugin;
+use crate::construct_replication::ServerConstructReplicationPlugin;
 use crate::missile::ServerMissilePlugin;
 use crate::network_id_generator::NetworkIdGenerator;
-use crate::packet_handling::process_packets;
+use crate::packet_handling::{process_packets, send_packets};
 use crate::part::ServerPartPlugin;
-use crate::player_connection::PlayerConnectionPlugin;
+use crate::player_connection::ServerPlayerConnectionPlugin;
+use crate::player_movement::ServerPlayerMovementPlugin;
 
 pub fn setup_hardcoded_parts(mut parts: ResMut<Parts>) {
     add_hardcoded_parts(&mut parts);
@@ -49,12 +57,23 @@ impl SetupServerSpecific for App {
                 PartPlugin,
                 ServerPartPlugin,
                 PlayerConnectionPlugin,
+                ServerPlayerConnectionPlugin,
+                PlayerMovementPlugin,
+                ServerPlayerMovementPlugin,
+                ConstructReplicationPlugin,
+                ServerConstructReplicationPlugin,
+                ConstructAccessPlugin,
+                ServerConstructAccessPlugin,
                 MissilePlugin,
                 ServerMissilePlugin,
             ))
             .insert_resource(FixedTime::new(Duration::from_secs_f32(PHYSICS_TIMESTEP)))
             .insert_resource(NetworkIdGenerator::new())
+            .insert_resource(NetworkSide::Server)
             .add_systems(Startup, setup_hardcoded_parts)
-            .add_systems(FixedUpdate, process_packets.in_set(FixedUpdateSet::PreUpdate))
+            .add_systems(FixedUpdate, (
+                process_packets.in_set(FixedUpdateSet::PreUpdate).before(NetworkReceiveSet),
+                send_packets.in_set(FixedUpdateSet::PostUpdate).after(NetworkSendSet),
+            ))
     }
 }
\ No newline at end of file
@@ -0,0 +1,221 @@
+use std::fs;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+
+use bevy::log::Level;
+use bevy::prelude::Resource;
+use packets::limits::Limits;
+use toml_edit::{Document, Item};
+
+pub const USAGE: &str = "\
+Usage: ship-designer-server [OPTIONS]
+
+Options are read from the config file first, then overridden by the command line.
+
+Options:
+    --config <FILE>                   TOML file with any of the settings below, using their names without
+                                      the leading dashes and with underscores, e.g. max_total_connections = 20
+    --address <ADDRESS:PORT>          Address to listen on [default: 127.0.0.1:36756]
+    --bind-all                        Listen on all interfaces, on the port of --address
+    --log-level <LEVEL>               trace, debug, info, warn or error [default: debug]
+    --max-total-connections <N>       Connections, including ones still connecting [default: 20]
+    --max-active-connections <N>      Connected players [default: 10]
+    --active-timeout-ms <MS>          Time before an unresponsive player is disconnected [default: 3600000]
+    --max-collection-len <N>          Longest list accepted in a packet [default: 16777216]
+    --max-string-len <N>              Longest string accepted in a packet [default: 65535]
+    -h, --help                        Print this message";
+
+// The settings that can be given in the config file or as --flags
+const SETTINGS: [&str; 8] = [
+    "address",
+    "bind_all",
+    "log_level",
+    "max_total_connections",
+    "max_active_connections",
+    "active_timeout_ms",
+    "max_collection_len",
+    "max_string_len",
+];
+
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct ServerConfig {
+    pub address: SocketAddr,
+    pub bind_all: bool,
+    pub log_level: Level,
+    pub max_total_connections: usize,
+    pub max_active_connections: usize,
+    pub active_timeout_ms: u64,
+    pub limits: Limits,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: SocketAddr::from((Ipv4Addr::LOCALHOST, 36756)),
+            bind_all: false,
+            log_level: Level::DEBUG,
+            max_total_connections: 20,
+            max_active_connections: 10,
+            active_timeout_ms: 3600000,
+            limits: Limits::default(),
+        }
+    }
+}
+
+fn parse<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid value '{}' for {}", value, name))
+}
+
+impl ServerConfig {
+    // Builds the config from the command line arguments, reading the config file they point to first
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let args: Vec<String> = args.into_iter().collect();
+        let mut config = Self::default();
+
+        if let Some(position) = args.iter().position(|arg| arg == "--config") {
+            let path = args.get(position + 1).ok_or("--config needs a file")?;
+            let text = fs::read_to_string(path).map_err(|err| format!("failed to read {}: {}", path, err))?;
+            config.apply_toml(&text).map_err(|err| format!("{}: {}", path, err))?;
+        }
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--bind-all" {
+                config.bind_all = true;
+                continue;
+            }
+
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name.replace('-', "_"),
+                None => return Err(format!("unexpected argument {}", arg)),
+            };
+
+            if name != "config" && !SETTINGS.contains(&name.as_str()) {
+                return Err(format!("unknown option {}", arg));
+            }
+
+            let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+
+            if name != "config" {
+                config.set(&name, &value)?;
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn apply_toml(&mut self, text: &str) -> Result<(), String> {
+        let document: Document = text.parse().map_err(|err| format!("{}", err))?;
+
+        for (name, item) in document.iter() {
+            let value = match item {
+                Item::Value(value) => match value.as_str() {
+                    Some(string) => string.to_string(),
+                    None => value.to_string().trim().to_string(),
+                },
+                _ => return Err(format!("{} must be a value", name)),
+            };
+
+            self.set(name, &value)?;
+        }
+
+        Ok(())
+    }
+
+    fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "address" => self.address = parse(name, value)?,
+            "bind_all" => self.bind_all = parse(name, value)?,
+            "log_level" => self.log_level = parse(name, value)?,
+            "max_total_connections" => self.max_total_connections = parse(name, value)?,
+            "max_active_connections" => self.max_active_connections = parse(name, value)?,
+            "active_timeout_ms" => self.active_timeout_ms = parse(name, value)?,
+            "max_collection_len" => self.limits.max_collection_len = parse(name, value)?,
+            "max_string_len" => self.limits.max_string_len = parse(name, value)?,
+            _ => return Err(format!("unknown setting {}", name)),
+        }
+
+        Ok(())
+    }
+
+    pub fn bind_address(&self) -> SocketAddr {
+        if self.bind_all {
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, self.address.port()))
+        } else {
+            self.address
+        }
+    }
+
+    pub fn uflow_config(&self) -> uflow::server::Config {
+        uflow::server::Config {
+            max_total_connections: self.max_total_connections,
+            max_active_connections: self.max_active_connections,
+            enable_handshake_errors: false,
+            endpoint_config: uflow::EndpointConfig {
+                active_timeout_ms: self.active_timeout_ms,
+                ..Default::default()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use bevy::log::Level;
+
+    use crate::config::ServerConfig;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn no_args_is_default() {
+        assert_eq!(ServerConfig::from_args(Vec::new()).unwrap(), ServerConfig::default());
+    }
+
+    #[test]
+    fn flags_override_defaults() {
+        let config = ServerConfig::from_args(args(&[
+            "--address", "127.0.0.1:4000",
+            "--bind-all",
+            "--log-level", "warn",
+            "--max-active-connections", "4",
+            "--max-string-len", "128",
+        ])).unwrap();
+
+        assert_eq!(config.bind_address(), "0.0.0.0:4000".parse::<SocketAddr>().unwrap());
+        assert_eq!(config.log_level, Level::WARN);
+        assert_eq!(config.max_active_connections, 4);
+        assert_eq!(config.limits.max_string_len, 128);
+        assert_eq!(config.max_total_connections, 20);
+    }
+
+    #[test]
+    fn toml_sets_values() {
+        let mut config = ServerConfig::default();
+
+        config.apply_toml("
+            address = \"10.0.0.2:5000\"
+            bind_all = true
+            log_level = \"info\"
+            active_timeout_ms = 30000
+        ").unwrap();
+
+        assert_eq!(config.address, "10.0.0.2:5000".parse::<SocketAddr>().unwrap());
+        assert!(config.bind_all);
+        assert_eq!(config.log_level, Level::INFO);
+        assert_eq!(config.active_timeout_ms, 30000);
+    }
+
+    #[test]
+    fn invalid_settings_are_errors() {
+        assert!(ServerConfig::default().apply_toml("max_players = 3").is_err());
+        assert!(ServerConfig::default().apply_toml("active_timeout_ms = \"soon\"").is_err());
+        assert!(ServerConfig::from_args(args(&["--max-total-connections"])).is_err());
+        assert!(ServerConfig::from_args(args(&["--log-level", "loud"])).is_err());
+        assert!(ServerConfig::from_args(args(&["--max-players", "3"])).is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,98 @@
+use bevy::prelude::*;
+
+use common::construct_access::{ConstructAccess, ConstructAccessChanged, ConstructRole, GrantConstructRole, RevokeConstructRole};
+use common::fixed_update::FixedUpdateSet;
+use common::network_id::NetworkId;
+use common::network_message::FromPlayer;
+use common::player::PlayerId;
+use common::player_connection::PlayerDisconnected;
+use common::ship::Ship;
+
+fn grant_construct_roles(
+    mut grant_reader: EventReader<FromPlayer<GrantConstructRole>>,
+    mut access_changed_writer: EventWriter<ConstructAccessChanged>,
+    mut construct_query: Query<(&NetworkId, &mut ConstructAccess), With<Ship>>,
+    player_query: Query<&PlayerId>,
+) {
+    for FromPlayer { sender, message: grant } in grant_reader.iter() {
+        let Some((_, mut access)) = construct_query.iter_mut()
+            .find(|(network_id, _)| **network_id == grant.construct_network_id) else {
+            continue;
+        };
+
+        if access.role(*sender) != ConstructRole::Owner {
+            warn!("Player {:?} tried to grant a role on construct {:?} without owning it", sender, grant.construct_network_id);
+            continue;
+        }
+
+        // Player IDs are reused, so a role given to one that isn't in the game would go to whoever joins with it next
+        if !player_query.iter().any(|player_id| *player_id == grant.player_id) {
+            warn!("Player {:?} tried to grant a role on construct {:?} to player {:?}, who isn't in the game", sender, grant.construct_network_id, grant.player_id);
+            continue;
+        }
+
+        info!("Player {:?} granted {:?} on construct {:?} to player {:?}", sender, grant.role, grant.construct_network_id, grant.player_id);
+
+        access.grant(grant.player_id, grant.role);
+        access_changed_writer.send(ConstructAccessChanged {
+            construct_network_id: grant.construct_network_id,
+            access: access.clone(),
+        });
+    }
+}
+
+fn revoke_construct_roles(
+    mut revoke_reader: EventReader<FromPlayer<RevokeConstructRole>>,
+    mut access_changed_writer: EventWriter<ConstructAccessChanged>,
+    mut construct_query: Query<(&NetworkId, &mut ConstructAccess), With<Ship>>,
+) {
+    for FromPlayer { sender, message: revoke } in revoke_reader.iter() {
+        let Some((_, mut access)) = construct_query.iter_mut()
+            .find(|(network_id, _)| **network_id == revoke.construct_network_id) else {
+            continue;
+        };
+
+        if access.role(*sender) != ConstructRole::Owner {
+            warn!("Player {:?} tried to revoke a role on construct {:?} without owning it", sender, revoke.construct_network_id);
+            continue;
+        }
+
+        info!("Player {:?} revoked the role of player {:?} on construct {:?}", sender, revoke.player_id, revoke.construct_network_id);
+
+        access.revoke(revoke.player_id);
+        access_changed_writer.send(ConstructAccessChanged {
+            construct_network_id: revoke.construct_network_id,
+            access: access.clone(),
+        });
+    }
+}
+
+// Player IDs are reused once they're free, so a new player would otherwise inherit the roles of the player that left
+fn forget_disconnected_players(
+    mut player_disconnected_reader: EventReader<PlayerDisconnected>,
+    mut access_changed_writer: EventWriter<ConstructAccessChanged>,
+    mut construct_query: Query<(&NetworkId, &mut ConstructAccess), With<Ship>>,
+) {
+    for player_disconnected in player_disconnected_reader.iter() {
+        for (network_id, mut access) in construct_query.iter_mut() {
+            if access.remove_player(player_disconnected.0) {
+                access_changed_writer.send(ConstructAccessChanged {
+                    construct_network_id: *network_id,
+                    access: access.clone(),
+                });
+            }
+        }
+    }
+}
+
+pub struct ServerConstructAccessPlugin;
+
+impl Plugin for ServerConstructAccessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, (
+            grant_construct_roles,
+            revoke_construct_roles,
+            forget_disconnected_players,
+        ).in_set(FixedUpdateSet::Update));
+    }
+}
\ No newline at end of file
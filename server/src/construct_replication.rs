@@ -0,0 +1,43 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use common::compact_transform::CompactTransform;
+use common::construct_replication::{ConstructState, ConstructStates, CONSTRUCT_STATE_INTERVAL};
+use common::fixed_update::{FixedTick, FixedUpdateSet, NetworkSendSet};
+use common::network_id::NetworkId;
+use common::ship::Ship;
+
+fn send_construct_states(
+    tick: Res<FixedTick>,
+    construct_query: Query<(&NetworkId, &Transform, &Velocity), With<Ship>>,
+    mut construct_states_writer: EventWriter<ConstructStates>,
+) {
+    if tick.0 % CONSTRUCT_STATE_INTERVAL != 0 {
+        return;
+    }
+
+    let constructs: Vec<ConstructState> = construct_query.iter()
+        .map(|(&network_id, &transform, velocity)| ConstructState {
+            network_id,
+            transform: CompactTransform::from(transform),
+            linear_velocity: velocity.linvel,
+            angular_velocity: velocity.angvel,
+        })
+        .collect();
+
+    if constructs.is_empty() {
+        return;
+    }
+
+    construct_states_writer.send(ConstructStates { tick: tick.0, constructs });
+}
+
+pub struct ServerConstructReplicationPlugin;
+
+impl Plugin for ServerConstructReplicationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate,
+            send_construct_states.in_set(FixedUpdateSet::PostUpdate).before(NetworkSendSet)
+        );
+    }
+}
\ No newline at end of file
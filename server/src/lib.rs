@@ -1,7 +1,11 @@
 pub mod app_setup;
+pub mod config;
+pub mod construct_access;
+pub mod construct_replication;
 pub mod missile;
 pub mod network_id_generator;
 pub mod packet_handling;
 pub mod part;
 pub mod player_connection;
+pub mod player_movement;
 pub mod server_state;
\ No newline at end of file
@@ -1,21 +1,42 @@
-use bevy::log::{LogPlugin, Level};
+use bevy::log::LogPlugin;
 use bevy::prelude::*;
 
+use common::construct_access::ConstructAccess;
 use common::part::{Parts, PartId};
 use common::fixed_update::{SetupFixedTimeStepSchedule, SetupRapier};
 use common::ship::ShipBundle;
+use packets::limits::set_global_limits;
 use ship_designer_server::app_setup::{setup_hardcoded_parts, SetupBevyPlugins, SetupServerSpecific};
+use ship_designer_server::config::{ServerConfig, USAGE};
 use ship_designer_server::part::spawn_part;
 use ship_designer_server::server_state::ServerState;
 use ship_designer_server::network_id_generator::NetworkIdGenerator;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let config = match ServerConfig::from_args(args) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    set_global_limits(config.limits);
+
     App::new()
         .setup_bevy_plugins()
         .add_plugins(LogPlugin {
-            level: Level::DEBUG,
+            level: config.log_level,
             filter: String::new()
         })
+        .insert_resource(config)
         .setup_fixed_timestep_schedule()
         .setup_rapier()
         .setup_server_specific()
@@ -27,18 +48,10 @@ fn main() {
 }
 
 fn setup_server(world: &mut World) {
-    let address = "127.0.0.1:36756";
-    let server_config = uflow::server::Config {
-        max_total_connections: 20,
-        max_active_connections: 10,
-        enable_handshake_errors: false,
-        endpoint_config: uflow::EndpointConfig {
-            active_timeout_ms: 3600000,
-            ..Default::default()
-        }
-    };
+    let config = world.resource::<ServerConfig>();
+    let address = config.bind_address();
 
-    let server = uflow::server::Server::bind(address, server_config)
+    let server = uflow::server::Server::bind(address, config.uflow_config())
         .expect(&format!("Failed to bind on {}", address));
     
     let server_state = ServerState::new(server);
@@ -60,6 +73,8 @@ fn setup(
             scale: Vec3::splat(1.0)
         }),
         network_id: network_id_generator.generate(),
+        // Anyone who joins can build on it
+        access: ConstructAccess::open(),
         ..Default::default()
     }).id();
     
@@ -1,31 +1,36 @@
 use std::f32::consts::TAU;
 
 use bevy::prelude::*;
-use bevy::utils::HashSet;
+use bevy::utils::{HashMap, HashSet};
 use bevy_rapier3d::prelude::*;
 
+use common::construct_access::ConstructAccess;
 use common::fixed_update::FixedUpdateSet;
 use common::network_id::NetworkId;
 use common::part::colliders::{RegenerateColliders, PartCollider};
 use common::part::events::{VoxelUpdate, DeletePartCommand};
 use common::part::materials::{Material, MaterialResistances};
+use common::part::voxel_changes::VoxelChanges;
 use common::missile::{Missile, SpawnMissileRequest, SpawnMissileCommand, ExplodeMissileCommand, MissileBundle};
-use common::player::PlayerId;
-use packets::Packet;
-use common::channels::Channel;
+use common::network_message::FromPlayer;
 use common::part::{PartHandle, Parts, VOXEL_SIZE, DeletePart};
+use common::player::PlayerId;
 
 use crate::network_id_generator::NetworkIdGenerator;
-use crate::server_state::ServerState;
+
+// The player that fired a missile, which can only damage constructs that player can build on
+#[derive(Component, Debug)]
+pub struct Shooter(pub PlayerId);
 
 fn spawn_missiles(
-    mut spawn_request_reader: EventReader<SpawnMissileRequest>,
+    mut spawn_request_reader: EventReader<FromPlayer<SpawnMissileRequest>>,
     mut spawn_command_writer: EventWriter<SpawnMissileCommand>,
     mut commands: Commands,
     mut network_id_generator: ResMut<NetworkIdGenerator>,
 ) {
-    for spawn_event in spawn_request_reader.iter() {
+    for FromPlayer { sender, message: spawn_event } in spawn_request_reader.iter() {
         let network_id = network_id_generator.generate();
+        debug!("Player {:?} spawned missile {:?}", sender, network_id);
 
         commands.spawn(MissileBundle {
             missile: Missile::new(5.0),
@@ -34,7 +39,7 @@ fn spawn_missiles(
             velocity: Velocity::linear(spawn_event.velocity),
             collider: Collider::cuboid(0.25, 0.25, 0.25),
             ..Default::default()
-        }).insert(ActiveEvents::COLLISION_EVENTS);
+        }).insert((ActiveEvents::COLLISION_EVENTS, Shooter(*sender)));
 
         spawn_command_writer.send(SpawnMissileCommand {
             transform: spawn_event.transform, 
@@ -44,36 +49,19 @@ fn spawn_missiles(
     }
 }
 
-fn send_spawn_missile_commands(
-    mut server_state: NonSendMut<ServerState>,
-    player_id_query: Query<&PlayerId>,
-    mut spawn_command_reader: EventReader<SpawnMissileCommand>
-) {
-    for spawn_command in spawn_command_reader.iter() {
-        let packet = Packet::from(spawn_command);
-
-        for &player_id in player_id_query.iter() {
-            server_state.send_to_player(
-                player_id,
-                (&packet).into(),
-                Channel::Missile.into(),
-                uflow::SendMode::Reliable
-            );
-        }
-    }
-}
-
 fn explode_missiles(
     rapier_context: Res<RapierContext>,
     material_resistances: Res<MaterialResistances>,
     mut commands: Commands,
     mut collision_events: EventReader<CollisionEvent>,
     sensor_query: Query<&Sensor>,
-    missile_query: Query<&Missile>,
-    part_collider_query: Query<&PartCollider>,
+    missile_query: Query<(&Missile, Option<&Shooter>)>,
+    // Part colliders are children of their construct
+    part_collider_query: Query<(&PartCollider, &Parent)>,
+    access_query: Query<&ConstructAccess>,
     mut part_query_set: ParamSet<(
         Query<(&GlobalTransform, &mut PartHandle)>,
-        Query<&PartHandle>,
+        Query<&mut PartHandle>,
     )>,
     global_transform_query: Query<&GlobalTransform>,
     network_id_query: Query<&NetworkId>,
@@ -83,8 +71,9 @@ fn explode_missiles(
     mut voxel_update_writer: EventWriter<VoxelUpdate>,
     mut delete_part_command_writer: EventWriter<DeletePartCommand>
 ) {
-    let mut affected_parts = HashSet::new();
-    let mut modified_parts = HashSet::new();
+    // The voxels of every part hit this tick, from before any missile hit it
+    let mut original_voxels = HashMap::new();
+    let mut modified_parts = Vec::new();
     let mut deleted_parts = HashSet::new();
 
     let mut exploded_missile_entities: HashSet<Entity> = HashSet::new();
@@ -99,7 +88,7 @@ fn explode_missiles(
                 continue;
             }
 
-            let (missile_entity, missile) = match missile_query.get(*e1) {
+            let (missile_entity, (missile, shooter)) = match missile_query.get(*e1) {
                 Ok(missile) => (*e1, missile),
                 Err(_) => match missile_query.get(*e2) {
                     Ok(missile) => (*e2, missile),
@@ -107,16 +96,24 @@ fn explode_missiles(
                 },
             };
 
-            affected_parts.extend(explode_missile(
+            // Damage changes voxels, so it needs the same access as building
+            let can_damage = |construct: Entity| match (shooter, access_query.get(construct)) {
+                (Some(Shooter(shooter)), Ok(access)) => access.can_build(*shooter),
+                _ => false,
+            };
+
+            explode_missile(
                 missile_entity,
                 missile.power,
+                &can_damage,
                 &rapier_context,
                 &material_resistances,
                 &part_collider_query,
                 &mut part_query_set.p0(),
                 &global_transform_query,
                 &mut parts,
-            ));
+                &mut original_voxels,
+            );
 
             let network_id = network_id_query.get(missile_entity).copied().unwrap();
             explode_missile_command_writer.send(ExplodeMissileCommand { 
@@ -129,23 +126,24 @@ fn explode_missiles(
         }
     }
 
-    let part_query = part_query_set.p1();
+    let mut part_query = part_query_set.p1();
 
-    for affected_part in affected_parts {
-        let part_handle = part_query.get(affected_part).unwrap();
-        let part = parts.get(part_handle).unwrap();
+    for (affected_part, old_voxels) in original_voxels {
+        let mut part_handle = part_query.get_mut(affected_part).unwrap();
+        let part = parts.get_mut(&mut part_handle).unwrap();
         let network_id = network_id_query.get(affected_part).copied().unwrap();
         
         if part.is_empty() {
             deleted_parts.insert((affected_part, network_id));
         } else {
-            modified_parts.insert((affected_part, network_id, Vec::from(part.voxels())));
+            let changes = VoxelChanges::between(&old_voxels, part.voxels());
+            modified_parts.push((affected_part, network_id, part.next_revision(), changes));
         }
     }
 
-    for (entity, network_id, voxels) in modified_parts {
+    for (entity, network_id, revision, changes) in modified_parts {
         regenerate_colliders_writer.send(RegenerateColliders(entity));
-        voxel_update_writer.send(VoxelUpdate { network_id, voxels });
+        voxel_update_writer.send(VoxelUpdate { network_id, revision, changes });
     }
 
     for (entity, network_id) in deleted_parts {
@@ -158,17 +156,17 @@ fn explode_missiles(
 fn explode_missile(
     missile: Entity,
     missile_power: f32,
+    can_damage: &dyn Fn(Entity) -> bool,
     rapier_context: &RapierContext,
     material_resistances: &MaterialResistances,
-    part_collider_query: &Query<&PartCollider>,
+    part_collider_query: &Query<(&PartCollider, &Parent)>,
     voxel_intersection_query: &mut Query<(&GlobalTransform, &mut PartHandle)>,
     global_transform_query: &Query<&GlobalTransform>,
-    parts: &mut Parts
-) -> HashSet<Entity> {
+    parts: &mut Parts,
+    original_voxels: &mut HashMap<Entity, Vec<Material>>,
+) {
     let missile_pos = global_transform_query.get(missile).unwrap().translation();
 
-    let mut affected_parts = HashSet::new();
-
     let points = 5000;
     for i in 0..points {
         // Add an offset to each point to optimize for nearest neighbor distance
@@ -197,7 +195,13 @@ fn explode_missile(
                     &|entity| part_collider_query.get(entity).is_ok()
                 )
             ) {
-                let part_entity = part_collider_query.get(entity).unwrap().part;
+                let (part_collider, construct) = part_collider_query.get(entity).unwrap();
+                let part_entity = part_collider.part;
+
+                // A part the shooter can't damage stops the ray
+                if !can_damage(construct.get()) {
+                    break;
+                }
 
                 if let Ok((&part_transform, mut part_handle)) = voxel_intersection_query.get_mut(part_entity) {
                     let part = parts.get_mut(&mut part_handle).unwrap();
@@ -216,9 +220,8 @@ fn explode_missile(
                         power -= VOXEL_SIZE + material_resistances.get(material);
 
                         if power > 0.0 {
+                            original_voxels.entry(part_entity).or_insert_with(|| part.voxels().to_vec());
                             part.set(ray_pos_part.into(), Material::Empty);
-
-                            affected_parts.insert(part_entity);
                         }
 
                         ray_pos_part += part_space_direction * VOXEL_SIZE;
@@ -239,27 +242,6 @@ fn explode_missile(
             }
         }
     }
-
-    affected_parts
-}
-
-fn send_explode_missile_commands(
-    mut server_state: NonSendMut<ServerState>,
-    player_id_query: Query<&PlayerId>,
-    mut explode_missile_command_reader: EventReader<ExplodeMissileCommand>,
-) {
-    for explode_missile in explode_missile_command_reader.iter() {
-        let packet = Packet::from(explode_missile);
-
-        for &player_id in player_id_query.iter() {
-            server_state.send_to_player(
-                player_id,
-                (&packet).into(),
-                Channel::Missile.into(),
-                uflow::SendMode::Reliable
-            );
-        }
-    }
 }
 
 pub struct ServerMissilePlugin;
@@ -268,9 +250,7 @@ impl Plugin for ServerMissilePlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(FixedUpdate, (
             spawn_missiles,
-            send_spawn_missile_commands.after(spawn_missiles),
             explode_missiles,
-            send_explode_missile_commands.after(explode_missiles),
         ).in_set(FixedUpdateSet::Update));
     }
 }
\ No newline at end of file
@@ -1,15 +1,18 @@
 use bevy::prelude::*;
 use common::entity_lookup::lookup;
-use common::missile::SpawnMissileRequest;
+use common::network_message::{IncomingPackets, OutgoingPackets};
 use common::player::PlayerBundle;
 use uflow::server::Event::*;
 use uflow::server::ErrorType;
 
-use common::part::events::{PlacePartRequest, DeletePartRequest};
-use common::player_connection::{PlayerConnected, PlayerDisconnected};
+use common::player_connection::{PlayerConnected, PlayerDisconnected, InitialStateLoaded};
+use common::protocol::{ClientHello, Handshake, PROTOCOL_FINGERPRINT};
 use packets::{Packet, PacketType};
+use packets::batch::Unbatcher;
+use packets::hex;
 use common::player::{PlayerId, PlayerName};
 
+use crate::player_movement::{LastInput, PendingInputs};
 use crate::server_state::ServerState;
 
 pub fn process_packets(
@@ -17,77 +20,140 @@ pub fn process_packets(
     mut commands: Commands,
     player_entity_query: Query<(Entity, &PlayerId)>,
     player_name_query: Query<&PlayerName>,
-    mut place_part_request_writer: EventWriter<PlacePartRequest>,
-    mut delete_part_request_writer: EventWriter<DeletePartRequest>,
+    mut incoming_packets: ResMut<IncomingPackets>,
     mut client_connected_writer: EventWriter<PlayerConnected>,
     mut client_disconnected_writer: EventWriter<PlayerDisconnected>,
-    mut spawn_missile_writer: EventWriter<SpawnMissileRequest>,
 ) {
     state.server.flush();
 
+    // Players created this step haven't been spawned yet, so their names aren't in the query
+    let mut new_player_names: Vec<PlayerName> = Vec::new();
+
     for event in state.server.step() {
         match event {
             Connect(address) => {
+                // The player is only created once the client's handshake and hello have been checked
                 info!("New incoming connection from {}", address);
-
-                let player_id = state.new_player_id();
-                state.add_client_address(player_id, address);
-
-                let player_name = PlayerName::from("Player".to_string());
-                let player_transform = Transform::from_translation(Vec3::splat(5.0));
-
-                client_connected_writer.send(PlayerConnected {
-                    id: player_id,
-                    name: player_name.clone(),
-                    transform: player_transform,
-                });
-
-                commands.spawn(PlayerBundle {
-                    id: player_id,
-                    name: player_name,
-                    transform: TransformBundle::from(player_transform),
-                    ..Default::default()
-                });
             },
             Disconnect(address) => {
+                state.remove_pending_client(address);
+
                 if let Some(player_id) = state.player_id(address).cloned() {
+                    // Sent even if the player hasn't been spawned yet, so its roles on constructs are always cleared before its ID is reused
+                    state.remove_client_address(player_id);
+                    client_disconnected_writer.send(PlayerDisconnected(player_id));
+
                     if let Some(entity) = lookup(&player_entity_query, &player_id) {
                         let name = player_name_query.get(entity).unwrap();
                         info!("{} disconnected", name);
-                        state.remove_client_address(player_id);
                         commands.entity(entity).despawn();
-
-                        client_disconnected_writer.send(PlayerDisconnected(player_id));
                     }
                 }
             },
-            Receive(_, data) => {
-                match Packet::try_from(data) {
-                    Ok(packet) => {
-                        debug!("Received packet {:?}", packet);
-                        generate_events(
-                            packet,
-                            &mut place_part_request_writer,
-                            &mut delete_part_request_writer,
-                            &mut spawn_missile_writer,
-                        );
-                    },
-                    Err(err) => {
-                        warn!(?err);
-                    }
-                };
+            Receive(address, data) => {
+                // The dump can be decoded with the packet inspector
+                debug!("Received datagram from {}: {}", address, hex::encode(&data));
+
+                // Clients batch their packets, except for the handshake
+                for packet in Unbatcher::new(data) {
+                    match packet {
+                        Ok(packet) => {
+                            if let Some(&player_id) = state.player_id(address) {
+                                // Until the player has loaded the initial state, only its acknowledgement is let through
+                                if state.is_loading(player_id) {
+                                    if packet.packet_type() != PacketType::InitialStateLoaded {
+                                        debug!("Held back {:?} packet from player {:?}, which is still loading", packet.packet_type(), player_id);
+                                        state.hold_packet(player_id, packet);
+                                        continue;
+                                    }
+
+                                    let initial_state_loaded = match check_initial_state_loaded(packet, state.chunks_sent(player_id)) {
+                                        Ok(initial_state_loaded) => initial_state_loaded,
+                                        Err(reason) => {
+                                            warn!("{} failed to load: {}", address, reason);
+                                            state.reject_client(address, reason);
+                                            break;
+                                        }
+                                    };
+
+                                    // They were sent before the acknowledgement, so they are handled before it too
+                                    for held_packet in state.finish_loading(player_id) {
+                                        incoming_packets.push(Some(player_id), held_packet);
+                                    }
+
+                                    incoming_packets.push(Some(player_id), Packet::from(&initial_state_loaded));
+                                    continue;
+                                }
+
+                                incoming_packets.push(Some(player_id), packet);
+                                continue;
+                            }
+
+                            if !state.is_pending(address) {
+                                // The first packet from a new client must be its handshake
+                                if let Err(reason) = check_handshake(packet) {
+                                    warn!("{} failed to connect: {}", address, reason);
+                                    state.reject_client(address, reason);
+                                    break;
+                                }
+
+                                state.add_pending_client(address);
+                                continue;
+                            }
+
+                            // The handshake is followed by the hello, which names the player
+                            let taken_names = player_name_query.iter().chain(new_player_names.iter());
+                            let player_name = match check_hello(packet, taken_names) {
+                                Ok(player_name) => player_name,
+                                Err(reason) => {
+                                    warn!("{} failed to connect: {}", address, reason);
+                                    state.reject_client(address, reason);
+                                    break;
+                                }
+                            };
+
+                            state.remove_pending_client(address);
+                            let player_id = state.new_player_id();
+                            state.add_client_address(player_id, address);
+                            state.start_loading(player_id);
+                            new_player_names.push(player_name.clone());
+
+                            info!("{} connected from {} with ID {:?}", player_name, address, player_id);
+
+                            let player_transform = Transform::from_translation(Vec3::splat(5.0));
+
+                            client_connected_writer.send(PlayerConnected {
+                                id: player_id,
+                                name: player_name.clone(),
+                                transform: player_transform,
+                            });
+
+                            commands.spawn(PlayerBundle {
+                                id: player_id,
+                                name: player_name,
+                                transform: TransformBundle::from(player_transform),
+                                ..Default::default()
+                            }).insert((LastInput::default(), PendingInputs::default()));
+                        },
+                        Err(err) => {
+                            warn!(?err);
+                        }
+                    };
+                }
             },
             Error(address, err) => {
                 match err {
                     ErrorType::Timeout => {
+                        state.remove_pending_client(address);
+
                         if let Some(player_id) = state.player_id(address).cloned() {
+                            state.remove_client_address(player_id);
+                            client_disconnected_writer.send(PlayerDisconnected(player_id));
+
                             if let Some(entity) = lookup(&player_entity_query, &player_id) {
                                 let name = player_name_query.get(entity).unwrap();
                                 error!("{} timed out", name);
-                                state.remove_client_address(player_id);
                                 commands.entity(entity).despawn();
-
-                                client_disconnected_writer.send(PlayerDisconnected(player_id));
                             }
                         }
                     },
@@ -106,47 +172,121 @@ pub fn process_packets(
     }
 }
 
-fn generate_events(
-    packet: Packet,
-    place_part_writer: &mut EventWriter<PlacePartRequest>,
-    delete_part_writer: &mut EventWriter<DeletePartRequest>,
-    spawn_missile_writer: &mut EventWriter<SpawnMissileRequest>,
+fn check_handshake(packet: Packet) -> Result<(), String> {
+    if !matches!(packet.packet_type(), PacketType::Handshake) {
+        return Err(format!("expected a handshake, received {:?}", packet.packet_type()));
+    }
+
+    let handshake = Handshake::try_from(packet)
+        .map_err(|err| format!("malformed handshake: {}", err))?;
+
+    if handshake.fingerprint != PROTOCOL_FINGERPRINT {
+        return Err(format!(
+            "protocol mismatch (server {:016x}, client {:016x}), the client and server were built from different versions",
+            PROTOCOL_FINGERPRINT,
+            handshake.fingerprint
+        ));
+    }
+
+    Ok(())
+}
+
+fn check_hello<'a>(packet: Packet, mut taken_names: impl Iterator<Item = &'a PlayerName>) -> Result<PlayerName, String> {
+    if !matches!(packet.packet_type(), PacketType::ClientHello) {
+        return Err(format!("expected a hello, received {:?}", packet.packet_type()));
+    }
+
+    let hello = ClientHello::try_from(packet)
+        .map_err(|err| format!("malformed hello: {}", err))?;
+
+    hello.name.validate()
+        .map_err(|reason| format!("invalid name: {}", reason))?;
+
+    if taken_names.any(|name| hello.name.is_taken_by(name)) {
+        return Err(format!("the name {} is already taken", hello.name));
+    }
+
+    Ok(hello.name)
+}
+
+fn check_initial_state_loaded(packet: Packet, chunks_sent: Option<u32>) -> Result<InitialStateLoaded, String> {
+    let initial_state_loaded = InitialStateLoaded::try_from(packet)
+        .map_err(|err| format!("malformed initial state acknowledgement: {}", err))?;
+
+    let Some(chunks_sent) = chunks_sent else {
+        return Err("acknowledged the initial state before its last chunk was sent".to_string());
+    };
+
+    if initial_state_loaded.chunk_count != chunks_sent {
+        return Err(format!(
+            "loaded {} initial state chunks, but {} were sent",
+            initial_state_loaded.chunk_count,
+            chunks_sent
+        ));
+    }
+
+    Ok(initial_state_loaded)
+}
+
+pub fn send_packets(
+    mut state: NonSendMut<ServerState>,
+    mut outgoing_packets: ResMut<OutgoingPackets>,
+    player_id_query: Query<&PlayerId>,
 ) {
-    match packet.packet_type() {
-        PacketType::PlacePart => {
-            match PlacePartRequest::try_from(packet) {
-                Ok(place_part_request) => {
-                    place_part_writer.send(place_part_request);
-                },
-                Err(err) => {
-                    warn!(?err);
-                }
-            }
-        },
-        PacketType::DeletePart => {
-            match DeletePartRequest::try_from(packet) {
-                Ok(delete_part_request) => {
-                    delete_part_writer.send(delete_part_request);
-                },
-                Err(err) => {
-                    warn!(?err);
-                }
-            }
-        },
-        PacketType::InitialState => {},
-        PacketType::PlayerConnected => {},
-        PacketType::PlayerDisconnected => {},
-        PacketType::VoxelUpdate => {},
-        PacketType::SpawnMissile => {
-            match SpawnMissileRequest::try_from(packet) {
-                Ok(spawn_missile_request) => {
-                    spawn_missile_writer.send(spawn_missile_request);
-                },
-                Err(err) => {
-                    warn!(?err);
-                }
+    for outgoing_packet in outgoing_packets.drain() {
+        for &player_id in player_id_query.iter() {
+            if outgoing_packet.recipients.includes(player_id) {
+                state.send_to_player(player_id, &outgoing_packet.packet, outgoing_packet.channel);
             }
-        },
-        PacketType::ExplodeMissile => {},
+        }
+    }
+
+    // Each player gets at most a few datagrams per channel each tick
+    state.send_batches();
+}
+
+#[cfg(test)]
+mod tests {
+    use common::player::PlayerName;
+    use common::player_connection::InitialStateLoaded;
+    use common::protocol::{ClientHello, Handshake};
+    use packets::Packet;
+
+    use super::{check_hello, check_initial_state_loaded};
+
+    fn hello(name: &str) -> Packet {
+        Packet::from(&ClientHello { name: PlayerName::from(name.to_string()) })
+    }
+
+    #[test]
+    fn valid_hello_names_player() {
+        let name = check_hello(hello("Player"), std::iter::empty()).unwrap();
+
+        assert_eq!(name.as_str(), "Player");
+    }
+
+    #[test]
+    fn taken_name_is_rejected() {
+        let taken_names = [PlayerName::from("player".to_string())];
+
+        assert!(check_hello(hello("Player"), taken_names.iter()).is_err());
+        assert!(check_hello(hello("Player 2"), taken_names.iter()).is_ok());
+    }
+
+    #[test]
+    fn second_handshake_is_rejected() {
+        let packet = Packet::from(&Handshake::default());
+
+        assert!(check_hello(packet, std::iter::empty()).is_err());
+    }
+
+    #[test]
+    fn initial_state_loaded_must_count_every_chunk() {
+        let packet = Packet::from(&InitialStateLoaded { chunk_count: 3 });
+
+        assert!(check_initial_state_loaded(packet.clone(), Some(3)).is_ok());
+        assert!(check_initial_state_loaded(packet.clone(), Some(4)).is_err());
+        // The last chunk hasn't even been sent
+        assert!(check_initial_state_loaded(packet, None).is_err());
     }
 }
\ No newline at end of file
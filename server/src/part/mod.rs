@@ -2,22 +2,22 @@ use bevy::ecs::system::Command;
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 use bevy_rapier3d::prelude::systems::init_colliders;
+use common::construct_access::ConstructAccess;
 use common::entity_lookup::{lookup_exclusive, lookup};
 use common::fixed_update::FixedUpdateSet;
 use common::part::colliders::{PartCollider, RegenerateColliders};
-use common::player::PlayerId;
 use common::ship::Ship;
-use uflow::SendMode;
 
 use common::part::colliders::{ColliderData, generate_collider_data};
-use common::channels::Channel;
-use common::part::events::{PlacePartRequest, PlacePartCommand, DeletePartRequest, DeletePartCommand, VoxelUpdate};
+use common::part::events::{PlacePartRequest, PlacePartCommand, DeletePartRequest, DeletePartCommand, DeleteVoxelRequest, VoxelUpdate, BuildRequestRejected, BuildRejectionReason, FullVoxelsRequest, FullVoxelsCommand};
+use common::part::voxel_changes::VoxelChanges;
 use common::network_id::NetworkId;
-use packets::Packet;
-use common::part::{Parts, PartHandle, DeletePart};
+use common::network_message::FromPlayer;
+use common::part::{Parts, PartHandle, DeletePart, MAX_BUILD_DISTANCE};
+use common::part::materials::Material;
+use common::player::PlayerId;
 
 use crate::network_id_generator::NetworkIdGenerator;
-use crate::server_state::ServerState;
 
 pub fn spawn_part(
     commands: &mut Commands,
@@ -74,145 +74,253 @@ pub fn spawn_part_exclusive(
     part_entity
 }
 
+// Returns the construct the part goes on, the part's handle and its colliders if the request can be carried out
+fn check_place_part_request(
+    world: &mut World,
+    sender: PlayerId,
+    place_part_request: &PlacePartRequest,
+) -> Result<(Entity, PartHandle, Vec<ColliderData>), BuildRejectionReason> {
+    // Only players that are in the game can build
+    let mut player_query = world.query::<(Entity, &PlayerId)>();
+    let player = lookup_exclusive(world, &mut player_query, &sender)
+        .ok_or(BuildRejectionReason::NoPermission)?;
+    let player_translation = world.get::<Transform>(player)
+        .ok_or(BuildRejectionReason::NoPermission)?
+        .translation;
+
+    let mut construct_query = world.query_filtered::<(Entity, &NetworkId), With<Ship>>();
+    let construct = lookup_exclusive(
+        world,
+        &mut construct_query,
+        &place_part_request.construct_network_id
+    ).ok_or(BuildRejectionReason::UnknownConstruct)?;
+
+    let can_build = world.get::<ConstructAccess>(construct)
+        .is_some_and(|access| access.can_build(sender));
+    if !can_build {
+        return Err(BuildRejectionReason::NoPermission);
+    }
+
+    let construct_transform = world.get::<GlobalTransform>(construct)
+        .ok_or(BuildRejectionReason::UnknownConstruct)?;
+    let part_transform = Transform::from(place_part_request.part_transform);
+    let (_, part_global_rotation, part_global_translation) = construct_transform.mul_transform(part_transform).to_scale_rotation_translation();
+
+    if player_translation.distance(part_global_translation) > MAX_BUILD_DISTANCE {
+        return Err(BuildRejectionReason::OutOfRange);
+    }
+
+    let parts = world.resource::<Parts>();
+    let part = parts.get_part_from_id(place_part_request.part_id)
+        .ok_or(BuildRejectionReason::UnknownPart)?;
+
+    // Prevent parts from being placed inside of each other
+    let part_half_extents = part.center() - Vec3::splat(0.01);
+    let rapier_context = world.resource::<RapierContext>();
+
+    if rapier_context.cast_shape(
+        part_global_translation,
+        part_global_rotation,
+        // Velocity needs to be > 0 or else the shape cast will ignore colliders
+        Vec3::splat(0.001),
+        &Collider::cuboid(
+            part_half_extents.x,
+            part_half_extents.y,
+            part_half_extents.z
+        ),
+        0.01,
+        QueryFilter::default()
+    ).is_some() {
+        return Err(BuildRejectionReason::Overlap);
+    }
+
+    let colliders = generate_collider_data(part, part_transform);
+
+    Ok((construct, parts.get_handle(place_part_request.part_id), colliders))
+}
+
 fn confirm_place_part_requests(
     world: &mut World,
 ) {
-    let place_part_requests: Vec<PlacePartRequest> = world.get_resource_mut::<Events<PlacePartRequest>>()
-        .unwrap()
+    let place_part_requests: Vec<FromPlayer<PlacePartRequest>> = world.resource_mut::<Events<FromPlayer<PlacePartRequest>>>()
         .drain()
         .collect();
 
-    for place_part_request in place_part_requests {
-        let mut construct_query = world.query_filtered::<(Entity, &NetworkId), With<Ship>>();
-        let construct = lookup_exclusive(
-            world,
-            &mut construct_query,
-            &place_part_request.construct_network_id
-        ).unwrap();
+    for FromPlayer { sender, message: place_part_request } in place_part_requests {
+        let (construct, part_handle, colliders) = match check_place_part_request(world, sender, &place_part_request) {
+            Ok(checked) => checked,
+            Err(reason) => {
+                debug!("Rejected part placement {} from player {:?}: {:?}", place_part_request.request_seq, sender, reason);
+
+                world.send_event(BuildRequestRejected {
+                    requester: sender,
+                    request_seq: place_part_request.request_seq,
+                    reason,
+                });
+                continue;
+            }
+        };
 
-        let construct_transform = world.get::<GlobalTransform>(construct).unwrap();
         let part_transform = Transform::from(place_part_request.part_transform);
-        let (_, part_global_rotation, part_global_translation) = construct_transform.mul_transform(part_transform).to_scale_rotation_translation();
+        let network_id = world.resource_mut::<NetworkIdGenerator>().generate();
 
-        let (part_handle, part_center) = {
-            let parts = world.get_resource::<Parts>().unwrap();
-            let part_handle = parts.get_handle(place_part_request.part_id);
-            let part_center = parts.get(&part_handle).unwrap().center();
-            (part_handle, part_center)
-        };
+        spawn_part_exclusive(world, part_handle, part_transform, network_id, construct, colliders);
 
-        // Prevent parts from being placed inside of each other
-        let part_half_extents = part_center - Vec3::splat(0.01);
-        let rapier_context = world.get_resource::<RapierContext>().unwrap();
-
-        if rapier_context.cast_shape(
-            part_global_translation,
-            part_global_rotation,
-            // Velocity needs to be > 0 or else the shape cast will ignore colliders
-            Vec3::splat(0.001),
-            &Collider::cuboid(
-                part_half_extents.x,
-                part_half_extents.y,
-                part_half_extents.z
-            ),
-            0.01,
-            QueryFilter::default()
-        ).is_none() {
-            let network_id = world.get_resource_mut::<NetworkIdGenerator>().unwrap().generate();
-            let colliders = {
-                let parts = world.get_resource::<Parts>().unwrap();
-                let part = parts.get(&part_handle).unwrap();
-                generate_collider_data(part, part_transform)
-            };
-
-            spawn_part_exclusive(world, part_handle, part_transform, network_id, construct, colliders);
-
-            let mut place_part_events = world.get_resource_mut::<Events<PlacePartCommand>>().unwrap();
-            place_part_events.send(PlacePartCommand {
-                part_id: place_part_request.part_id,
-                part_network_id: network_id,
-                transform: place_part_request.part_transform,
-                construct_network_id: place_part_request.construct_network_id
-            });
+        world.send_event(PlacePartCommand {
+            part_id: place_part_request.part_id,
+            part_network_id: network_id,
+            transform: place_part_request.part_transform,
+            construct_network_id: place_part_request.construct_network_id,
+            requester: sender,
+            request_seq: place_part_request.request_seq,
+        });
 
-            // Update colliders in Rapier
-            Schedule::new().add_systems(init_colliders).run(world);
-            world.resource_scope(|_, mut rapier_context: Mut<RapierContext>| {
-                rapier_context.update_query_pipeline();
-            });
-        }
+        // Update colliders in Rapier
+        Schedule::new().add_systems(init_colliders).run(world);
+        world.resource_scope(|_, mut rapier_context: Mut<RapierContext>| {
+            rapier_context.update_query_pipeline();
+        });
+    }
+}
+
+// Finds the part if the sender can build on the construct it is on
+fn check_part_access(
+    sender: PlayerId,
+    part_network_id: &NetworkId,
+    network_id_query: &Query<(Entity, &NetworkId), With<PartHandle>>,
+    parent_query: &Query<&Parent>,
+    construct_query: &Query<&ConstructAccess, With<Ship>>,
+) -> Result<Entity, BuildRejectionReason> {
+    let part = lookup(network_id_query, part_network_id)
+        .ok_or(BuildRejectionReason::UnknownPart)?;
+
+    let access = parent_query.get(part).ok()
+        .and_then(|parent| construct_query.get(parent.get()).ok())
+        .ok_or(BuildRejectionReason::UnknownConstruct)?;
+
+    if !access.can_build(sender) {
+        return Err(BuildRejectionReason::NoPermission);
     }
+
+    Ok(part)
 }
 
 fn confirm_delete_part_requests(
     mut commands: Commands,
-    mut delete_part_request_reader: EventReader<DeletePartRequest>,
+    mut delete_part_request_reader: EventReader<FromPlayer<DeletePartRequest>>,
     mut send_delete_part_writer: EventWriter<DeletePartCommand>,
-    network_id_query: Query<(Entity, &NetworkId), With<PartHandle>>
+    mut build_request_rejected_writer: EventWriter<BuildRequestRejected>,
+    network_id_query: Query<(Entity, &NetworkId), With<PartHandle>>,
+    parent_query: Query<&Parent>,
+    construct_query: Query<&ConstructAccess, With<Ship>>,
 ) {
-    for delete_part_request in delete_part_request_reader.iter() {
-        if let Some(part) = lookup(&network_id_query, &delete_part_request.0) {
-            commands.add(DeletePart(part));
+    for FromPlayer { sender, message: delete_part_request } in delete_part_request_reader.iter() {
+        let part = match check_part_access(*sender, &delete_part_request.part_network_id, &network_id_query, &parent_query, &construct_query) {
+            Ok(part) => part,
+            Err(reason) => {
+                debug!("Rejected part deletion {} from player {:?}: {:?}", delete_part_request.request_seq, sender, reason);
 
-            send_delete_part_writer.send(DeletePartCommand(delete_part_request.0));
-        }
+                build_request_rejected_writer.send(BuildRequestRejected {
+                    requester: *sender,
+                    request_seq: delete_part_request.request_seq,
+                    reason,
+                });
+                continue;
+            }
+        };
+
+        debug!("Player {:?} deleted part {:?}", sender, delete_part_request.part_network_id);
+        commands.add(DeletePart(part));
+
+        send_delete_part_writer.send(DeletePartCommand(delete_part_request.part_network_id));
     }
 }
 
-fn send_place_part_commands(
-    mut server_state: NonSendMut<ServerState>,
-    player_id_query: Query<&PlayerId>,
-    mut send_place_part_reader: EventReader<PlacePartCommand>
+fn confirm_delete_voxel_requests(
+    mut commands: Commands,
+    mut delete_voxel_request_reader: EventReader<FromPlayer<DeleteVoxelRequest>>,
+    mut voxel_update_writer: EventWriter<VoxelUpdate>,
+    mut send_delete_part_writer: EventWriter<DeletePartCommand>,
+    mut regenerate_colliders_writer: EventWriter<RegenerateColliders>,
+    mut build_request_rejected_writer: EventWriter<BuildRequestRejected>,
+    network_id_query: Query<(Entity, &NetworkId), With<PartHandle>>,
+    parent_query: Query<&Parent>,
+    construct_query: Query<&ConstructAccess, With<Ship>>,
+    mut part_handle_query: Query<&mut PartHandle>,
+    mut parts: ResMut<Parts>,
 ) {
-    for place_part_command in send_place_part_reader.iter() {
-        let packet = Packet::from(place_part_command);
-
-        for &player_id in player_id_query.iter() {
-            server_state.send_to_player(
-                player_id,
-                (&packet).into(),
-                Channel::PartCommands.into(),
-                SendMode::Reliable
-            );
+    for FromPlayer { sender, message: delete_voxel_request } in delete_voxel_request_reader.iter() {
+        let checked = check_part_access(*sender, &delete_voxel_request.part_network_id, &network_id_query, &parent_query, &construct_query)
+            .and_then(|part_entity| {
+                let mut part_handle = part_handle_query.get_mut(part_entity)
+                    .map_err(|_| BuildRejectionReason::UnknownPart)?;
+                let part = parts.get_mut(&mut part_handle)
+                    .ok_or(BuildRejectionReason::UnknownPart)?;
+
+                if !part.contains(delete_voxel_request.voxel_pos) {
+                    return Err(BuildRejectionReason::UnknownVoxel);
+                }
+
+                Ok((part_entity, part))
+            });
+
+        let (part_entity, part) = match checked {
+            Ok(checked) => checked,
+            Err(reason) => {
+                debug!("Rejected voxel deletion {} from player {:?}: {:?}", delete_voxel_request.request_seq, sender, reason);
+
+                build_request_rejected_writer.send(BuildRequestRejected {
+                    requester: *sender,
+                    request_seq: delete_voxel_request.request_seq,
+                    reason,
+                });
+                continue;
+            }
+        };
+
+        let old_voxels = part.voxels().to_vec();
+        part.set(delete_voxel_request.voxel_pos, Material::Empty);
+
+        if part.voxels() == old_voxels.as_slice() {
+            continue;
         }
-    }
-}
 
-fn send_delete_part_commands(
-    mut server_state: NonSendMut<ServerState>,
-    player_id_query: Query<&PlayerId>,
-    mut send_delete_part_reader: EventReader<DeletePartCommand>
-) {
-    for delete_part_command in send_delete_part_reader.iter() {
-        let packet = Packet::from(delete_part_command);
-
-        for &player_id in player_id_query.iter() {
-            server_state.send_to_player(
-                player_id,
-                (&packet).into(),
-                Channel::PartCommands.into(),
-                SendMode::Reliable
-            );
+        let network_id = delete_voxel_request.part_network_id;
+
+        if part.is_empty() {
+            commands.add(DeletePart(part_entity));
+            send_delete_part_writer.send(DeletePartCommand(network_id));
+        } else {
+            let changes = VoxelChanges::between(&old_voxels, part.voxels());
+            voxel_update_writer.send(VoxelUpdate { network_id, revision: part.next_revision(), changes });
+            regenerate_colliders_writer.send(RegenerateColliders(part_entity));
         }
     }
 }
 
-fn send_voxel_updates(
-    mut server_state: NonSendMut<ServerState>,
-    player_id_query: Query<&PlayerId>,
-    mut voxel_update_reader: EventReader<VoxelUpdate>,
+fn send_full_voxels(
+    mut full_voxels_request_reader: EventReader<FromPlayer<FullVoxelsRequest>>,
+    mut full_voxels_writer: EventWriter<FullVoxelsCommand>,
+    network_id_query: Query<(Entity, &NetworkId), With<PartHandle>>,
+    part_handle_query: Query<&PartHandle>,
+    parts: Res<Parts>,
 ) {
-    for voxel_update in voxel_update_reader.iter() {
-        let packet = Packet::from(voxel_update);
-
-        for &player_id in player_id_query.iter() {
-            server_state.send_to_player(
-                player_id,
-                (&packet).into(),
-                Channel::PartCommands.into(),
-                SendMode::Reliable,
-            );
-        }
+    for FromPlayer { sender, message: FullVoxelsRequest(network_id) } in full_voxels_request_reader.iter() {
+        // The part may have been deleted since, which the client finds out about from the DeletePartCommand
+        let Some(part) = lookup(&network_id_query, network_id)
+            .and_then(|entity| part_handle_query.get(entity).ok())
+            .and_then(|part_handle| parts.get(part_handle)) else {
+            continue;
+        };
+
+        debug!("Sending all voxels of part {:?} to player {:?}", network_id, sender);
+
+        full_voxels_writer.send(FullVoxelsCommand {
+            requester: *sender,
+            network_id: *network_id,
+            revision: part.revision(),
+            changes: VoxelChanges::full(part.voxels()),
+        });
     }
 }
 
@@ -226,7 +334,10 @@ fn regenerate_colliders(
     parts: Res<Parts>
 ) {
     for request in regenerate_colliders_reader.iter() {
-        let (part_handle, transform) = part_query.get(request.0).unwrap();
+        // The part can be deleted by a player in the same tick a missile hits it
+        let Ok((part_handle, transform)) = part_query.get(request.0) else {
+            continue;
+        };
         let part = parts.get(&part_handle).unwrap();
 
         if let Ok(parent) = parent_query.get(request.0) {
@@ -261,10 +372,9 @@ impl Plugin for ServerPartPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(FixedUpdate, (
             confirm_place_part_requests,
-            send_place_part_commands.after(confirm_place_part_requests),
             confirm_delete_part_requests,
-            send_delete_part_commands.after(confirm_delete_part_requests),
-            send_voxel_updates,
+            confirm_delete_voxel_requests,
+            send_full_voxels,
             regenerate_colliders,
         ).in_set(FixedUpdateSet::Update));
     }
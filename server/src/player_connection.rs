@@ -1,119 +1,198 @@
+use std::collections::VecDeque;
+
 use bevy::prelude::*;
-use common::fixed_update::{AddFixedEvent, FixedUpdateSet};
+use common::fixed_update::{FixedUpdateSet, NetworkSendSet};
+use common::network_message::FromPlayer;
 use common::ship::Ship;
-use uflow::SendMode;
 
-use common::channels::Channel;
-use common::player_connection::{PlayerConnected, PlayerDisconnected, InitialState};
+use common::player_connection::{PlayerConnected, InitialState, InitialStateChunk, InitialStateLoaded};
 use common::network_id::NetworkId;
 use common::part::{PartHandle, Parts, PartNetworkRepr};
 use common::compact_transform::CompactTransform;
-use packets::Packet;
+use common::construct_access::ConstructAccess;
 use common::player::{PlayerId, PlayerName};
+use packets::{Packet, PacketSerialize, PacketType};
 
 use crate::server_state::ServerState;
 
-fn send_player_connected(
-    ship_query: Query<(Entity, &NetworkId, &Transform), &Ship>,
+// Chunks are filled up to this many bytes of parts, so that each one fits in a batch
+// A part that is bigger than this on its own is sent in a chunk by itself
+const MAX_CHUNK_SIZE: usize = 1000;
+// How many chunks each loading player is sent per tick
+const CHUNKS_PER_TICK: usize = 4;
+
+// The parts that still have to be sent to a loading player, one construct after another
+#[derive(Component, Debug)]
+pub struct InitialStateStream {
+    parts: VecDeque<(NetworkId, Entity)>,
+    chunks_sent: u32,
+}
+
+type PartEntry = (PartNetworkRepr, CompactTransform, NetworkId);
+
+fn encoded_size(part_entry: &PartEntry) -> usize {
+    let mut packet = Packet::new(PacketType::InitialStateChunk);
+    part_entry.serialize(&mut packet);
+
+    // Without the packet type
+    Box::<[u8]>::from(&packet).len() - 1
+}
+
+fn part_network_repr(parts: &Parts, part_handle: &PartHandle) -> Option<PartNetworkRepr> {
+    match parts.get(part_handle) {
+        Some(part) => match part.parent_part_id() {
+            Some(_) => Some(PartNetworkRepr::Child(part.clone())),
+            None => Some(PartNetworkRepr::Predefined(part_handle.id())),
+        },
+        None => {
+            warn!("Attempted to send non-existent part with ID {:?} to new player!", part_handle.id());
+            None
+        }
+    }
+}
+
+// The parts are sent as they are now, rather than when the player connected, so later part commands apply on top of them
+fn next_chunk(
+    stream: &mut InitialStateStream,
+    player_id: PlayerId,
+    parts: &Parts,
+    part_query: &Query<(&PartHandle, &Transform, &NetworkId)>,
+) -> InitialStateChunk {
+    let mut part_data: Vec<PartEntry> = Vec::new();
+    let mut chunk_size = 0;
+    let mut construct_network_id = None;
+
+    while let Some((part_construct_network_id, part_entity)) = stream.parts.pop_front() {
+        // Each chunk only holds parts of one construct
+        if *construct_network_id.get_or_insert(part_construct_network_id) != part_construct_network_id {
+            stream.parts.push_front((part_construct_network_id, part_entity));
+            break;
+        }
+
+        // Parts deleted since the player connected are left out
+        let Ok((part_handle, transform, network_id)) = part_query.get(part_entity) else {
+            continue;
+        };
+
+        let Some(part_network_repr) = part_network_repr(parts, part_handle) else {
+            continue;
+        };
+
+        let part_entry = (part_network_repr, CompactTransform::from(*transform), *network_id);
+        let part_size = encoded_size(&part_entry);
+
+        if !part_data.is_empty() && chunk_size + part_size > MAX_CHUNK_SIZE {
+            stream.parts.push_front((part_construct_network_id, part_entity));
+            break;
+        }
+
+        chunk_size += part_size;
+        part_data.push(part_entry);
+    }
+
+    InitialStateChunk {
+        player_id,
+        construct_network_id,
+        parts: part_data,
+        last: stream.parts.is_empty(),
+    }
+}
+
+// Runs after the tick's part changes have been applied, so the parts that are streamed include them
+fn send_initial_state(
+    mut commands: Commands,
+    ship_query: Query<(Entity, &NetworkId, &Transform, &ConstructAccess), &Ship>,
     mut player_connected_reader: EventReader<PlayerConnected>,
-    player_id_query: Query<&PlayerId>,
-    player_query: Query<(&PlayerId, &PlayerName, &Transform)>,
-    mut server_state: NonSendMut<ServerState>,
-    parts: Res<Parts>,
-    part_query: Query<(&PartHandle, &Transform, &NetworkId)>,
+    player_query: Query<(Entity, &PlayerId, &PlayerName, &Transform)>,
+    mut initial_state_writer: EventWriter<InitialState>,
+    part_query: Query<(), With<PartHandle>>,
     ship_children_query: Query<&Children>,
 ) {
-    for (ship, ship_network_id, ship_transform) in ship_query.iter() {
-        for player_connected in player_connected_reader.iter() {
-            // Send new player connected packet to existing players
-            let player_connected_packet = Packet::from(player_connected);
-            
-            for &player_id in player_id_query.iter() {
-                if player_id != player_connected.id {
-                    server_state.send_to_player(
-                        player_id,
-                        (&player_connected_packet).into(),
-                        Channel::PlayerConnectionEvents.into(),
-                        SendMode::Reliable
-                    );
-                }
-            }
+    for player_connected in player_connected_reader.iter() {
+        // Send the current state of the world to the new player
+        let players: Vec<(PlayerId, PlayerName, Transform)> = player_query.iter()
+            .map(|(_, player_id, player_name, transform)| (*player_id, player_name.clone(), *transform))
+            .collect();
 
-            // Send the current state of the world to the new player
-            let players: Vec<(PlayerId, PlayerName, Transform)> = player_query.iter()
-                .map(|(player_id, player_name, transform)| (*player_id, player_name.clone(), *transform))
-                .collect();
-
-            let mut part_data: Vec<(PartNetworkRepr, CompactTransform, NetworkId)> = Vec::new();
-            let ship_children = ship_children_query.get(ship).unwrap();
-            for &child in ship_children {
-                if let Ok((part_handle, transform, network_id)) = part_query.get(child) {
-                    let part_network_repr = match parts.get(part_handle) {
-                        Some(part) => match part.parent_part_id() {
-                            Some(_) => PartNetworkRepr::Child(part.clone()),
-                            None => PartNetworkRepr::Predefined(part_handle.id()),
-                        },
-                        None => {
-                            warn!("Attempted to send non-existent part with ID {:?} to new player!", part_handle.id());
-                            continue;
-                        }
-                    };
-    
-                    let part_transform = CompactTransform::from(*transform);
-    
-                    part_data.push((part_network_repr, part_transform, *network_id));
-                }
+        let Some((player_entity, ..)) = player_query.iter().find(|(_, player_id, ..)| **player_id == player_connected.id) else {
+            continue;
+        };
+
+        let mut constructs = Vec::new();
+        let mut parts = VecDeque::new();
+
+        for (ship, ship_network_id, ship_transform, ship_access) in ship_query.iter() {
+            constructs.push((*ship_network_id, CompactTransform::from(*ship_transform), ship_access.clone()));
+
+            // Constructs without parts have no children
+            if let Ok(children) = ship_children_query.get(ship) {
+                parts.extend(children.iter()
+                    .copied()
+                    .filter(|&child| part_query.contains(child))
+                    .map(|child| (*ship_network_id, child)));
             }
+        }
+
+        info!("Sending initial state with {} constructs and {} parts to {:?}", constructs.len(), parts.len(), player_connected.id);
 
-            let initial_state = InitialState {
-                player_id: player_connected.id,
-                players,
-                construct_network_id: *ship_network_id,
-                parts: part_data,
-                construct_transform: CompactTransform::from(*ship_transform)
-            };
-            let initial_state_packet = Packet::from(&initial_state);
-            
-            info!("Sending initial state to {:?}", player_connected.id);
-
-            server_state.send_to_player(
-                player_connected.id,
-                (&initial_state_packet).into(),
-                Channel::PartCommands.into(),
-                SendMode::Reliable
-            );
+        initial_state_writer.send(InitialState {
+            player_id: player_connected.id,
+            players,
+            constructs,
+            part_count: parts.len() as u32,
+        });
+
+        // The chunks start on the next tick, since messages of different types sent in the same tick can arrive in any order
+        // The last chunk is sent even if there are no parts, since the player waits for it
+        commands.entity(player_entity).insert(InitialStateStream { parts, chunks_sent: 0 });
+    }
+}
+
+fn stream_initial_state(
+    mut commands: Commands,
+    mut stream_query: Query<(Entity, &PlayerId, &mut InitialStateStream)>,
+    mut chunk_writer: EventWriter<InitialStateChunk>,
+    mut state: NonSendMut<ServerState>,
+    parts: Res<Parts>,
+    part_query: Query<(&PartHandle, &Transform, &NetworkId)>,
+) {
+    for (player_entity, player_id, mut stream) in stream_query.iter_mut() {
+        for _ in 0..CHUNKS_PER_TICK {
+            let chunk = next_chunk(&mut stream, *player_id, &parts, &part_query);
+            let last = chunk.last;
+
+            chunk_writer.send(chunk);
+            stream.chunks_sent += 1;
+
+            if last {
+                // The player's acknowledgement has to account for every chunk
+                state.set_chunks_sent(*player_id, stream.chunks_sent);
+                commands.entity(player_entity).remove::<InitialStateStream>();
+                break;
+            }
         }
     }
 }
 
-fn send_player_disconnected(
-    mut player_disconnected_reader: EventReader<PlayerDisconnected>,
-    player_id_query: Query<&PlayerId>,
-    mut server_state: NonSendMut<ServerState>
+fn player_loaded(
+    mut initial_state_loaded_reader: EventReader<FromPlayer<InitialStateLoaded>>,
+    player_query: Query<(&PlayerId, &PlayerName)>,
 ) {
-    for disconnected_player in player_disconnected_reader.iter() {
-        let packet = Packet::from(disconnected_player);
-
-        for &player_id in player_id_query.iter() {
-            server_state.send_to_player(
-                player_id,
-                (&packet).into(),
-                Channel::PlayerConnectionEvents.into(),
-                SendMode::Reliable
-            );
+    for FromPlayer { sender, .. } in initial_state_loaded_reader.iter() {
+        if let Some((_, name)) = player_query.iter().find(|(player_id, _)| *player_id == sender) {
+            info!("{} loaded the initial state", name);
         }
     }
 }
 
-pub struct PlayerConnectionPlugin;
+pub struct ServerPlayerConnectionPlugin;
 
-impl Plugin for PlayerConnectionPlugin {
+impl Plugin for ServerPlayerConnectionPlugin {
     fn build(&self, app: &mut App) {
-        app.add_fixed_event::<PlayerConnected>()
-            .add_fixed_event::<PlayerDisconnected>()
+        app.add_systems(FixedUpdate, player_loaded.in_set(FixedUpdateSet::Update))
             .add_systems(FixedUpdate, (
-                send_player_connected,
-                send_player_disconnected,
-            ).in_set(FixedUpdateSet::Update));
+                send_initial_state,
+                stream_initial_state,
+            ).in_set(FixedUpdateSet::PostUpdate).before(NetworkSendSet));
     }
 }
\ No newline at end of file
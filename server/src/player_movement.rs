@@ -0,0 +1,91 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use common::compact_transform::CompactTransform;
+use common::entity_lookup::lookup;
+use common::fixed_update::{FixedTick, FixedUpdateSet, NetworkReceiveSet, NetworkSendSet};
+use common::network_message::FromPlayer;
+use common::player::PlayerId;
+use common::player_movement::{PlayerInput, PlayerState, PlayerStates};
+
+// The sequence number of the newest input applied to a player
+#[derive(Component, Debug, Default)]
+pub struct LastInput(pub u32);
+
+// How many received inputs a player can have waiting, the oldest are dropped beyond this
+const MAX_PENDING_INPUTS: usize = 8;
+
+// Inputs that have arrived but not been applied yet, oldest first, as (sequence, impulse, torque impulse)
+#[derive(Component, Debug, Default)]
+pub struct PendingInputs(VecDeque<(u32, Vec3, Vec3)>);
+
+fn queue_player_input(
+    mut player_input_reader: EventReader<FromPlayer<PlayerInput>>,
+    player_entity_query: Query<(Entity, &PlayerId)>,
+    mut player_query: Query<(&LastInput, &mut PendingInputs)>,
+) {
+    for FromPlayer { sender, message: input } in player_input_reader.iter() {
+        let Some(entity) = lookup(&player_entity_query, sender) else { continue };
+        let Ok((last_input, mut pending_inputs)) = player_query.get_mut(entity) else { continue };
+
+        // The channel is unreliable, so an input that arrives after a newer one is stale
+        let newest = pending_inputs.0.back().map_or(last_input.0, |(sequence, ..)| *sequence);
+        if input.sequence <= newest {
+            continue;
+        }
+
+        if pending_inputs.0.len() == MAX_PENDING_INPUTS {
+            pending_inputs.0.pop_front();
+        }
+
+        let (impulse, torque_impulse) = input.clamped();
+        pending_inputs.0.push_back((input.sequence, impulse, torque_impulse));
+    }
+}
+
+// Inputs move the player that sent them
+// Only one is applied each tick, like on the client, so sending more of them doesn't make a player faster
+fn apply_player_input(
+    mut player_query: Query<(&mut ExternalImpulse, &mut LastInput, &mut PendingInputs)>,
+) {
+    for (mut external_impulse, mut last_input, mut pending_inputs) in player_query.iter_mut() {
+        let Some((sequence, impulse, torque_impulse)) = pending_inputs.0.pop_front() else { continue };
+
+        external_impulse.impulse += impulse;
+        external_impulse.torque_impulse += torque_impulse;
+        last_input.0 = sequence;
+    }
+}
+
+fn send_player_states(
+    tick: Res<FixedTick>,
+    player_query: Query<(&PlayerId, &LastInput, &Transform)>,
+    mut player_states_writer: EventWriter<PlayerStates>,
+) {
+    let players: Vec<PlayerState> = player_query.iter()
+        .map(|(&id, last_input, &transform)| PlayerState {
+            id,
+            last_input: last_input.0,
+            transform: CompactTransform::from(transform),
+        })
+        .collect();
+
+    if players.is_empty() {
+        return;
+    }
+
+    player_states_writer.send(PlayerStates { tick: tick.0, players });
+}
+
+pub struct ServerPlayerMovementPlugin;
+
+impl Plugin for ServerPlayerMovementPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, (
+            (queue_player_input, apply_player_input).chain().in_set(FixedUpdateSet::PreUpdate).after(NetworkReceiveSet),
+            send_player_states.in_set(FixedUpdateSet::PostUpdate).before(NetworkSendSet),
+        ));
+    }
+}
\ No newline at end of file
@@ -1,37 +1,72 @@
 use std::net::SocketAddr;
 
-use bevy::utils::HashMap;
-use uflow::SendMode;
-use uflow::server::Server;
-
+use bevy::utils::{HashMap, HashSet};
+use common::batching::OutgoingBatches;
+use common::channels::Channel;
 use common::player::PlayerId;
+use common::protocol::ConnectionRejected;
+use common::transport::ServerTransport;
+use packets::Packet;
+
+// A player that hasn't acknowledged the last chunk of its initial state yet
+#[derive(Default)]
+struct LoadingPlayer {
+    // Known once the last chunk has been sent
+    chunks_sent: Option<u32>,
+    // Handled once the player has loaded, so that nothing it sends in the meantime is lost
+    held_packets: Vec<Packet>,
+}
 
 pub struct ServerState {
-    pub server: Server,
+    pub server: Box<dyn ServerTransport>,
     current_player_id: u8,
     client_addresses: HashMap<PlayerId, SocketAddr>,
-    player_ids: HashMap<SocketAddr, PlayerId>
+    player_ids: HashMap<SocketAddr, PlayerId>,
+    // Clients that have sent a valid handshake but not their hello yet
+    pending_clients: HashSet<SocketAddr>,
+    loading_players: HashMap<PlayerId, LoadingPlayer>,
+    outgoing_batches: HashMap<PlayerId, OutgoingBatches>
 }
 
 impl ServerState {
-    pub fn new(server: Server) -> Self {
-        Self { server, current_player_id: 0, client_addresses: HashMap::new(), player_ids: HashMap::new() }
-    }
-
-    pub fn send_to_player(
-        &mut self,
-        player_id: PlayerId,
-        data: Box<[u8]>,
-        channel_id: usize,
-        send_mode: SendMode
-    ) {
-        if let Some(client_address) = self.client_addresses.get(&player_id) {
-            if let Some(remote_client) = self.server.client(client_address) {
-                remote_client.borrow_mut().send(data, channel_id, send_mode);
+    pub fn new(server: impl ServerTransport + 'static) -> Self {
+        Self {
+            server: Box::new(server),
+            current_player_id: 0,
+            client_addresses: HashMap::new(),
+            player_ids: HashMap::new(),
+            pending_clients: HashSet::new(),
+            loading_players: HashMap::new(),
+            outgoing_batches: HashMap::new()
+        }
+    }
+
+    // Queues the packet to be sent with the rest of the tick's packets by send_batches
+    pub fn send_to_player(&mut self, player_id: PlayerId, packet: &Packet, channel: Channel) {
+        if self.client_addresses.contains_key(&player_id) {
+            self.outgoing_batches.entry(player_id).or_default().push(channel, packet);
+        }
+    }
+
+    pub fn send_batches(&mut self) {
+        for (player_id, outgoing_batches) in self.outgoing_batches.iter_mut() {
+            let Some(&client_address) = self.client_addresses.get(player_id) else { continue };
+
+            for (channel, batch) in outgoing_batches.drain() {
+                self.server.send(client_address, batch, channel);
             }
         }
     }
 
+    // Tells the client why it is being disconnected, then disconnects it once the reason has been delivered
+    pub fn reject_client(&mut self, client_address: SocketAddr, reason: String) {
+        self.pending_clients.remove(&client_address);
+
+        let packet = Packet::from(&ConnectionRejected { reason });
+        self.server.send(client_address, (&packet).into(), Channel::PlayerConnectionEvents);
+        self.server.disconnect(client_address);
+    }
+
     pub fn new_player_id(&mut self) -> PlayerId {
         let id = self.current_player_id;
         self.set_next_id();
@@ -57,6 +92,54 @@ impl ServerState {
         if let Some(address) = self.client_addresses.remove(&player_id) {
             self.player_ids.remove(&address);
         }
+
+        self.outgoing_batches.remove(&player_id);
+        self.loading_players.remove(&player_id);
+    }
+
+    pub fn start_loading(&mut self, player_id: PlayerId) {
+        self.loading_players.insert(player_id, LoadingPlayer::default());
+    }
+
+    pub fn set_chunks_sent(&mut self, player_id: PlayerId, chunks_sent: u32) {
+        if let Some(loading_player) = self.loading_players.get_mut(&player_id) {
+            loading_player.chunks_sent = Some(chunks_sent);
+        }
+    }
+
+    // None until the last chunk has been sent
+    pub fn chunks_sent(&self, player_id: PlayerId) -> Option<u32> {
+        self.loading_players.get(&player_id).and_then(|loading_player| loading_player.chunks_sent)
+    }
+
+    pub fn hold_packet(&mut self, player_id: PlayerId, packet: Packet) {
+        if let Some(loading_player) = self.loading_players.get_mut(&player_id) {
+            loading_player.held_packets.push(packet);
+        }
+    }
+
+    // Returns the packets the player sent while loading, in the order they arrived
+    pub fn finish_loading(&mut self, player_id: PlayerId) -> Vec<Packet> {
+        self.loading_players.remove(&player_id)
+            .map(|loading_player| loading_player.held_packets)
+            .unwrap_or_default()
+    }
+
+    pub fn is_loading(&self, player_id: PlayerId) -> bool {
+        self.loading_players.contains_key(&player_id)
+    }
+
+    pub fn add_pending_client(&mut self, client_address: SocketAddr) {
+        self.pending_clients.insert(client_address);
+    }
+
+    // Returns whether the client was waiting to send its hello
+    pub fn remove_pending_client(&mut self, client_address: SocketAddr) -> bool {
+        self.pending_clients.remove(&client_address)
+    }
+
+    pub fn is_pending(&self, client_address: SocketAddr) -> bool {
+        self.pending_clients.contains(&client_address)
     }
 
     pub fn player_id(&self, client_address: SocketAddr) -> Option<&PlayerId> {
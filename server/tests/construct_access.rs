@@ -0,0 +1,242 @@
+use bevy::prelude::*;
+
+use common::channels::Channel;
+use common::compact_transform::CompactTransform;
+use common::construct_access::{ConstructAccess, ConstructAccessChanged, ConstructRole, GrantConstructRole, RevokeConstructRole};
+use common::network_id::NetworkId;
+use common::network_message::FromPlayer;
+use common::part::{PartHandle, VoxelPos};
+use common::part::events::{PlacePartRequest, DeletePartRequest, DeleteVoxelRequest, VoxelUpdate, BuildRequestRejected, BuildRejectionReason};
+use common::player::{PlayerBundle, PlayerId, PlayerName};
+use common::protocol::{ClientHello, Handshake};
+use common::ship::ShipBundle;
+use common::transport::ClientTransport;
+use common::transport::loopback::{LinkConditions, LoopbackNetwork};
+use packets::Packet;
+use scaffolding::{ServerTest, FixedUpdate};
+use ship_designer_server::network_id_generator::NetworkIdGenerator;
+use ship_designer_server::server_state::ServerState;
+
+mod scaffolding;
+
+fn setup(app: &mut App, access: ConstructAccess) -> (Entity, NetworkId) {
+    let construct_network_id = app.world.resource_mut::<NetworkIdGenerator>().generate();
+    let construct = app.world.spawn(ShipBundle {
+        network_id: construct_network_id,
+        access,
+        ..Default::default()
+    }).id();
+
+    for id in [1, 2] {
+        app.world.spawn(PlayerBundle {
+            id: PlayerId::from(id),
+            transform: TransformBundle::from_transform(Transform::from_xyz(0.0, 2.0 * id as f32, 0.0)),
+            ..Default::default()
+        });
+    }
+
+    (construct, construct_network_id)
+}
+
+fn place_part(app: &mut App, sender: PlayerId, construct_network_id: NetworkId, request_seq: u32) {
+    app.world.send_event(FromPlayer {
+        sender,
+        message: PlacePartRequest {
+            request_seq,
+            part_id: 0.into(),
+            part_transform: CompactTransform::from(Transform::from_xyz(0.0, 0.0, 2.0 * request_seq as f32)),
+            construct_network_id,
+        },
+    });
+    app.fixed_update();
+}
+
+fn delete_part(app: &mut App, sender: PlayerId, part_network_id: NetworkId, request_seq: u32) {
+    app.world.send_event(FromPlayer { sender, message: DeletePartRequest { request_seq, part_network_id } });
+    app.fixed_update();
+}
+
+fn delete_voxel(app: &mut App, sender: PlayerId, part_network_id: NetworkId, voxel_pos: VoxelPos, request_seq: u32) {
+    app.world.send_event(FromPlayer { sender, message: DeleteVoxelRequest { request_seq, part_network_id, voxel_pos } });
+    app.fixed_update();
+}
+
+fn part_count(app: &mut App) -> usize {
+    app.world.query::<&PartHandle>().iter(&app.world).count()
+}
+
+fn rejections(app: &App) -> Vec<BuildRejectionReason> {
+    let rejections = app.world.resource::<Events<BuildRequestRejected>>();
+    let mut reader = rejections.get_reader();
+    reader.iter(rejections).map(|rejection| rejection.reason).collect()
+}
+
+fn last_rejection(app: &App) -> (PlayerId, u32, BuildRejectionReason) {
+    let rejections = app.world.resource::<Events<BuildRequestRejected>>();
+    let mut reader = rejections.get_reader();
+    let rejection = reader.iter(rejections).last().unwrap();
+    (rejection.requester, rejection.request_seq, rejection.reason)
+}
+
+#[test]
+fn only_builders_can_change_construct() {
+    let mut app = App::server_test();
+    let (construct, construct_network_id) = setup(&mut app, ConstructAccess::owned_by(PlayerId::from(1)));
+
+    place_part(&mut app, PlayerId::from(1), construct_network_id, 1);
+    assert_eq!(part_count(&mut app), 1);
+
+    place_part(&mut app, PlayerId::from(2), construct_network_id, 2);
+    assert_eq!(rejections(&app), vec![BuildRejectionReason::NoPermission]);
+    assert_eq!(part_count(&mut app), 1);
+
+    let part_network_id = *app.world.query_filtered::<&NetworkId, With<PartHandle>>().single(&app.world);
+    delete_part(&mut app, PlayerId::from(2), part_network_id, 3);
+    assert_eq!(last_rejection(&app), (PlayerId::from(2), 3, BuildRejectionReason::NoPermission));
+    app.fixed_update();
+
+    assert_eq!(part_count(&mut app), 1);
+    assert_eq!(*app.world.get::<ConstructAccess>(construct).unwrap(), ConstructAccess::owned_by(PlayerId::from(1)));
+}
+
+#[test]
+fn only_builders_can_delete_voxels() {
+    let mut app = App::server_test();
+    let (_, construct_network_id) = setup(&mut app, ConstructAccess::owned_by(PlayerId::from(1)));
+
+    place_part(&mut app, PlayerId::from(1), construct_network_id, 1);
+    let part_network_id = *app.world.query_filtered::<&NetworkId, With<PartHandle>>().single(&app.world);
+
+    delete_voxel(&mut app, PlayerId::from(2), part_network_id, VoxelPos::new(0, 0, 0), 2);
+    assert_eq!(last_rejection(&app), (PlayerId::from(2), 2, BuildRejectionReason::NoPermission));
+
+    delete_voxel(&mut app, PlayerId::from(1), part_network_id, VoxelPos::new(10, 0, 0), 3);
+    assert_eq!(last_rejection(&app), (PlayerId::from(1), 3, BuildRejectionReason::UnknownVoxel));
+
+    delete_voxel(&mut app, PlayerId::from(1), part_network_id, VoxelPos::new(0, 0, 0), 4);
+    let voxel_updates = app.world.resource::<Events<VoxelUpdate>>();
+    let revisions: Vec<(NetworkId, u32)> = voxel_updates.get_reader().iter(voxel_updates)
+        .map(|voxel_update| (voxel_update.network_id, voxel_update.revision))
+        .collect();
+    assert_eq!(revisions, vec![(part_network_id, 1)]);
+}
+
+#[test]
+fn deleting_unknown_part_is_rejected() {
+    let mut app = App::server_test();
+    let (_, construct_network_id) = setup(&mut app, ConstructAccess::owned_by(PlayerId::from(1)));
+
+    place_part(&mut app, PlayerId::from(1), construct_network_id, 1);
+    let part_network_id = *app.world.query_filtered::<&NetworkId, With<PartHandle>>().single(&app.world);
+
+    delete_part(&mut app, PlayerId::from(1), part_network_id, 2);
+    assert_eq!(part_count(&mut app), 0);
+
+    // The part is already gone
+    delete_part(&mut app, PlayerId::from(1), part_network_id, 3);
+    assert_eq!(last_rejection(&app), (PlayerId::from(1), 3, BuildRejectionReason::UnknownPart));
+}
+
+#[test]
+fn building_on_construct_without_owner_does_not_claim_it() {
+    let mut app = App::server_test();
+
+    let (_, construct_network_id) = setup(&mut app, ConstructAccess::default());
+    place_part(&mut app, PlayerId::from(1), construct_network_id, 1);
+    assert_eq!(rejections(&app), vec![BuildRejectionReason::NoPermission]);
+
+    let mut app = App::server_test();
+
+    let (construct, construct_network_id) = setup(&mut app, ConstructAccess::open());
+    place_part(&mut app, PlayerId::from(1), construct_network_id, 1);
+    place_part(&mut app, PlayerId::from(2), construct_network_id, 2);
+    assert_eq!(part_count(&mut app), 2);
+    assert_eq!(app.world.get::<ConstructAccess>(construct).unwrap().owner(), None);
+}
+
+#[test]
+fn owners_grant_and_revoke_roles() {
+    let mut app = App::server_test();
+    let (construct, construct_network_id) = setup(&mut app, ConstructAccess::owned_by(PlayerId::from(1)));
+
+    // Only the owner can hand out roles
+    app.world.send_event(FromPlayer {
+        sender: PlayerId::from(2),
+        message: GrantConstructRole { construct_network_id, player_id: PlayerId::from(2), role: ConstructRole::Builder },
+    });
+    app.fixed_update();
+    assert_eq!(app.world.get::<ConstructAccess>(construct).unwrap().role(PlayerId::from(2)), ConstructRole::Viewer);
+
+    app.world.send_event(FromPlayer {
+        sender: PlayerId::from(1),
+        message: GrantConstructRole { construct_network_id, player_id: PlayerId::from(2), role: ConstructRole::Builder },
+    });
+    app.fixed_update();
+
+    place_part(&mut app, PlayerId::from(2), construct_network_id, 1);
+    assert_eq!(part_count(&mut app), 1);
+
+    app.world.send_event(FromPlayer {
+        sender: PlayerId::from(1),
+        message: RevokeConstructRole { construct_network_id, player_id: PlayerId::from(2) },
+    });
+    app.fixed_update();
+
+    place_part(&mut app, PlayerId::from(2), construct_network_id, 2);
+    assert_eq!(rejections(&app), vec![BuildRejectionReason::NoPermission]);
+    assert_eq!(part_count(&mut app), 1);
+}
+
+#[test]
+fn roles_are_only_granted_to_players_in_the_game() {
+    let mut app = App::server_test();
+    let (construct, construct_network_id) = setup(&mut app, ConstructAccess::owned_by(PlayerId::from(1)));
+
+    for role in [ConstructRole::Builder, ConstructRole::Owner] {
+        app.world.send_event(FromPlayer {
+            sender: PlayerId::from(1),
+            message: GrantConstructRole { construct_network_id, player_id: PlayerId::from(3), role },
+        });
+        app.fixed_update();
+    }
+
+    let access = app.world.get::<ConstructAccess>(construct).unwrap();
+    assert_eq!(access.owner(), Some(PlayerId::from(1)));
+    assert_eq!(access.role(PlayerId::from(3)), ConstructRole::Viewer);
+    assert!(app.world.resource::<Events<ConstructAccessChanged>>().is_empty());
+}
+
+#[test]
+fn disconnecting_player_loses_its_roles() {
+    let mut app = App::server_test();
+    app.update();
+
+    let network = LoopbackNetwork::new(LinkConditions::default(), 1);
+    app.world.insert_non_send_resource(ServerState::new(network.server()));
+
+    let mut client = network.connect();
+    let _ = client.step();
+
+    client.send((&Packet::from(&Handshake::default())).into(), Channel::PlayerConnectionEvents);
+    let hello = ClientHello { name: PlayerName::from("Player".to_string()) };
+    client.send((&Packet::from(&hello)).into(), Channel::PlayerConnectionEvents);
+
+    for _ in 0..3 {
+        app.fixed_update();
+    }
+
+    let player_id = *app.world.query::<&PlayerId>().single(&app.world);
+    let mut access = ConstructAccess::owned_by(PlayerId::from(200));
+    access.grant(player_id, ConstructRole::Builder);
+    let construct = app.world.spawn(ShipBundle { access, ..Default::default() }).id();
+
+    client.disconnect();
+    client.flush();
+
+    for _ in 0..3 {
+        app.fixed_update();
+    }
+
+    assert_eq!(app.world.query::<&PlayerId>().iter(&app.world).len(), 0);
+    assert_eq!(app.world.get::<ConstructAccess>(construct).unwrap().role(player_id), ConstructRole::Viewer);
+}
\ No newline at end of file
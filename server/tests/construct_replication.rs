@@ -0,0 +1,32 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use common::construct_replication::ConstructStates;
+use common::ship::ShipBundle;
+use scaffolding::{ServerTest, FixedUpdate};
+use ship_designer_server::network_id_generator::NetworkIdGenerator;
+
+mod scaffolding;
+
+#[test]
+fn construct_states_are_sent() {
+    let mut app = App::server_test();
+
+    let construct_network_id = app.world.get_resource_mut::<NetworkIdGenerator>().unwrap().generate();
+    app.world.spawn(ShipBundle {
+        network_id: construct_network_id,
+        velocity: Velocity::linear(Vec3::X),
+        ..Default::default()
+    });
+
+    app.fixed_update();
+
+    let events = app.world.resource::<Events<ConstructStates>>();
+    let mut reader = events.get_reader();
+    let construct_states: Vec<_> = reader.iter(events).collect();
+
+    assert_eq!(construct_states.len(), 1);
+    assert_eq!(construct_states[0].constructs.len(), 1);
+    assert_eq!(construct_states[0].constructs[0].network_id, construct_network_id);
+    assert_eq!(construct_states[0].constructs[0].linear_velocity, Vec3::X);
+}
\ No newline at end of file
@@ -0,0 +1,136 @@
+use bevy::ecs::event::ManualEventReader;
+use bevy::prelude::*;
+
+use common::compact_transform::CompactTransform;
+use common::construct_access::ConstructAccess;
+use common::network_id::NetworkId;
+use common::network_message::FromPlayer;
+use common::part::PartHandle;
+use common::part::events::PlacePartRequest;
+use common::player::{PlayerBundle, PlayerId, PlayerName};
+use common::player_connection::{PlayerConnected, InitialState, InitialStateChunk};
+use common::ship::ShipBundle;
+use scaffolding::{ServerTest, FixedUpdate};
+use ship_designer_server::network_id_generator::NetworkIdGenerator;
+
+mod scaffolding;
+
+fn spawn_construct(app: &mut App, translation: Vec3, part_count: u32) {
+    let construct_network_id = app.world.resource_mut::<NetworkIdGenerator>().generate();
+    app.world.spawn(ShipBundle {
+        transform: TransformBundle::from_transform(Transform::from_translation(translation)),
+        network_id: construct_network_id,
+        access: ConstructAccess::open(),
+        ..Default::default()
+    });
+
+    for request_seq in 0..part_count {
+        app.world.send_event(FromPlayer {
+            sender: PlayerId::from(1),
+            message: PlacePartRequest {
+                request_seq,
+                part_id: 0.into(),
+                part_transform: CompactTransform::from(Transform::from_xyz(0.0, 0.0, 2.0 * request_seq as f32)),
+                construct_network_id,
+            },
+        });
+    }
+}
+
+fn connect_player(app: &mut App) {
+    app.world.spawn(PlayerBundle {
+        id: PlayerId::from(1),
+        ..Default::default()
+    });
+    app.fixed_update();
+
+    app.world.send_event(PlayerConnected {
+        id: PlayerId::from(1),
+        name: PlayerName::from("Player".to_string()),
+        transform: Transform::default(),
+    });
+}
+
+struct StreamedChunk {
+    construct_network_id: Option<NetworkId>,
+    parts: Vec<NetworkId>,
+    last: bool,
+}
+
+// Steps until the last chunk has been sent, and returns the initial state's construct and part counts with every chunk
+fn stream_initial_state(app: &mut App) -> (usize, u32, Vec<StreamedChunk>) {
+    let mut initial_state_reader = ManualEventReader::<InitialState>::default();
+    let mut chunk_reader = ManualEventReader::<InitialStateChunk>::default();
+    let mut counts = None;
+    let mut chunks: Vec<StreamedChunk> = Vec::new();
+
+    for _ in 0..60 {
+        app.fixed_update();
+
+        let initial_states = app.world.resource::<Events<InitialState>>();
+        for initial_state in initial_state_reader.iter(initial_states) {
+            counts = Some((initial_state.constructs.len(), initial_state.part_count));
+        }
+
+        let events = app.world.resource::<Events<InitialStateChunk>>();
+        for chunk in chunk_reader.iter(events) {
+            chunks.push(StreamedChunk {
+                construct_network_id: chunk.construct_network_id,
+                parts: chunk.parts.iter().map(|(_, _, network_id)| *network_id).collect(),
+                last: chunk.last,
+            });
+        }
+
+        if matches!(chunks.last(), Some(chunk) if chunk.last) {
+            break;
+        }
+    }
+
+    let (construct_count, part_count) = counts.unwrap();
+    (construct_count, part_count, chunks)
+}
+
+#[test]
+fn player_is_sent_last_chunk_without_constructs() {
+    let mut app = App::server_test();
+    connect_player(&mut app);
+
+    let (construct_count, part_count, chunks) = stream_initial_state(&mut app);
+    assert_eq!((construct_count, part_count), (0, 0));
+
+    assert_eq!(chunks.len(), 1);
+    assert!(chunks[0].last);
+    assert_eq!(chunks[0].construct_network_id, None);
+    assert!(chunks[0].parts.is_empty());
+}
+
+#[test]
+fn every_construct_is_streamed() {
+    let mut app = App::server_test();
+    spawn_construct(&mut app, Vec3::ZERO, 3);
+    spawn_construct(&mut app, Vec3::new(5.0, 0.0, 0.0), 2);
+    spawn_construct(&mut app, Vec3::new(-5.0, 0.0, 0.0), 0);
+    connect_player(&mut app);
+
+    let (construct_count, part_count, chunks) = stream_initial_state(&mut app);
+    assert_eq!((construct_count, part_count), (3, 5));
+
+    assert!(chunks.last().unwrap().last);
+    assert_eq!(chunks.iter().filter(|chunk| chunk.last).count(), 1);
+
+    // Each part arrives once, in a chunk for the construct it is on
+    let mut part_query = app.world.query::<(&NetworkId, &Parent)>();
+    let mut streamed_parts = 0;
+    for chunk in chunks.iter() {
+        for part_network_id in chunk.parts.iter() {
+            let (_, parent) = part_query.iter(&app.world)
+                .find(|(network_id, _)| *network_id == part_network_id)
+                .unwrap();
+            assert_eq!(app.world.get::<NetworkId>(parent.get()).copied(), chunk.construct_network_id);
+            streamed_parts += 1;
+        }
+    }
+
+    assert_eq!(streamed_parts, 5);
+    assert_eq!(app.world.query::<&PartHandle>().iter(&app.world).count(), 5);
+}
\ No newline at end of file
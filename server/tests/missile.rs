@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use bevy_rapier3d::rapier::geometry::CollisionEventFlags;
+
+use common::compact_transform::CompactTransform;
+use common::construct_access::ConstructAccess;
+use common::missile::{Missile, MissileBundle};
+use common::network_id::NetworkId;
+use common::network_message::FromPlayer;
+use common::part::{PartHandle, Parts};
+use common::part::colliders::PartCollider;
+use common::part::events::{PlacePartRequest, DeletePartRequest, VoxelUpdate};
+use common::part::materials::Material;
+use common::player::{PlayerBundle, PlayerId};
+use common::ship::ShipBundle;
+use scaffolding::{ServerTest, FixedUpdate};
+use ship_designer_server::missile::Shooter;
+use ship_designer_server::network_id_generator::NetworkIdGenerator;
+
+mod scaffolding;
+
+// Spawns a construct owned by player 1 with the parts on it, and player 2 who has no role on it
+fn setup(app: &mut App, parts: &[(u32, Vec3)]) {
+    let construct_network_id = app.world.resource_mut::<NetworkIdGenerator>().generate();
+    app.world.spawn(ShipBundle {
+        network_id: construct_network_id,
+        access: ConstructAccess::owned_by(PlayerId::from(1)),
+        ..Default::default()
+    });
+
+    for id in [1, 2] {
+        app.world.spawn(PlayerBundle {
+            id: PlayerId::from(id),
+            transform: TransformBundle::from_transform(Transform::from_xyz(0.0, 2.0, 0.0)),
+            ..Default::default()
+        });
+    }
+
+    for (request_seq, &(part_id, translation)) in parts.iter().enumerate() {
+        app.world.send_event(FromPlayer {
+            sender: PlayerId::from(1),
+            message: PlacePartRequest {
+                request_seq: request_seq as u32,
+                part_id: part_id.into(),
+                part_transform: CompactTransform::from(Transform::from_translation(translation)),
+                construct_network_id,
+            },
+        });
+    }
+    app.fixed_update();
+}
+
+// The missile explodes in the next tick
+fn explode_missile(app: &mut App, shooter: PlayerId, translation: Vec3) {
+    let missile_transform = Transform::from_translation(translation);
+    let missile_network_id = app.world.resource_mut::<NetworkIdGenerator>().generate();
+    let missile = app.world.spawn(MissileBundle {
+        missile: Missile::new(5.0),
+        network_id: missile_network_id,
+        transform: TransformBundle {
+            local: missile_transform,
+            global: GlobalTransform::from(missile_transform),
+        },
+        collider: Collider::cuboid(0.25, 0.25, 0.25),
+        ..Default::default()
+    }).insert(Shooter(shooter)).id();
+    let part_collider = app.world.query_filtered::<Entity, With<PartCollider>>().iter(&app.world).next().unwrap();
+
+    app.world.send_event(CollisionEvent::Started(missile, part_collider, CollisionEventFlags::SENSOR));
+}
+
+fn part_network_ids(app: &mut App) -> Vec<NetworkId> {
+    app.world.query_filtered::<&NetworkId, With<PartHandle>>().iter(&app.world).copied().collect()
+}
+
+fn damaged_part_count(app: &mut App) -> usize {
+    let mut part_query = app.world.query::<&PartHandle>();
+    let parts = app.world.resource::<Parts>();
+
+    part_query.iter(&app.world)
+        .filter(|part_handle| parts.get(part_handle).unwrap().voxels().contains(&Material::Empty))
+        .count()
+}
+
+#[test]
+fn part_deleted_in_the_tick_a_missile_hits_it() {
+    let mut app = App::server_test();
+
+    // A cube the missile only damages and a small prism next to it that the missile destroys
+    setup(&mut app, &[(0, Vec3::ZERO), (1, Vec3::new(0.0, 0.0, 0.8))]);
+
+    let parts = part_network_ids(&mut app);
+    assert_eq!(parts.len(), 2);
+
+    // The missile hits in the same tick the player deletes both parts
+    explode_missile(&mut app, PlayerId::from(1), Vec3::new(0.0, 0.3, 0.8));
+    for (request_seq, part_network_id) in parts.into_iter().enumerate() {
+        let message = DeletePartRequest { request_seq: request_seq as u32, part_network_id };
+        app.world.send_event(FromPlayer { sender: PlayerId::from(1), message });
+    }
+
+    app.fixed_update();
+    app.fixed_update();
+
+    assert!(part_network_ids(&mut app).is_empty());
+    assert!(app.world.query::<&PartCollider>().iter(&app.world).next().is_none());
+}
+
+#[test]
+fn missiles_only_damage_constructs_the_shooter_can_build_on() {
+    let mut app = App::server_test();
+    setup(&mut app, &[(0, Vec3::ZERO)]);
+
+    explode_missile(&mut app, PlayerId::from(2), Vec3::new(0.0, 0.8, 0.0));
+    app.fixed_update();
+
+    assert_eq!(damaged_part_count(&mut app), 0);
+    assert!(app.world.resource::<Events<VoxelUpdate>>().is_empty());
+
+    explode_missile(&mut app, PlayerId::from(1), Vec3::new(0.0, 0.8, 0.0));
+    app.fixed_update();
+
+    assert_eq!(damaged_part_count(&mut app), 1);
+}
\ No newline at end of file
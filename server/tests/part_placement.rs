@@ -1,17 +1,19 @@
 use bevy::prelude::*;
 
-use common::part::events::{PlacePartRequest, PlacePartCommand};
+use common::part::MAX_BUILD_DISTANCE;
+use common::part::events::{PlacePartRequest, PlacePartCommand, BuildRequestRejected, BuildRejectionReason};
 use common::compact_transform::CompactTransform;
+use common::construct_access::ConstructAccess;
+use common::network_id::NetworkId;
+use common::network_message::FromPlayer;
+use common::player::{PlayerBundle, PlayerId};
 use common::ship::ShipBundle;
 use scaffolding::{ServerTest, FixedUpdate};
 use ship_designer_server::network_id_generator::NetworkIdGenerator;
 
 mod scaffolding;
 
-#[test]
-fn cannot_place_overlapping_parts() {
-    let mut app = App::server_test();
-
+fn spawn_construct(app: &mut App) -> NetworkId {
     let construct_network_id = app.world.get_resource_mut::<NetworkIdGenerator>().unwrap().generate();
     app.world.spawn(ShipBundle {
         transform: TransformBundle::from_transform(Transform {
@@ -20,64 +22,159 @@ fn cannot_place_overlapping_parts() {
             scale: Vec3::splat(1.0)
         }),
         network_id: construct_network_id,
+        access: ConstructAccess::open(),
+        ..Default::default()
+    });
+
+    construct_network_id
+}
+
+fn spawn_player(app: &mut App, player_id: PlayerId, translation: Vec3) {
+    app.world.spawn(PlayerBundle {
+        id: player_id,
+        transform: TransformBundle::from_transform(Transform::from_translation(translation)),
         ..Default::default()
     });
+}
+
+fn send_request(app: &mut App, sender: PlayerId, place_part_request: PlacePartRequest) {
+    app.world
+        .get_resource_mut::<Events<FromPlayer<PlacePartRequest>>>()
+        .unwrap()
+        .send(FromPlayer { sender, message: place_part_request });
+}
+
+fn rejections(app: &App) -> Vec<(PlayerId, u32, BuildRejectionReason)> {
+    let rejections = app.world.resource::<Events<BuildRequestRejected>>();
+    let mut reader = rejections.get_reader();
+    reader.iter(rejections)
+        .map(|rejection| (rejection.requester, rejection.request_seq, rejection.reason))
+        .collect()
+}
+
+#[test]
+fn cannot_place_overlapping_parts() {
+    let mut app = App::server_test();
+
+    let construct_network_id = spawn_construct(&mut app);
+    spawn_player(&mut app, PlayerId::from(1), Vec3::new(0.0, 10.0, 0.0));
 
     let place_part_request = PlacePartRequest {
+        request_seq: 1,
         part_id: 0.into(),
         part_transform: CompactTransform::from(Transform::from_xyz(0.0, 0.0, 0.0)),
-        construct_network_id: construct_network_id,
+        construct_network_id,
     };
 
-    app.world
-        .get_resource_mut::<Events<PlacePartRequest>>()
-        .unwrap()
-        .send(place_part_request.clone());
-    app.world
-        .get_resource_mut::<Events<PlacePartRequest>>()
-        .unwrap()
-        .send(place_part_request);
+    send_request(&mut app, PlayerId::from(1), place_part_request.clone());
+    send_request(&mut app, PlayerId::from(1), PlacePartRequest { request_seq: 2, ..place_part_request });
 
     app.fixed_update();
 
     assert_eq!(app.world.get_resource::<Events<PlacePartCommand>>().unwrap().len(), 1);
+    assert_eq!(rejections(&app), vec![(PlayerId::from(1), 2, BuildRejectionReason::Overlap)]);
 }
 
 #[test]
 fn can_place_non_overlapping_parts() {
     let mut app = App::server_test();
 
-    let construct_network_id = app.world.get_resource_mut::<NetworkIdGenerator>().unwrap().generate();
-    app.world.spawn(ShipBundle {
-        transform: TransformBundle::from_transform(Transform {
-            translation: Vec3::splat(0.0),
-            rotation: Quat::IDENTITY,
-            scale: Vec3::splat(1.0)
-        }),
-        network_id: construct_network_id,
-        ..Default::default()
-    });
+    let construct_network_id = spawn_construct(&mut app);
+    // Each part is placed by a player standing next to it
+    spawn_player(&mut app, PlayerId::from(1), Vec3::new(0.0, 10.0, 0.0));
+    spawn_player(&mut app, PlayerId::from(2), Vec3::new(100.0, 60.0, 50.0));
 
     let place_part_request_1 = PlacePartRequest {
+        request_seq: 1,
         part_id: 0.into(),
         part_transform: CompactTransform::from(Transform::from_xyz(0.0, 0.0, 0.0)),
-        construct_network_id: construct_network_id,
+        construct_network_id,
     };
     let place_part_request_2 = PlacePartRequest {
+        request_seq: 2,
         part_transform: CompactTransform::from(Transform::from_xyz(100.0, 50.0, 50.0)),
         ..place_part_request_1
     };
 
-    app.world
-        .get_resource_mut::<Events<PlacePartRequest>>()
-        .unwrap()
-        .send(place_part_request_1);
-    app.world
-        .get_resource_mut::<Events<PlacePartRequest>>()
-        .unwrap()
-        .send(place_part_request_2);
+    send_request(&mut app, PlayerId::from(1), place_part_request_1);
+    send_request(&mut app, PlayerId::from(2), place_part_request_2);
 
     app.fixed_update();
 
     assert_eq!(app.world.get_resource::<Events<PlacePartCommand>>().unwrap().len(), 2);
+    assert!(rejections(&app).is_empty());
+}
+
+#[test]
+fn invalid_requests_are_rejected() {
+    let mut app = App::server_test();
+
+    let construct_network_id = spawn_construct(&mut app);
+    spawn_player(&mut app, PlayerId::from(1), Vec3::new(0.0, 10.0, 0.0));
+
+    let place_part_request = PlacePartRequest {
+        request_seq: 1,
+        part_id: 0.into(),
+        part_transform: CompactTransform::from(Transform::from_xyz(0.0, 0.0, 0.0)),
+        construct_network_id,
+    };
+
+    send_request(&mut app, PlayerId::from(1), PlacePartRequest {
+        request_seq: 1,
+        construct_network_id: NetworkId::from(1000),
+        ..place_part_request.clone()
+    });
+    send_request(&mut app, PlayerId::from(1), PlacePartRequest {
+        request_seq: 2,
+        part_id: 1000.into(),
+        ..place_part_request.clone()
+    });
+    send_request(&mut app, PlayerId::from(1), PlacePartRequest {
+        request_seq: 3,
+        part_transform: CompactTransform::from(Transform::from_xyz(1000.0, 0.0, 0.0)),
+        ..place_part_request.clone()
+    });
+    // Player 2 isn't in the game
+    send_request(&mut app, PlayerId::from(2), PlacePartRequest {
+        request_seq: 4,
+        ..place_part_request
+    });
+
+    app.fixed_update();
+
+    assert_eq!(app.world.get_resource::<Events<PlacePartCommand>>().unwrap().len(), 0);
+    assert_eq!(rejections(&app), vec![
+        (PlayerId::from(1), 1, BuildRejectionReason::UnknownConstruct),
+        (PlayerId::from(1), 2, BuildRejectionReason::UnknownPart),
+        (PlayerId::from(1), 3, BuildRejectionReason::OutOfRange),
+        (PlayerId::from(2), 4, BuildRejectionReason::NoPermission),
+    ]);
+}
+
+#[test]
+fn parts_out_of_build_range_are_rejected() {
+    let mut app = App::server_test();
+
+    let construct_network_id = spawn_construct(&mut app);
+    spawn_player(&mut app, PlayerId::from(1), Vec3::new(0.0, 10.0, 0.0));
+
+    // The player is at (0, 10, 0)
+    let place_part_request = PlacePartRequest {
+        request_seq: 1,
+        part_id: 0.into(),
+        part_transform: CompactTransform::from(Transform::from_xyz(0.0, 10.0, MAX_BUILD_DISTANCE - 1.0)),
+        construct_network_id,
+    };
+
+    send_request(&mut app, PlayerId::from(1), place_part_request.clone());
+    send_request(&mut app, PlayerId::from(1), PlacePartRequest {
+        request_seq: 2,
+        part_transform: CompactTransform::from(Transform::from_xyz(0.0, 10.0, -MAX_BUILD_DISTANCE - 1.0)),
+        ..place_part_request
+    });
+
+    app.fixed_update();
+
+    assert_eq!(app.world.get_resource::<Events<PlacePartCommand>>().unwrap().len(), 1);
+    assert_eq!(rejections(&app), vec![(PlayerId::from(1), 2, BuildRejectionReason::OutOfRange)]);
 }
\ No newline at end of file
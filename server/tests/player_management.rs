@@ -1,11 +1,28 @@
 use bevy::prelude::*;
-use common::player::PlayerId;
+use common::player::{PlayerId, PlayerName};
 use scaffolding::{ServerTest, FixedUpdate};
 use ship_designer_server::server_state::ServerState;
+use common::channels::Channel;
+use common::protocol::{ClientHello, Handshake, PROTOCOL_FINGERPRINT};
+use common::transport::ClientTransport;
+use common::transport::loopback::{LinkConditions, LoopbackNetwork};
+use packets::Packet;
+use uflow::SendMode;
 use uflow::client::{Client, Config};
 
 mod scaffolding;
 
+fn send_handshake(client: &mut Client, handshake: Handshake) {
+    client.send((&Packet::from(&handshake)).into(), Channel::PlayerConnectionEvents.into(), SendMode::Reliable);
+    let _ = client.step();
+}
+
+fn send_hello(client: &mut Client, name: &str) {
+    let hello = ClientHello { name: PlayerName::from(name.to_string()) };
+    client.send((&Packet::from(&hello)).into(), Channel::PlayerConnectionEvents.into(), SendMode::Reliable);
+    let _ = client.step();
+}
+
 #[test]
 fn connecting_player_gets_created() {
     let mut app = App::server_test();
@@ -18,7 +35,9 @@ fn connecting_player_gets_created() {
     let mut client = Client::connect(server_address, Config::default()).expect("Failed to connect to server!");
     app.fixed_update();
     let _ = client.step();
-    
+
+    send_handshake(&mut client, Handshake::default());
+    send_hello(&mut client, "Player");
     app.fixed_update();
 
     let mut player_id_query = app.world.query::<&PlayerId>();
@@ -37,7 +56,9 @@ fn disconnecting_player_gets_removed() {
     let mut client = Client::connect(server_address, Config::default()).expect("Failed to connect to server!");
     app.fixed_update();
     let _ = client.step();
-    
+
+    send_handshake(&mut client, Handshake::default());
+    send_hello(&mut client, "Player");
     app.fixed_update();
 
     client.disconnect();
@@ -46,4 +67,69 @@ fn disconnecting_player_gets_removed() {
 
     let mut player_id_query = app.world.query::<&PlayerId>();
     assert!(player_id_query.iter(&mut app.world).len() == 0);
+}
+
+#[test]
+fn mismatched_protocol_is_rejected() {
+    let mut app = App::server_test();
+
+    app.update();
+
+    let mut server_address = app.world.get_non_send_resource_mut::<ServerState>().unwrap().server.address();
+    server_address.set_ip(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST));
+    
+    let mut client = Client::connect(server_address, Config::default()).expect("Failed to connect to server!");
+    app.fixed_update();
+    let _ = client.step();
+
+    send_handshake(&mut client, Handshake { fingerprint: !PROTOCOL_FINGERPRINT });
+    app.fixed_update();
+
+    let mut player_id_query = app.world.query::<&PlayerId>();
+    assert_eq!(player_id_query.iter(&mut app.world).len(), 0);
+}
+
+#[test]
+fn player_connecting_over_loopback_gets_created() {
+    let mut app = App::server_test();
+
+    app.update();
+
+    let network = LoopbackNetwork::new(LinkConditions { latency: 2, jitter: 1, ..Default::default() }, 1);
+    app.world.insert_non_send_resource(ServerState::new(network.server()));
+
+    let mut client = network.connect();
+    let _ = client.step();
+
+    client.send((&Packet::from(&Handshake::default())).into(), Channel::PlayerConnectionEvents);
+    let hello = ClientHello { name: PlayerName::from("Player".to_string()) };
+    client.send((&Packet::from(&hello)).into(), Channel::PlayerConnectionEvents);
+
+    // The handshake and hello take a few steps to arrive
+    for _ in 0..5 {
+        app.fixed_update();
+    }
+
+    let mut player_id_query = app.world.query::<&PlayerId>();
+    assert_eq!(player_id_query.iter(&mut app.world).len(), 1);
+}
+
+#[test]
+fn mismatched_protocol_over_loopback_is_disconnected() {
+    let mut app = App::server_test();
+
+    app.update();
+
+    let network = LoopbackNetwork::new(LinkConditions::default(), 1);
+    app.world.insert_non_send_resource(ServerState::new(network.server()));
+
+    let mut client = network.connect();
+    let _ = client.step();
+
+    client.send((&Packet::from(&Handshake { fingerprint: !PROTOCOL_FINGERPRINT })).into(), Channel::PlayerConnectionEvents);
+    app.fixed_update();
+    app.fixed_update();
+
+    let events = client.step();
+    assert!(matches!(events.last(), Some(uflow::client::Event::Disconnect)));
 }
\ No newline at end of file
@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+
+use common::network_message::IncomingPackets;
+use common::player::{PlayerBundle, PlayerId};
+use common::player_movement::{PlayerInput, PlayerStates, MAX_MOVE_IMPULSE};
+use packets::Packet;
+use scaffolding::{ServerTest, FixedUpdate};
+use ship_designer_server::player_movement::{LastInput, PendingInputs};
+
+mod scaffolding;
+
+fn send_input(app: &mut App, sender: PlayerId, input: &PlayerInput) {
+    app.world.resource_mut::<IncomingPackets>().push(Some(sender), Packet::from(input));
+}
+
+#[test]
+fn player_input_moves_sender() {
+    let mut app = App::server_test();
+
+    let player_id = PlayerId::from(1);
+    let player = app.world.spawn(PlayerBundle { id: player_id, ..Default::default() })
+        .insert((LastInput::default(), PendingInputs::default()))
+        .id();
+    app.fixed_update();
+
+    let input = PlayerInput { sequence: 1, impulse: Vec3::X * MAX_MOVE_IMPULSE, torque_impulse: Vec3::ZERO };
+    send_input(&mut app, player_id, &input);
+    app.fixed_update();
+
+    assert_eq!(app.world.get::<LastInput>(player).unwrap().0, 1);
+    assert!(app.world.get::<Transform>(player).unwrap().translation.x > 0.0);
+
+    let events = app.world.resource::<Events<PlayerStates>>();
+    let mut reader = events.get_reader();
+    let player_states = reader.iter(events).last().unwrap();
+    assert_eq!(player_states.players.len(), 1);
+    assert_eq!(player_states.players[0].last_input, 1);
+}
+
+#[test]
+fn stale_input_is_ignored() {
+    let mut app = App::server_test();
+
+    let player_id = PlayerId::from(1);
+    let player = app.world.spawn(PlayerBundle { id: player_id, ..Default::default() })
+        .insert((LastInput(5), PendingInputs::default()))
+        .id();
+    app.fixed_update();
+
+    let input = PlayerInput { sequence: 4, impulse: Vec3::X * MAX_MOVE_IMPULSE, torque_impulse: Vec3::ZERO };
+    send_input(&mut app, player_id, &input);
+    app.fixed_update();
+
+    assert_eq!(app.world.get::<LastInput>(player).unwrap().0, 5);
+    assert_eq!(app.world.get::<Transform>(player).unwrap().translation, Vec3::ZERO);
+}
+
+#[test]
+fn one_input_is_applied_per_tick() {
+    let mut app = App::server_test();
+
+    let player_id = PlayerId::from(1);
+    let player = app.world.spawn(PlayerBundle { id: player_id, ..Default::default() })
+        .insert((LastInput::default(), PendingInputs::default()))
+        .id();
+    app.fixed_update();
+
+    // Sending several inputs in one tick doesn't move the player any faster, the rest wait for the following ticks
+    for sequence in 1..=3 {
+        let input = PlayerInput { sequence, impulse: Vec3::X * MAX_MOVE_IMPULSE, torque_impulse: Vec3::ZERO };
+        send_input(&mut app, player_id, &input);
+    }
+
+    for sequence in 1..=3 {
+        app.fixed_update();
+        assert_eq!(app.world.get::<LastInput>(player).unwrap().0, sequence);
+    }
+}
\ No newline at end of file